@@ -0,0 +1,132 @@
+//! Configuration file for the Aqon CLI.
+//! The file holds `key = value` lines like a flat TOML file. Values may be quoted,
+//! and lines starting with `#` are comments. A comment may also follow a value, so an
+//! unquoted value ends at the first ` #`; quote values that contain one, such as
+//! `footer = "Page # {n}"`. Command line options take precedence.
+
+use std::path::Path;
+use anyhow::{Result, Context};
+use log::debug;
+
+use crate::converter::{parse_line_spacing, PdfOptions, MIN_FONT_SIZE};
+
+/// Settings read from a configuration file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    /// Font family name or directory of TTF/OTF files used for PDF output
+    pub font: Option<String>,
+    /// Language tag of the locale used to display numbers and dates
+    pub locale: Option<String>,
+    /// Page layout and text settings of PDF output
+    pub pdf: PdfOptions,
+}
+
+impl Config {
+    /// Reads a configuration file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the configuration file
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Config>` - The settings or an error
+    pub fn load(path: &Path) -> Result<Config> {
+        debug!("Reading configuration file: {}", path.display());
+        let text = std::fs::read_to_string(path)
+            .context(format!("Failed to read configuration file: {}", path.display()))?;
+        Config::parse(&text)
+            .context(format!("Invalid configuration file: {}", path.display()))
+    }
+
+    /// Parses the contents of a configuration file
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The `key = value` lines of the file
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Config>` - The settings or an error naming the offending line
+    pub fn parse(text: &str) -> Result<Config> {
+        let mut config = Config::default();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line.split_once('=')
+                .context(format!("Expected `key = value` on line {}", index + 1))?;
+            let value = parse_value(value.trim())
+                .context(format!("Invalid value on line {}", index + 1))?;
+
+            let invalid = || format!("Invalid value on line {}: {}", index + 1, value);
+            match key.trim() {
+                "font" => config.font = Some(value.clone()),
+                "locale" => config.locale = Some(value.clone()),
+                "paper_size" => config.pdf.paper_size = Some(value.parse().with_context(invalid)?),
+                "orientation" => config.pdf.orientation = Some(value.parse().with_context(invalid)?),
+                "margins" => config.pdf.margins = Some(value.parse().with_context(invalid)?),
+                "font_size" => {
+                    let font_size: u8 = value.parse().with_context(invalid)?;
+                    if font_size < MIN_FONT_SIZE {
+                        anyhow::bail!("Font size on line {} must be at least {} pt: {}", index + 1, MIN_FONT_SIZE, value);
+                    }
+                    config.pdf.font_size = Some(font_size);
+                },
+                "line_spacing" => config.pdf.line_spacing = Some(parse_line_spacing(&value).with_context(invalid)?),
+                "header" => config.pdf.header = Some(value.clone()),
+                "footer" => config.pdf.footer = Some(value.clone()),
+                "toc" => config.pdf.table_of_contents = value.parse().with_context(invalid)?,
+                "pdf_a" => config.pdf.pdf_a = value.parse().with_context(invalid)?,
+                key => anyhow::bail!("Unknown configuration key on line {}: {}", index + 1, key),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Returns the text of a value, removing quotes and trailing comments
+///
+/// # Arguments
+///
+/// * `value` - The value as written after the `=`
+///
+/// # Returns
+///
+/// * `Result<String>` - The value text or an error for an unterminated quote or text after the closing quote
+fn parse_value(value: &str) -> Result<String> {
+    let Some(quote) = value.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+        // Unquoted values end at a comment
+        let value = value.split_once(" #").map_or(value, |(value, _)| value);
+        return Ok(value.trim().to_string());
+    };
+
+    let mut text = String::new();
+    let mut chars = value[1..].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c == quote => {
+                // Only a comment may follow the closing quote
+                let rest = chars.as_str().trim_start();
+                if !rest.is_empty() && !rest.starts_with('#') {
+                    anyhow::bail!("Unexpected text after closing quote: {}", value);
+                }
+                return Ok(text);
+            },
+            // Double-quoted values support backslash escapes, as in TOML basic strings
+            '\\' if quote == '"' => match chars.next() {
+                Some('n') => text.push('\n'),
+                Some('t') => text.push('\t'),
+                Some(escaped) => text.push(escaped),
+                None => break,
+            },
+            c => text.push(c),
+        }
+    }
+
+    anyhow::bail!("Missing closing quote: {}", value)
+}
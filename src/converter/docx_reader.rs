@@ -1,17 +1,296 @@
 //! Module for reading and extracting content from Word (.docx) documents.
 
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
 use std::path::Path;
 use anyhow::{Result, Context};
-use docx_rs::{DocumentChild, ParagraphChild, RunChild, TableChild, TableRowChild, TableCellContent};
+use docx_rs::{
+    DocumentChild, DrawingData, FieldCharType, HyperlinkData, ParagraphChild, RunChild, StructuredDataTagChild,
+    TableChild, TableRowChild, TableCellContent,
+};
 use log::{info, debug, warn};
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+
+use crate::converter::options::{PageMargins, PageSetup};
+use crate::converter::package::read_package_part;
+use crate::converter::table::{Table, TableCell};
+
+/// Outline levels at or above this value mark body text rather than headings
+const BODY_TEXT_OUTLINE_LEVEL: usize = 9;
+
+/// Font name fragments that identify a monospace font
+const MONOSPACE_FONT_HINTS: [&str; 6] = ["courier", "consolas", "mono", "menlo", "lucida console", "code"];
+
+/// Bookmark Word adds to remember the last edit position; it is never a link target
+const LAST_EDIT_BOOKMARK: &str = "_GoBack";
+
+/// Package part holding the document body
+const DOCUMENT_PART: &str = "word/document.xml";
+
+/// Package part holding the numbering definitions
+const NUMBERING_PART: &str = "word/numbering.xml";
+
+/// Package part holding the styles
+const STYLES_PART: &str = "word/styles.xml";
+
+/// Package part holding the footnotes
+const FOOTNOTES_PART: &str = "word/footnotes.xml";
+
+/// Package part holding the endnotes
+const ENDNOTES_PART: &str = "word/endnotes.xml";
+
+/// Start and end of the placeholder text that replaces note references before parsing
+///
+/// docx-rs drops `w:footnoteReference` and `w:endnoteReference` elements, so they are
+/// turned into text (e.g. "\u{E000}f2\u{E001}" for footnote 2) that survives in the run.
+const NOTE_REFERENCE_START: char = '\u{E000}';
+const NOTE_REFERENCE_END: char = '\u{E001}';
+
+/// Placeholder for the page number in header and footer text
+///
+/// Private use characters cannot be typed as text, so a literal `{n}` in a header is kept.
+pub const PAGE_NUMBER_PLACEHOLDER: &str = "\u{E002}";
+
+/// Placeholder for the number of pages in header and footer text
+pub const PAGE_COUNT_PLACEHOLDER: &str = "\u{E003}";
+
+/// English Metric Units per millimetre, the unit of drawing sizes
+const EMU_PER_MM: f64 = 36000.0;
+
+/// Twentieths of a point (twips) per millimetre, the unit of page sizes and margins
+const TWIPS_PER_MM: f64 = 1440.0 / 25.4;
+
+/// Vertical position of a run relative to the baseline
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VerticalAlign {
+    /// Regular text on the baseline
+    #[default]
+    Baseline,
+    /// Raised, smaller text
+    Superscript,
+    /// Lowered, smaller text
+    Subscript,
+}
+
+/// Character formatting applied to a run of text
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunFormat {
+    /// Bold text
+    pub bold: bool,
+    /// Italic text
+    pub italic: bool,
+    /// Underlined text
+    pub underline: bool,
+    /// Struck-through text
+    pub strike: bool,
+    /// Text set in a monospace font
+    pub monospace: bool,
+    /// Superscript or subscript position
+    pub vertical_align: VerticalAlign,
+}
+
+/// Character formatting set directly on a run or by a character style
+///
+/// docx-rs keeps these values private, so they are read from the XML of the
+/// document and styles parts. Unset values are inherited from the style.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct RunProperties {
+    /// ID of the character style (`w:rStyle`)
+    style: Option<String>,
+    bold: Option<bool>,
+    italic: Option<bool>,
+    underline: Option<bool>,
+    strike: Option<bool>,
+    monospace: Option<bool>,
+    vertical_align: Option<VerticalAlign>,
+}
+
+impl RunProperties {
+    /// Reads an element of a run properties block (`w:rPr`) into the properties
+    ///
+    /// # Arguments
+    ///
+    /// * `element` - A child element of `w:rPr`
+    /// * `reader` - The reader of the part, used to decode attribute values
+    fn read_element<R>(&mut self, element: &BytesStart, reader: &quick_xml::Reader<R>) {
+        let value = attribute_value(element, b"val", reader);
+        // Toggles without a value switch the property on
+        let toggle = || !matches!(value.as_deref(), Some("false" | "0" | "off"));
+
+        match element.local_name().as_ref() {
+            b"rStyle" => self.style = value.clone(),
+            b"b" => self.bold = Some(toggle()),
+            b"i" => self.italic = Some(toggle()),
+            b"strike" => self.strike = Some(toggle()),
+            b"u" => self.underline = Some(value.as_deref() != Some("none")),
+            b"vertAlign" => self.vertical_align = match value.as_deref() {
+                Some("superscript") => Some(VerticalAlign::Superscript),
+                Some("subscript") => Some(VerticalAlign::Subscript),
+                _ => Some(VerticalAlign::Baseline),
+            },
+            b"rFonts" => {
+                if let Some(font) = attribute_value(element, b"ascii", reader) {
+                    let font = font.to_lowercase();
+                    self.monospace = Some(MONOSPACE_FONT_HINTS.iter().any(|hint| font.contains(hint)));
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Target of a hyperlink
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LinkTarget {
+    /// External URL, resolved through the document relationships
+    Url(String),
+    /// Bookmark inside the document
+    Anchor(String),
+}
+
+/// A run of text sharing the same formatting
+#[derive(Debug, Clone, Default)]
+pub struct Run {
+    /// Text of the run
+    pub text: String,
+    /// Formatting of the run
+    pub format: RunFormat,
+    /// Hyperlink the run belongs to
+    pub link: Option<LinkTarget>,
+    /// Index into `DocxContent::notes` when the run is a footnote or endnote reference
+    pub note: Option<usize>,
+}
+
+/// Bullet used for list levels whose symbol cannot be displayed as text
+const DEFAULT_BULLET: &str = "•";
+
+/// Position of a paragraph in a numbered or bulleted list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListItem {
+    /// Numbering instance (`w:numId`); consecutive items with the same ID belong to one list
+    pub num_id: usize,
+    /// Zero-based nesting level
+    pub level: usize,
+    /// Whether the level is numbered rather than bulleted
+    pub ordered: bool,
+    /// Counter value of the item at its level
+    pub number: usize,
+    /// Marker as displayed by Word (e.g. "1.", "a)", "1.2." or "•")
+    pub marker: String,
+}
+
+/// A paragraph together with its style information
+#[derive(Debug, Clone, Default)]
+pub struct Paragraph {
+    /// Formatted runs making up the paragraph text
+    pub runs: Vec<Run>,
+    /// Style ID from the paragraph properties (e.g. "Heading1" or "Title")
+    pub style_id: Option<String>,
+    /// Zero-based outline level, from the paragraph itself or its style
+    pub outline_level: Option<usize>,
+    /// List membership, for numbered and bulleted paragraphs
+    pub list: Option<ListItem>,
+    /// Names of the bookmarks pointing at the paragraph
+    pub bookmarks: Vec<String>,
+}
+
+impl Paragraph {
+    /// Returns the plain text of the paragraph without formatting
+    pub fn text(&self) -> String {
+        self.runs.iter().map(|run| run.text.as_str()).collect()
+    }
+
+    /// Returns the one-based heading level, or `None` for body text
+    pub fn heading_level(&self) -> Option<usize> {
+        self.outline_level.map(|level| level + 1)
+    }
+}
+
+/// An image embedded in the document
+#[derive(Debug, Clone, Default)]
+pub struct Image {
+    /// File name of the image in the package media (e.g. "image1.png")
+    pub name: String,
+    /// Image data as stored in the package
+    pub data: Vec<u8>,
+    /// Display width in millimetres
+    pub width_mm: f64,
+    /// Display height in millimetres
+    pub height_mm: f64,
+    /// Alternative text (`descr` of the drawing properties)
+    pub alt_text: Option<String>,
+}
+
+/// Kind of a note referenced from the document body
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NoteKind {
+    /// A footnote, shown at the bottom of the page in Word
+    Footnote,
+    /// An endnote, shown at the end of the document in Word
+    Endnote,
+}
+
+/// A footnote or endnote
+#[derive(Debug, Clone)]
+pub struct Note {
+    /// Whether this is a footnote or an endnote
+    pub kind: NoteKind,
+    /// Label shown at the reference and before the note (e.g. "1" or "iv")
+    pub label: String,
+    /// Paragraphs of the note text
+    pub paragraphs: Vec<String>,
+}
+
+/// A block-level element of the document body
+#[derive(Debug, Clone)]
+pub enum Block {
+    /// A paragraph of text
+    Paragraph(Paragraph),
+    /// A table, with cells that may span several rows and columns
+    Table(Table),
+    /// An image, placed after the text of the paragraph containing it
+    Image(Image),
+}
 
 /// Represents the content extracted from a Word document
 #[derive(Debug, Default)]
 pub struct DocxContent {
-    /// Paragraphs of text from the document
-    pub paragraphs: Vec<String>,
-    /// Tables extracted from the document
-    pub tables: Vec<Vec<Vec<String>>>, // Tables -> Rows -> Cells
+    /// Paragraphs and tables in the order they appear in the document body
+    pub blocks: Vec<Block>,
+    /// Lines of the page header, with page numbers as `PAGE_NUMBER_PLACEHOLDER` and page counts as `PAGE_COUNT_PLACEHOLDER`
+    pub header: Vec<String>,
+    /// Lines of the page footer, with page numbers as `PAGE_NUMBER_PLACEHOLDER` and page counts as `PAGE_COUNT_PLACEHOLDER`
+    pub footer: Vec<String>,
+    /// Footnotes and endnotes in the order they are first referenced
+    pub notes: Vec<Note>,
+    /// Page size and margins of the last section
+    pub page_setup: Option<PageSetup>,
+}
+
+impl DocxContent {
+    /// Returns an iterator over the paragraphs of the document
+    pub fn paragraphs(&self) -> impl Iterator<Item = &Paragraph> {
+        self.blocks.iter().filter_map(|block| match block {
+            Block::Paragraph(paragraph) => Some(paragraph),
+            _ => None,
+        })
+    }
+
+    /// Returns an iterator over the tables of the document
+    pub fn tables(&self) -> impl Iterator<Item = &Table> {
+        self.blocks.iter().filter_map(|block| match block {
+            Block::Table(table) => Some(table),
+            _ => None,
+        })
+    }
+
+    /// Returns an iterator over the images of the document
+    pub fn images(&self) -> impl Iterator<Item = &Image> {
+        self.blocks.iter().filter_map(|block| match block {
+            Block::Image(image) => Some(image),
+            _ => None,
+        })
+    }
 }
 
 /// Extracts content from a Word document
@@ -29,86 +308,1368 @@ pub fn extract_content(path: &Path) -> Result<DocxContent> {
     let buf = std::fs::read(path)
         .context(format!("Failed to read file: {}", path.display()))?;
 
-    let docx = docx_rs::read_docx(&buf)
+    let marked = mark_note_references(&buf)
+        .context("Failed to prepare note references")?;
+    let docx = docx_rs::read_docx(marked.as_deref().unwrap_or(&buf))
         .context("Failed to parse DOCX file")?;
 
+    let styles = docx.styles;
+    // docx-rs keeps the start values and level texts of numbering levels private
+    let levels = match read_package_part(&buf, NUMBERING_PART) {
+        Ok(xml) => xml.as_deref().map(numbering_levels).unwrap_or_default(),
+        Err(err) => {
+            warn!("Failed to read numbering definitions: {:#}", err);
+            HashMap::new()
+        }
+    };
+    let mut lists = ListCounters::new(&docx.numberings, &levels);
+    let relationships: HashMap<String, String> = docx.hyperlinks.iter()
+        .map(|(id, target, _)| (id.clone(), target.clone()))
+        .collect();
+    // docx-rs only keeps images it can decode, keyed by relationship ID
+    let media: HashMap<&str, (&str, &[u8])> = docx.images.iter()
+        .map(|(id, path, image, _)| (id.as_str(), (path.as_str(), image.0.as_slice())))
+        .collect();
+    // Image descriptions, repeated table headers and cell spans are not kept by docx-rs
+    let document_xml = read_package_part(&buf, DOCUMENT_PART).unwrap_or_else(|err| {
+        warn!("Failed to read document body: {:#}", err);
+        None
+    });
+    let descriptions = document_xml.as_deref().map(image_descriptions).unwrap_or_default();
+    let table_layouts = document_xml.as_deref().map(table_grid_layouts).unwrap_or_default();
+    // docx-rs keeps run formatting private, so it is read from the document and styles parts
+    let run_properties = document_xml.as_deref().map(paragraph_run_properties);
+    let character_styles = match read_package_part(&buf, STYLES_PART) {
+        Ok(xml) => xml.as_deref().map(style_run_properties).unwrap_or_default(),
+        Err(err) => {
+            warn!("Failed to read styles: {:#}", err);
+            HashMap::new()
+        }
+    };
+    let mut paragraph_index = 0;
+    let mut table_index = 0;
+    let mut notes = NoteCollector::new(&buf);
     let document = docx.document;
     let mut content = DocxContent::default();
+    // Bookmarks on empty paragraphs move to the next paragraph with text
+    let mut bookmarks: Vec<String> = Vec::new();
 
-    // Process document body
-    for child in document.children {
+    // Process document body, keeping paragraphs and tables in order
+    for child in &document.children {
         match child {
             DocumentChild::Paragraph(paragraph) => {
-                let mut paragraph_text = String::new();
-
-                for child in paragraph.children {
-                    if let ParagraphChild::Run(run) = child {
-                        for child in run.children {
-                            if let RunChild::Text(text) = child {
-                                paragraph_text.push_str(&text.text);
-                            }
-                        }
+                // Formatting is matched to the runs by position, so it is only used when the run counts agree
+                let properties = run_properties.as_ref().map(|paragraphs| {
+                    let properties = paragraphs.get(paragraph_index).map(Vec::as_slice).unwrap_or_default();
+                    let run_count = paragraph_runs(paragraph).len();
+                    if properties.len() == run_count {
+                        properties
+                    } else {
+                        warn!(
+                            "Found formatting for {} runs but {} runs in paragraph {}; showing its runs without formatting",
+                            properties.len(), run_count, paragraph_index + 1,
+                        );
+                        &[]
                     }
-                }
+                }).unwrap_or_default();
+                paragraph_index += 1;
+                let runs = extract_runs(paragraph, properties, &character_styles, &relationships, &mut notes);
+                bookmarks.extend(paragraph.children.iter()
+                    .filter_map(|child| match child {
+                        ParagraphChild::BookmarkStart(bookmark) if bookmark.name != LAST_EDIT_BOOKMARK => {
+                            Some(bookmark.name.clone())
+                        },
+                        _ => None,
+                    }));
+                let paragraph_text: String = runs.iter().map(|run| run.text.as_str()).collect();
 
                 if !paragraph_text.trim().is_empty() {
                     debug!("Extracted paragraph: {}", paragraph_text);
-                    content.paragraphs.push(paragraph_text);
+                    let style_id = paragraph.property.style.as_ref()
+                        .map(|style| style.val.clone());
+                    let outline_level = paragraph.property.outline_lvl.as_ref()
+                        .map(|level| level.v)
+                        .or_else(|| style_id.as_ref().and_then(|id| style_outline_level(&styles, id)))
+                        .filter(|level| *level < BODY_TEXT_OUTLINE_LEVEL);
+
+                    let list = paragraph.property.numbering_property.as_ref()
+                        .or_else(|| style_id.as_ref()
+                            .and_then(|id| styles.find_style_by_id(id))
+                            .and_then(|style| style.paragraph_property.numbering_property.as_ref()))
+                        .and_then(|numbering| lists.next_item(numbering));
+
+                    content.blocks.push(Block::Paragraph(Paragraph {
+                        runs,
+                        style_id,
+                        outline_level,
+                        list,
+                        bookmarks: std::mem::take(&mut bookmarks),
+                    }));
+                }
+
+                for image in extract_images(paragraph, &media, &descriptions) {
+                    debug!("Extracted image: {} ({:.0} x {:.0} mm)", image.name, image.width_mm, image.height_mm);
+                    content.blocks.push(Block::Image(image));
                 }
             },
             DocumentChild::Table(table) => {
-                let mut table_data = Vec::new();
-
-                for row_child in &table.rows {
-                    if let TableChild::TableRow(row) = row_child {
-                        let mut row_data = Vec::new();
-
-                        for cell_child in &row.cells {
-                            if let TableRowChild::TableCell(cell) = cell_child {
-                                let mut cell_text = String::new();
-
-                                for content in &cell.children {
-                                    // Match on TableCellContent instead of DocumentChild
-                                    if let docx_rs::TableCellContent::Paragraph(paragraph) = content {
-                                        for child in &paragraph.children {
-                                            if let ParagraphChild::Run(run) = child {
-                                                for child in &run.children {
-                                                    if let RunChild::Text(text) = child {
-                                                        cell_text.push_str(&text.text);
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-
-                                row_data.push(cell_text);
-                            }
+                let layout = table_layouts.get(table_index).map(Vec::as_slice).unwrap_or_default();
+                let mut table = extract_table(table, layout, &mut notes);
+                table.header_rows = layout.iter().take_while(|row| row.header).count().min(table.rows.len());
+                table_index += 1;
+
+                if !table.is_empty() {
+                    debug!("Extracted table with {} rows and {} columns", table.rows.len(), table.column_count());
+                    content.blocks.push(Block::Table(table));
+                }
+            },
+            _ => {
+                // Ignore other document elements
+            }
+        }
+    }
+
+    // Headers and footers of the last section apply to the whole converted document
+    let section = &document.section_property;
+    content.header = section.header.as_ref().or(section.first_header.as_ref())
+        .map(|(_, header)| header.children.iter().flat_map(|child| match child {
+            docx_rs::HeaderChild::Paragraph(paragraph) => vec![field_text(paragraph)],
+            docx_rs::HeaderChild::Table(table) => table_lines(table),
+            docx_rs::HeaderChild::StructuredDataTag(tag) => tag_lines(tag),
+        }).collect())
+        .unwrap_or_default();
+    content.footer = section.footer.as_ref().or(section.first_footer.as_ref())
+        .map(|(_, footer)| footer.children.iter().flat_map(|child| match child {
+            docx_rs::FooterChild::Paragraph(paragraph) => vec![field_text(paragraph)],
+            docx_rs::FooterChild::Table(table) => table_lines(table),
+            docx_rs::FooterChild::StructuredDataTag(tag) => tag_lines(tag),
+        }).collect())
+        .unwrap_or_default();
+    content.header.retain(|line| !line.trim().is_empty());
+    content.footer.retain(|line| !line.trim().is_empty());
+    content.page_setup = document_xml.as_deref().and_then(|xml| section_page_setup(section, xml));
+
+    content.notes = notes.notes;
+
+    info!("Extracted {} paragraphs, {} tables, {} images and {} notes from document",
+          content.paragraphs().count(), content.tables().count(), content.images().count(), content.notes.len());
+
+    if content.blocks.is_empty() {
+        warn!("No content extracted from document");
+    }
+
+    Ok(content)
+}
+
+/// Returns the page size and margins of a section
+///
+/// # Arguments
+///
+/// * `section` - The section properties
+/// * `xml` - The XML of the document body
+///
+/// # Returns
+///
+/// * `Option<PageSetup>` - The page setup in millimetres, or `None` if the page size is missing
+fn section_page_setup(section: &docx_rs::SectionProperty, xml: &str) -> Option<PageSetup> {
+    // docx-rs keeps the page size private, so it is read from the XML
+    let (width, height) = section_page_size(xml)?;
+    let width = width / TWIPS_PER_MM;
+    let height = height / TWIPS_PER_MM;
+
+    // Negative top and bottom margins keep the text from moving away from headers and footers
+    let margin = |twips: i32| f64::from(twips.unsigned_abs()) / TWIPS_PER_MM;
+    let page_margin = &section.page_margin;
+    let margins = PageMargins {
+        top: margin(page_margin.top),
+        right: margin(page_margin.right),
+        bottom: margin(page_margin.bottom),
+        left: margin(page_margin.left) + margin(page_margin.gutter),
+    };
+
+    Some(PageSetup { width, height, margins })
+}
+
+/// Reads the page size of the final section of the document body
+///
+/// # Arguments
+///
+/// * `xml` - The XML of the document body
+///
+/// # Returns
+///
+/// * `Option<(f64, f64)>` - The page width and height in twips, or `None` if either is missing
+fn section_page_size(xml: &str) -> Option<(f64, f64)> {
+    let mut size = None;
+
+    visit_elements(xml, "section properties", |event, path, reader| {
+        if let XmlEvent::Start(element) = event
+            && element.local_name().as_ref() == b"pgSz"
+            && path_ends_with(path, &[b"body", b"sectPr"]) {
+            let dimension = |name: &[u8]| attribute_value(element, name, reader)
+                .and_then(|value| value.parse::<f64>().ok())
+                .filter(|value| *value > 0.0);
+            size = dimension(b"w").zip(dimension(b"h"));
+        }
+    });
+
+    size
+}
+
+/// Start value and level text of a level of an abstract numbering definition
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct NumberingLevel {
+    /// First counter value (`w:start`)
+    start: Option<usize>,
+    /// Marker template such as "%1." or the bullet character (`w:lvlText`)
+    text: Option<String>,
+}
+
+/// Tracks list counters while walking the document, following the numbering definitions
+struct ListCounters<'a> {
+    numberings: &'a docx_rs::Numberings,
+    /// Start values and level texts, keyed by abstract numbering ID and level
+    levels: &'a HashMap<(usize, usize), NumberingLevel>,
+    /// Current counter values for each level, keyed by numbering instance
+    counters: HashMap<usize, Vec<Option<usize>>>,
+}
+
+impl<'a> ListCounters<'a> {
+    /// Creates counters for the numbering definitions of a document
+    fn new(numberings: &'a docx_rs::Numberings, levels: &'a HashMap<(usize, usize), NumberingLevel>) -> Self {
+        ListCounters {
+            numberings,
+            levels,
+            counters: HashMap::new(),
+        }
+    }
+
+    /// Advances the counters for a list paragraph and returns its list item
+    ///
+    /// # Arguments
+    ///
+    /// * `property` - The numbering property of the paragraph
+    ///
+    /// # Returns
+    ///
+    /// * `Option<ListItem>` - The list item, or `None` if the paragraph is not part of a list
+    fn next_item(&mut self, property: &docx_rs::NumberingProperty) -> Option<ListItem> {
+        let num_id = property.id.as_ref()?.id;
+        let level = property.level.as_ref().map_or(0, |level| level.val);
+
+        // Numbering ID 0 explicitly removes the paragraph from a list
+        if num_id == 0 {
+            return None;
+        }
+
+        let numbering = self.numberings.numberings.iter().find(|n| n.id == num_id)?;
+        let abstract_num = self.numberings.abstract_nums.iter()
+            .find(|a| a.id == numbering.abstract_num_id)?;
+        let definition = |lvl: usize| abstract_num.levels.iter().find(|l| l.level == lvl);
+        let level_text = |lvl: usize| self.levels.get(&(abstract_num.id, lvl)).and_then(|l| l.text.clone());
+        let start = |lvl: usize| {
+            numbering.level_overrides.iter()
+                .find(|o| o.level == lvl)
+                .and_then(|o| o.override_start)
+                .or_else(|| self.levels.get(&(abstract_num.id, lvl)).and_then(|l| l.start))
+                .unwrap_or(1)
+        };
+
+        let counters = self.counters.entry(num_id).or_default();
+        if counters.len() <= level {
+            counters.resize(level + 1, None);
+        }
+        let number = counters[level].map_or_else(|| start(level), |n| n + 1);
+        counters[level] = Some(number);
+        // Deeper levels restart when a shallower item appears
+        counters.truncate(level + 1);
+
+        let format = definition(level).map_or("decimal", |d| d.format.val.as_str());
+        let ordered = format != "bullet" && format != "none";
+        let marker = if ordered {
+            let template = level_text(level)
+                .unwrap_or_else(|| format!("%{}.", level + 1));
+            let mut marker = template;
+            for (lvl, value) in counters.iter().enumerate() {
+                let lvl_format = definition(lvl).map_or("decimal", |d| d.format.val.as_str());
+                let value = value.unwrap_or_else(|| start(lvl));
+                marker = marker.replace(&format!("%{}", lvl + 1), &format_number(value, lvl_format));
+            }
+            marker
+        } else {
+            bullet_symbol(level_text(level))
+        };
+
+        Some(ListItem {
+            num_id,
+            level,
+            ordered,
+            number,
+            marker,
+        })
+    }
+}
+
+/// Reads the start values and level texts of the abstract numbering definitions
+///
+/// # Arguments
+///
+/// * `xml` - The XML of the numbering part
+///
+/// # Returns
+///
+/// * `HashMap<(usize, usize), NumberingLevel>` - The levels, keyed by abstract numbering ID and level
+fn numbering_levels(xml: &str) -> HashMap<(usize, usize), NumberingLevel> {
+    let mut levels = HashMap::new();
+    let mut abstract_id: Option<usize> = None;
+    let mut level: Option<(usize, NumberingLevel)> = None;
+
+    visit_elements(xml, "numbering definitions", |event, path, reader| {
+        let element = match event {
+            XmlEvent::Start(element) => element,
+            XmlEvent::End(name) => {
+                if name == b"lvl" && let (Some(id), Some((lvl, definition))) = (abstract_id, level.take()) {
+                    levels.insert((id, lvl), definition);
+                }
+                return;
+            },
+        };
+        let number = |key: &[u8]| attribute_value(element, key, reader).and_then(|value| value.parse::<usize>().ok());
+        match element.local_name().as_ref() {
+            b"abstractNum" if path_ends_with(path, &[b"numbering"]) => abstract_id = number(b"abstractNumId"),
+            b"lvl" if path_ends_with(path, &[b"numbering", b"abstractNum"]) => {
+                level = number(b"ilvl").map(|lvl| (lvl, NumberingLevel::default()));
+            },
+            b"start" if path_ends_with(path, &[b"abstractNum", b"lvl"]) => {
+                if let Some((_, definition)) = level.as_mut() {
+                    definition.start = number(b"val");
+                }
+            },
+            b"lvlText" if path_ends_with(path, &[b"abstractNum", b"lvl"]) => {
+                if let Some((_, definition)) = level.as_mut() {
+                    definition.text = attribute_value(element, b"val", reader);
+                }
+            },
+            _ => {}
+        }
+    });
+
+    levels
+}
+
+/// Formats a list counter using a Word number format
+///
+/// # Arguments
+///
+/// * `value` - The counter value
+/// * `format` - The `w:numFmt` value (e.g. "decimal", "lowerLetter", "upperRoman")
+///
+/// # Returns
+///
+/// * `String` - The formatted counter
+fn format_number(value: usize, format: &str) -> String {
+    match format {
+        "lowerLetter" => letter_number(value).to_lowercase(),
+        "upperLetter" => letter_number(value),
+        "lowerRoman" => roman_number(value).to_lowercase(),
+        "upperRoman" => roman_number(value),
+        "decimalZero" => format!("{:02}", value),
+        "none" => String::new(),
+        _ => value.to_string(),
+    }
+}
+
+/// Formats a counter as letters the way Word does (A, B, ..., Z, AA, BB, ...)
+fn letter_number(value: usize) -> String {
+    if value == 0 {
+        return String::new();
+    }
+    let letter = (b'A' + ((value - 1) % 26) as u8) as char;
+    letter.to_string().repeat((value - 1) / 26 + 1)
+}
+
+/// Formats a counter as an upper-case Roman numeral
+fn roman_number(mut value: usize) -> String {
+    const NUMERALS: [(usize, &str); 13] = [
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+        (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+
+    let mut roman = String::new();
+    for (number, numeral) in NUMERALS {
+        while value >= number {
+            roman.push_str(numeral);
+            value -= number;
+        }
+    }
+    roman
+}
+
+/// Maps the level text of a bulleted list to a displayable bullet
+///
+/// Word bullets are usually glyphs from the Symbol or Wingdings fonts, stored
+/// as private-use characters that have no meaning outside those fonts.
+fn bullet_symbol(text: Option<String>) -> String {
+    match text.as_deref() {
+        Some("o") => "◦".to_string(),
+        Some("§") | Some("\u{f0a7}") => "▪".to_string(),
+        Some(text) if !text.is_empty() && !text.chars().any(|c| ('\u{e000}'..='\u{f8ff}').contains(&c)) => {
+            text.to_string()
+        },
+        _ => DEFAULT_BULLET.to_string(),
+    }
+}
+
+/// Looks up the outline level conferred by a paragraph style
+///
+/// Uses the outline level defined on the style when present, and otherwise
+/// recognises the built-in "Title" and "heading N" styles by name or ID.
+///
+/// # Arguments
+///
+/// * `styles` - The styles part of the document
+/// * `style_id` - ID of the paragraph style
+///
+/// # Returns
+///
+/// * `Option<usize>` - Zero-based outline level, or `None` if the style is not a heading
+fn style_outline_level(styles: &docx_rs::Styles, style_id: &str) -> Option<usize> {
+    if let Some(style) = styles.find_style_by_id(style_id) {
+        if let Some(level) = &style.paragraph_property.outline_lvl {
+            return Some(level.v);
+        }
+        if style.name.is_heading() {
+            return style.name.get_heading_number().map(|n| n.saturating_sub(1));
+        }
+        if style.name.starts_with("Title") {
+            return Some(0);
+        }
+    }
+
+    // Documents without a styles part still use the built-in style IDs
+    if style_id == "Title" {
+        return Some(0);
+    }
+    style_id.strip_prefix("Heading")
+        .and_then(|n| n.parse::<usize>().ok())
+        .map(|n| n.saturating_sub(1))
+}
+
+/// Extracts the formatted runs of a paragraph, merging neighbours with equal formatting
+///
+/// # Arguments
+///
+/// * `paragraph` - The paragraph to read
+/// * `properties` - Formatting set directly on each run of the paragraph, in order
+/// * `styles` - Formatting of the character styles, keyed by style ID
+/// * `relationships` - Hyperlink targets of the document, keyed by relationship ID
+/// * `notes` - Collector for the footnotes and endnotes referenced by the runs
+///
+/// # Returns
+///
+/// * `Vec<Run>` - The runs of the paragraph in order
+fn extract_runs(
+    paragraph: &docx_rs::Paragraph,
+    properties: &[RunProperties],
+    styles: &HashMap<String, RunProperties>,
+    relationships: &HashMap<String, String>,
+    notes: &mut NoteCollector,
+) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+    // Runs are matched with their properties by position, as both follow the document order
+    let mut properties = properties.iter();
+    let mut next_format = || run_format(properties.next(), styles);
+
+    for child in &paragraph.children {
+        match child {
+            ParagraphChild::Run(run) => push_run(&mut runs, run, None, next_format(), notes),
+            ParagraphChild::Hyperlink(hyperlink) => {
+                let link = match &hyperlink.link {
+                    HyperlinkData::External { rid, .. } => {
+                        let target = relationships.get(rid).cloned().map(LinkTarget::Url);
+                        if target.is_none() {
+                            warn!("Hyperlink relationship not found: {}", rid);
                         }
+                        target
+                    },
+                    HyperlinkData::Anchor { anchor } => Some(LinkTarget::Anchor(anchor.clone())),
+                };
+
+                for child in &hyperlink.children {
+                    if let ParagraphChild::Run(run) = child {
+                        push_run(&mut runs, run, link.clone(), next_format(), notes);
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+
+    runs
+}
+
+/// Appends the text of a run, merging it into the previous run when formatting and link match
+///
+/// Note reference placeholders in the text become separate runs pointing at their note.
+///
+/// # Arguments
+///
+/// * `runs` - The runs extracted so far
+/// * `run` - The run to append
+/// * `link` - The hyperlink target of the run, if any
+/// * `format` - The formatting of the run
+/// * `notes` - Collector for the footnotes and endnotes referenced by the run
+fn push_run(
+    runs: &mut Vec<Run>,
+    run: &docx_rs::Run,
+    link: Option<LinkTarget>,
+    format: RunFormat,
+    notes: &mut NoteCollector,
+) {
+    let mut text = String::new();
+    for child in &run.children {
+        if let RunChild::Text(t) = child {
+            text.push_str(&t.text);
+        }
+    }
+
+    if text.is_empty() {
+        return;
+    }
 
-                        if !row_data.is_empty() {
-                            table_data.push(row_data);
+    for segment in text_segments(&text) {
+        match segment {
+            TextSegment::Text(text) => match runs.last_mut() {
+                Some(last) if last.format == format && last.link == link && last.note.is_none() => {
+                    last.text.push_str(text)
+                },
+                _ => runs.push(Run { text: text.to_string(), format: format.clone(), link: link.clone(), note: None }),
+            },
+            TextSegment::Note(kind, id) => {
+                let index = notes.reference(kind, id);
+                runs.push(Run {
+                    text: notes.notes[index].label.clone(),
+                    format: format.clone(),
+                    link: link.clone(),
+                    note: Some(index),
+                });
+            }
+        }
+    }
+}
+
+/// Resolves the formatting of a run from its direct properties and character style
+///
+/// # Arguments
+///
+/// * `property` - The properties set directly on the run, if any
+/// * `styles` - Formatting of the character styles, keyed by style ID
+///
+/// # Returns
+///
+/// * `RunFormat` - The effective formatting of the run
+fn run_format(property: Option<&RunProperties>, styles: &HashMap<String, RunProperties>) -> RunFormat {
+    let direct = property.cloned().unwrap_or_default();
+    let style = direct.style.as_ref().and_then(|id| styles.get(id)).cloned().unwrap_or_default();
+
+    // Direct formatting wins over the character style
+    let flag = |direct: Option<bool>, from_style: Option<bool>| direct.or(from_style).unwrap_or(false);
+
+    RunFormat {
+        bold: flag(direct.bold, style.bold),
+        italic: flag(direct.italic, style.italic),
+        underline: flag(direct.underline, style.underline),
+        strike: flag(direct.strike, style.strike),
+        monospace: flag(direct.monospace, style.monospace),
+        vertical_align: direct.vertical_align.or(style.vertical_align).unwrap_or_default(),
+    }
+}
+
+/// Extracts the images drawn inline in the runs of a paragraph
+///
+/// # Arguments
+///
+/// * `paragraph` - The paragraph to read
+/// * `media` - Image paths and data of the package, keyed by relationship ID
+/// * `descriptions` - Alternative texts, keyed by relationship ID
+///
+/// # Returns
+///
+/// * `Vec<Image>` - The images of the paragraph in order
+fn extract_images(
+    paragraph: &docx_rs::Paragraph,
+    media: &HashMap<&str, (&str, &[u8])>,
+    descriptions: &HashMap<String, String>,
+) -> Vec<Image> {
+    let mut images = Vec::new();
+    for run in paragraph_runs(paragraph) {
+        for child in &run.children {
+            let RunChild::Drawing(drawing) = child else {
+                continue;
+            };
+            let Some(DrawingData::Pic(pic)) = &drawing.data else {
+                continue;
+            };
+
+            match media.get(pic.id.as_str()) {
+                Some((path, data)) => images.push(Image {
+                    name: path.rsplit('/').next().unwrap_or(path).to_string(),
+                    data: data.to_vec(),
+                    width_mm: f64::from(pic.size.0) / EMU_PER_MM,
+                    height_mm: f64::from(pic.size.1) / EMU_PER_MM,
+                    alt_text: descriptions.get(&pic.id).cloned(),
+                }),
+                None => warn!("Skipping image in unsupported format: {}", pic.id),
+            }
+        }
+    }
+
+    images
+}
+
+/// Collects the alternative text of the drawings in a document part
+///
+/// docx-rs does not read drawing properties, so the `descr` attribute of each
+/// `wp:docPr` is paired with the relationship ID of the `a:blip` that follows it.
+///
+/// # Arguments
+///
+/// * `xml` - The XML of the document part
+///
+/// # Returns
+///
+/// * `HashMap<String, String>` - Alternative texts keyed by image relationship ID
+fn image_descriptions(xml: &str) -> HashMap<String, String> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut descriptions = HashMap::new();
+    let mut description: Option<String> = None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(element)) | Ok(Event::Empty(element)) => {
+                let attribute = |name: &[u8]| attribute_value(&element, name, &reader)
+                    .map(|value| value.trim().to_string())
+                    .filter(|value| !value.is_empty());
+
+                match element.local_name().as_ref() {
+                    b"docPr" => description = attribute(b"descr"),
+                    b"blip" => {
+                        if let (Some(id), Some(text)) = (attribute(b"embed"), description.take()) {
+                            descriptions.entry(id).or_insert(text);
+                        }
+                    },
+                    _ => {}
+                }
+            },
+            Ok(Event::Eof) => break,
+            Err(err) => {
+                warn!("Failed to parse drawing properties: {}", err);
+                break;
+            },
+            _ => {}
+        }
+    }
+
+    descriptions
+}
+
+/// Role of a table cell in a vertical merge (`w:vMerge`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VerticalMerge {
+    /// The cell starts a merge with the cells below it
+    Restart,
+    /// The cell continues the merge of the cell above it
+    Continue,
+}
+
+/// Grid placement of a table cell, as read from the XML
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct CellLayout {
+    /// Number of grid columns the cell covers (`w:gridSpan`)
+    col_span: usize,
+    /// Vertical merge the cell takes part in, if any
+    vertical_merge: Option<VerticalMerge>,
+}
+
+/// Grid placement of the cells of a table row, as read from the XML
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct RowLayout {
+    /// Number of grid columns before the first cell (`w:gridBefore`)
+    grid_before: usize,
+    /// Whether the row is flagged to repeat as a header on every page (`w:tblHeader`)
+    header: bool,
+    /// Placement of each cell, in order
+    cells: Vec<CellLayout>,
+}
+
+/// Reads the column spans and vertical merges of the cells and the header flags of the rows of each body table
+///
+/// docx-rs keeps these row and cell properties private. Tables nested in other
+/// tables are not read.
+///
+/// # Arguments
+///
+/// * `xml` - The XML of the document body
+///
+/// # Returns
+///
+/// * `Vec<Vec<RowLayout>>` - The layout of each row, per table directly in the body, in document order
+fn table_grid_layouts(xml: &str) -> Vec<Vec<RowLayout>> {
+    let mut tables: Vec<Vec<RowLayout>> = Vec::new();
+
+    visit_elements(xml, "table properties", |event, path, reader| {
+        let XmlEvent::Start(element) = event else {
+            return;
+        };
+        let name = element.local_name();
+        let value = || attribute_value(element, b"val", reader);
+        let row = tables.last_mut().and_then(|rows| rows.last_mut());
+        match name.as_ref() {
+            b"tbl" if path_ends_with(path, &[b"body"]) => tables.push(Vec::new()),
+            b"tr" if path_ends_with(path, &[b"body", b"tbl"]) => {
+                if let Some(rows) = tables.last_mut() {
+                    rows.push(RowLayout::default());
+                }
+            },
+            b"gridBefore" if path_ends_with(path, &[b"body", b"tbl", b"tr", b"trPr"]) => {
+                if let Some(row) = row {
+                    row.grid_before = value().and_then(|value| value.parse().ok()).unwrap_or(0);
+                }
+            },
+            b"tblHeader" if path_ends_with(path, &[b"body", b"tbl", b"tr", b"trPr"]) => {
+                if let Some(row) = row {
+                    // A missing value switches the property on
+                    row.header = !matches!(value().as_deref(), Some("false" | "0" | "off"));
+                }
+            },
+            b"tc" if path_ends_with(path, &[b"body", b"tbl", b"tr"]) => {
+                if let Some(row) = row {
+                    row.cells.push(CellLayout { col_span: 1, vertical_merge: None });
+                }
+            },
+            b"gridSpan" | b"vMerge" if path_ends_with(path, &[b"body", b"tbl", b"tr", b"tc", b"tcPr"]) => {
+                let Some(cell) = row.and_then(|row| row.cells.last_mut()) else {
+                    return;
+                };
+                if name.as_ref() == b"gridSpan" {
+                    cell.col_span = value().and_then(|value| value.parse().ok()).unwrap_or(1);
+                } else {
+                    // A merge without a value continues the merge above
+                    cell.vertical_merge = match value().as_deref() {
+                        Some("restart") => Some(VerticalMerge::Restart),
+                        _ => Some(VerticalMerge::Continue),
+                    };
+                }
+            },
+            _ => {}
+        }
+    });
+
+    tables
+}
+
+/// Reads the formatting set directly on the runs of each body paragraph
+///
+/// Only the runs docx-rs reports as paragraph children are listed: runs directly in
+/// the paragraph and runs inside hyperlinks. Paragraphs in tables are not listed.
+///
+/// # Arguments
+///
+/// * `xml` - The XML of the document body
+///
+/// # Returns
+///
+/// * `Vec<Vec<RunProperties>>` - The properties of each run, per paragraph directly in the body, in document order
+fn paragraph_run_properties(xml: &str) -> Vec<Vec<RunProperties>> {
+    let mut paragraphs: Vec<Vec<RunProperties>> = Vec::new();
+
+    visit_elements(xml, "run properties", |event, path, reader| {
+        let XmlEvent::Start(element) = event else {
+            return;
+        };
+        let in_run = path_ends_with(path, &[b"body", b"p", b"r", b"rPr"])
+            || path_ends_with(path, &[b"body", b"p", b"hyperlink", b"r", b"rPr"]);
+        match element.local_name().as_ref() {
+            b"p" if path_ends_with(path, &[b"body"]) => paragraphs.push(Vec::new()),
+            b"r" if path_ends_with(path, &[b"body", b"p"]) || path_ends_with(path, &[b"body", b"p", b"hyperlink"]) => {
+                if let Some(runs) = paragraphs.last_mut() {
+                    runs.push(RunProperties::default());
+                }
+            },
+            _ if in_run => {
+                if let Some(properties) = paragraphs.last_mut().and_then(|runs| runs.last_mut()) {
+                    properties.read_element(element, reader);
+                }
+            },
+            _ => {}
+        }
+    });
+
+    paragraphs
+}
+
+/// Reads the run formatting of the styles in the styles part
+///
+/// # Arguments
+///
+/// * `xml` - The XML of the styles part
+///
+/// # Returns
+///
+/// * `HashMap<String, RunProperties>` - The run properties of each style, keyed by style ID
+fn style_run_properties(xml: &str) -> HashMap<String, RunProperties> {
+    let mut styles = HashMap::new();
+    let mut style: Option<(String, RunProperties)> = None;
+
+    visit_elements(xml, "style properties", |event, path, reader| {
+        let element = match event {
+            XmlEvent::Start(element) => element,
+            XmlEvent::End(name) => {
+                if name == b"style" {
+                    styles.extend(style.take());
+                }
+                return;
+            },
+        };
+        match element.local_name().as_ref() {
+            b"style" if path_ends_with(path, &[b"styles"]) => {
+                style = attribute_value(element, b"styleId", reader).map(|id| (id, RunProperties::default()));
+            },
+            _ if path_ends_with(path, &[b"styles", b"style", b"rPr"]) => {
+                if let Some((_, properties)) = style.as_mut() {
+                    properties.read_element(element, reader);
+                }
+            },
+            _ => {}
+        }
+    });
+
+    styles
+}
+
+/// Event passed to the visitor of `visit_elements`
+enum XmlEvent<'a> {
+    /// A start tag or an empty element
+    Start(&'a BytesStart<'a>),
+    /// The end of an element, with its local name
+    End(&'a [u8]),
+}
+
+/// Walks the elements of an XML part, keeping track of the local names of the open elements
+///
+/// An empty element is visited as a start followed by an end. Reading stops at the
+/// first parse error, which is logged.
+///
+/// # Arguments
+///
+/// * `xml` - The XML to read
+/// * `description` - What the XML holds, for the parse error warning
+/// * `visit` - Called with each event, the local names of the enclosing open elements, outermost first, and the reader
+fn visit_elements(xml: &str, description: &str, mut visit: impl FnMut(XmlEvent, &[Vec<u8>], &quick_xml::Reader<&[u8]>)) {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    // Local names of the open elements
+    let mut path: Vec<Vec<u8>> = Vec::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(element)) => {
+                visit(XmlEvent::Start(&element), &path, &reader);
+                path.push(element.local_name().as_ref().to_vec());
+            },
+            Ok(Event::Empty(element)) => {
+                visit(XmlEvent::Start(&element), &path, &reader);
+                visit(XmlEvent::End(element.local_name().as_ref()), &path, &reader);
+            },
+            Ok(Event::End(_)) => {
+                if let Some(name) = path.pop() {
+                    visit(XmlEvent::End(&name), &path, &reader);
+                }
+            },
+            Ok(Event::Eof) => break,
+            Err(err) => {
+                warn!("Failed to parse {}: {}", description, err);
+                break;
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Returns the decoded value of an element's attribute, matched by local name
+fn attribute_value<R>(element: &BytesStart, name: &[u8], reader: &quick_xml::Reader<R>) -> Option<String> {
+    element.attributes()
+        .filter_map(|attribute| attribute.ok())
+        .find(|attribute| attribute.key.local_name().as_ref() == name)
+        .and_then(|attribute| attribute.decode_and_unescape_value(reader).ok())
+        .map(|value| value.to_string())
+}
+
+/// Checks if the innermost open elements have the given local names, outermost first
+fn path_ends_with(path: &[Vec<u8>], names: &[&[u8]]) -> bool {
+    path.len() >= names.len()
+        && path[path.len() - names.len()..].iter().zip(names).all(|(open, name)| open.as_slice() == *name)
+}
+
+/// Returns the runs of a paragraph, including those inside hyperlinks
+fn paragraph_runs(paragraph: &docx_rs::Paragraph) -> Vec<&docx_rs::Run> {
+    paragraph.children.iter().flat_map(|child| match child {
+        ParagraphChild::Run(run) => vec![run.as_ref()],
+        ParagraphChild::Hyperlink(hyperlink) => hyperlink.children.iter()
+            .filter_map(|child| match child {
+                ParagraphChild::Run(run) => Some(run.as_ref()),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }).collect()
+}
+
+/// Concatenates the text of all runs in a paragraph
+///
+/// Note references are registered with the collector and replaced by their label.
+///
+/// # Arguments
+///
+/// * `paragraph` - The paragraph to read
+/// * `notes` - Collector for the footnotes and endnotes referenced by the paragraph
+///
+/// # Returns
+///
+/// * `String` - The plain text of the paragraph
+fn extract_paragraph_text(paragraph: &docx_rs::Paragraph, notes: &mut NoteCollector) -> String {
+    let mut paragraph_text = String::new();
+
+    for run in paragraph_runs(paragraph) {
+        for child in &run.children {
+            if let RunChild::Text(text) = child {
+                for segment in text_segments(&text.text) {
+                    match segment {
+                        TextSegment::Text(text) => paragraph_text.push_str(text),
+                        TextSegment::Note(kind, id) => {
+                            let index = notes.reference(kind, id);
+                            paragraph_text.push_str(&notes.notes[index].label);
                         }
                     }
                 }
+            }
+        }
+    }
 
-                if !table_data.is_empty() {
-                    debug!("Extracted table with {} rows", table_data.len());
-                    content.tables.push(table_data);
+    paragraph_text
+}
+
+/// Extracts the rows and cells of a table, resolving merged cells
+///
+/// Horizontally merged cells (`gridSpan`) become a cell spanning several columns.
+/// Vertically merged cells (`vMerge`) extend the cell that started the merge, and
+/// the text of the continuation cells is dropped, as in Word.
+///
+/// # Arguments
+///
+/// * `table` - The table to read
+/// * `layout` - The grid layout of each row of the table, in order
+/// * `notes` - Collector for the footnotes and endnotes referenced by the table
+///
+/// # Returns
+///
+/// * `Table` - The table data with row and column spans
+fn extract_table(table: &docx_rs::Table, layout: &[RowLayout], notes: &mut NoteCollector) -> Table {
+    let mut table_data = Table::default();
+    // Cells with an open vertical merge, as (row, index in row), keyed by start column
+    let mut merges: HashMap<usize, (usize, usize)> = HashMap::new();
+
+    // Rows and cells are matched with their layout by position, as both follow the document order
+    for (position, row_child) in table.rows.iter().enumerate() {
+        let TableChild::TableRow(row) = row_child;
+        let row_layout = layout.get(position);
+        if row.cells.is_empty() {
+            continue;
+        }
+
+        let row_index = table_data.rows.len();
+        // Rows may start after some grid columns that have no cells
+        let mut column = row_layout.map_or(0, |layout| layout.grid_before);
+        let mut row_data = Vec::new();
+        let mut continued = Vec::new();
+
+        for (cell_index, cell_child) in row.cells.iter().enumerate() {
+            let TableRowChild::TableCell(cell) = cell_child;
+            let cell_layout = row_layout.and_then(|layout| layout.cells.get(cell_index)).cloned().unwrap_or_default();
+            let col_span = cell_layout.col_span.max(1);
+            let vertical_merge = cell_layout.vertical_merge;
+
+            match merges.get(&column) {
+                Some(&(start_row, index)) if vertical_merge == Some(VerticalMerge::Continue) => {
+                    table_data.rows[start_row][index].row_span += 1;
+                    continued.push(column);
+                },
+                _ => {
+                    let mut cell_text = String::new();
+                    for content in &cell.children {
+                        if let TableCellContent::Paragraph(paragraph) = content {
+                            cell_text.push_str(&extract_paragraph_text(paragraph, notes));
+                        }
+                    }
+
+                    if vertical_merge == Some(VerticalMerge::Restart) {
+                        merges.insert(column, (row_index, row_data.len()));
+                        continued.push(column);
+                    }
+                    row_data.push(TableCell { col_span, ..TableCell::new(cell_text, column) });
+                }
+            }
+
+            column += col_span;
+        }
+
+        // A merge ends at the first row that does not continue it
+        merges.retain(|column, _| continued.contains(column));
+        table_data.rows.push(row_data);
+    }
+
+    table_data
+}
+
+/// Returns the text of a header or footer paragraph, replacing page number and page count fields
+///
+/// # Arguments
+///
+/// * `paragraph` - The paragraph to read
+///
+/// # Returns
+///
+/// * `String` - The paragraph text with `PAGE_NUMBER_PLACEHOLDER` for page numbers and `PAGE_COUNT_PLACEHOLDER` for page counts
+fn field_text(paragraph: &docx_rs::Paragraph) -> String {
+    let mut text = String::new();
+    // Instruction of the field being read, and whether its cached result is being read
+    let mut field: Option<String> = None;
+    let mut in_result = false;
+
+    for run in paragraph_runs(paragraph) {
+        for child in &run.children {
+            match child {
+                RunChild::FieldChar(field_char) => match field_char.field_char_type {
+                    FieldCharType::Begin => {
+                        field = Some(String::new());
+                        in_result = false;
+                    },
+                    FieldCharType::Separate => in_result = true,
+                    FieldCharType::End => {
+                        if let Some(placeholder) = field.take().and_then(|instruction| page_field_placeholder(&instruction)) {
+                            text.push_str(placeholder);
+                        }
+                        in_result = false;
+                    },
+                    FieldCharType::Unsupported => {},
+                },
+                RunChild::InstrTextString(instruction) => {
+                    if let Some(field) = field.as_mut() {
+                        field.push_str(instruction);
+                    }
+                },
+                RunChild::Text(t) => {
+                    // The cached result of a page field is replaced by the placeholder
+                    let is_page_result = in_result && field.as_deref().and_then(page_field_placeholder).is_some();
+                    if !is_page_result {
+                        text.push_str(&t.text);
+                    }
+                },
+                RunChild::Tab(_) => text.push_str("    "),
+                _ => {}
+            }
+        }
+    }
+
+    text
+}
+
+/// Returns the placeholder for a field instruction that inserts the page number or page count
+fn page_field_placeholder(instruction: &str) -> Option<&'static str> {
+    match instruction.split_whitespace().next()? {
+        "PAGE" => Some(PAGE_NUMBER_PLACEHOLDER),
+        "NUMPAGES" | "SECTIONPAGES" => Some(PAGE_COUNT_PLACEHOLDER),
+        _ => None,
+    }
+}
+
+/// Returns one line per row of a header or footer table, with cells separated by spaces
+fn table_lines(table: &docx_rs::Table) -> Vec<String> {
+    table.rows.iter().map(|row_child| {
+        let TableChild::TableRow(row) = row_child;
+        row.cells.iter()
+            .map(|cell_child| {
+                let TableRowChild::TableCell(cell) = cell_child;
+                cell.children.iter()
+                    .filter_map(|content| match content {
+                        TableCellContent::Paragraph(paragraph) => Some(field_text(paragraph)),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .filter(|text| !text.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("    ")
+    }).collect()
+}
+
+/// Returns the lines of a content control in a header or footer
+fn tag_lines(tag: &docx_rs::StructuredDataTag) -> Vec<String> {
+    tag.children.iter().flat_map(|child| match child {
+        StructuredDataTagChild::Paragraph(paragraph) => vec![field_text(paragraph)],
+        StructuredDataTagChild::Table(table) => table_lines(table),
+        StructuredDataTagChild::StructuredDataTag(tag) => tag_lines(tag),
+        _ => Vec::new(),
+    }).collect()
+}
+
+/// A piece of run text: plain text or a note reference placeholder
+enum TextSegment<'a> {
+    /// Plain text
+    Text(&'a str),
+    /// Reference to the note with the given kind and ID
+    Note(NoteKind, &'a str),
+}
+
+/// Splits run text into plain text and note reference placeholders
+///
+/// Placeholders without a note kind and ID, which the document text itself may
+/// contain, are kept as plain text.
+fn text_segments(text: &str) -> Vec<TextSegment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find(NOTE_REFERENCE_START) {
+        let Some(length) = rest[start..].find(NOTE_REFERENCE_END) else {
+            break;
+        };
+        let end = start + length + NOTE_REFERENCE_END.len_utf8();
+
+        let reference = &rest[start + NOTE_REFERENCE_START.len_utf8()..start + length];
+        let note = reference.strip_prefix('e').map(|id| (NoteKind::Endnote, id))
+            .or_else(|| reference.strip_prefix('f').map(|id| (NoteKind::Footnote, id)))
+            .filter(|(_, id)| !id.is_empty());
+        match note {
+            Some((kind, id)) => {
+                if start > 0 {
+                    segments.push(TextSegment::Text(&rest[..start]));
                 }
+                segments.push(TextSegment::Note(kind, id));
             },
-            _ => {
-                // Ignore other document elements
+            None => segments.push(TextSegment::Text(&rest[..end])),
+        }
+        rest = &rest[end..];
+    }
+
+    if !rest.is_empty() {
+        segments.push(TextSegment::Text(rest));
+    }
+    segments
+}
+
+/// Collects the footnotes and endnotes referenced from the document, in reference order
+struct NoteCollector {
+    /// Note paragraphs from the notes parts, keyed by kind and ID
+    texts: HashMap<(NoteKind, String), Vec<String>>,
+    /// Index into `notes` of each note referenced so far
+    indices: HashMap<(NoteKind, String), usize>,
+    /// Notes in the order they were first referenced
+    notes: Vec<Note>,
+}
+
+impl NoteCollector {
+    /// Reads the footnotes and endnotes parts of a DOCX package
+    fn new(buf: &[u8]) -> Self {
+        let mut texts = HashMap::new();
+
+        for (kind, part, element) in [
+            (NoteKind::Footnote, FOOTNOTES_PART, b"footnote".as_slice()),
+            (NoteKind::Endnote, ENDNOTES_PART, b"endnote".as_slice()),
+        ] {
+            match read_package_part(buf, part) {
+                Ok(Some(xml)) => {
+                    for (id, paragraphs) in read_notes(&xml, element) {
+                        texts.insert((kind, id), paragraphs);
+                    }
+                },
+                Ok(None) => {},
+                Err(err) => warn!("Failed to read {}: {:#}", part, err),
             }
         }
+
+        NoteCollector {
+            texts,
+            indices: HashMap::new(),
+            notes: Vec::new(),
+        }
     }
 
-    info!("Extracted {} paragraphs and {} tables from document", 
-          content.paragraphs.len(), content.tables.len());
+    /// Registers a reference to a note and returns its index
+    ///
+    /// Footnotes are labelled 1, 2, 3 and endnotes i, ii, iii, as in Word's defaults.
+    fn reference(&mut self, kind: NoteKind, id: &str) -> usize {
+        let key = (kind, id.to_string());
+        if let Some(index) = self.indices.get(&key) {
+            return *index;
+        }
 
-    if content.paragraphs.is_empty() && content.tables.is_empty() {
-        warn!("No content extracted from document");
+        let number = self.notes.iter().filter(|note| note.kind == kind).count() + 1;
+        let label = match kind {
+            NoteKind::Footnote => format_number(number, "decimal"),
+            NoteKind::Endnote => format_number(number, "lowerRoman"),
+        };
+        let paragraphs = self.texts.get(&key).cloned().unwrap_or_else(|| {
+            warn!("Note not found in document: {}", id);
+            Vec::new()
+        });
+
+        self.notes.push(Note { kind, label, paragraphs });
+        self.indices.insert(key, self.notes.len() - 1);
+        self.notes.len() - 1
     }
+}
 
-    Ok(content)
+/// Reads the paragraphs of each note in a footnotes or endnotes part
+///
+/// # Arguments
+///
+/// * `xml` - The XML of the notes part
+/// * `element` - Local name of the note elements ("footnote" or "endnote")
+///
+/// # Returns
+///
+/// * `Vec<(String, Vec<String>)>` - Note IDs with their non-empty paragraphs
+fn read_notes(xml: &str, element: &[u8]) -> Vec<(String, Vec<String>)> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut notes = Vec::new();
+    let mut note: Option<(String, Vec<String>)> = None;
+    let mut paragraph = String::new();
+    let mut in_text = false;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(start)) => match start.local_name().as_ref() {
+                // Separators and continuation notices carry a type and are not real notes
+                name if name == element && attribute_value(&start, b"type", &reader).is_none() => {
+                    note = attribute_value(&start, b"id", &reader).map(|id| (id, Vec::new()));
+                },
+                b"p" => paragraph.clear(),
+                b"t" => in_text = true,
+                _ => {}
+            },
+            Ok(Event::Empty(empty)) if empty.local_name().as_ref() == b"tab" => paragraph.push(' '),
+            Ok(Event::Text(text)) if in_text => {
+                if let Ok(text) = text.unescape() {
+                    paragraph.push_str(&text);
+                }
+            },
+            Ok(Event::End(end)) => match end.local_name().as_ref() {
+                name if name == element => notes.extend(note.take()),
+                b"p" => {
+                    if let Some((_, paragraphs)) = note.as_mut() {
+                        let text = paragraph.trim();
+                        if !text.is_empty() {
+                            paragraphs.push(text.to_string());
+                        }
+                    }
+                },
+                b"t" => in_text = false,
+                _ => {}
+            },
+            Ok(Event::Eof) => break,
+            Err(err) => {
+                warn!("Failed to parse notes: {}", err);
+                break;
+            },
+            _ => {}
+        }
+    }
+
+    notes
+}
+
+/// Replaces note reference elements in the document body with placeholder text
+///
+/// # Arguments
+///
+/// * `buf` - The DOCX file contents
+///
+/// # Returns
+///
+/// * `Result<Option<Vec<u8>>>` - The rewritten package, `None` if the document has no note references, or an error
+fn mark_note_references(buf: &[u8]) -> Result<Option<Vec<u8>>> {
+    let Some(xml) = read_package_part(buf, DOCUMENT_PART)? else {
+        return Ok(None);
+    };
+    if !xml.contains("footnoteReference") && !xml.contains("endnoteReference") {
+        return Ok(None);
+    }
+
+    let mut reader = quick_xml::Reader::from_str(&xml);
+    let mut writer = quick_xml::Writer::new(Vec::new());
+    loop {
+        let event = reader.read_event().context("Failed to parse document body")?;
+        let kind = match &event {
+            Event::Empty(element) => match element.local_name().as_ref() {
+                b"footnoteReference" => Some('f'),
+                b"endnoteReference" => Some('e'),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        match (kind, event) {
+            (_, Event::Eof) => break,
+            (Some(kind), Event::Empty(element)) => {
+                let id = attribute_value(&element, b"id", &reader).unwrap_or_default();
+                let placeholder = format!("{}{}{}{}", NOTE_REFERENCE_START, kind, id, NOTE_REFERENCE_END);
+                writer.write_event(Event::Start(BytesStart::new("w:t")))?;
+                writer.write_event(Event::Text(BytesText::new(&placeholder)))?;
+                writer.write_event(Event::End(BytesEnd::new("w:t")))?;
+            },
+            (_, event) => writer.write_event(event)?,
+        }
+    }
+
+    // Copy the package, replacing the document body
+    let mut archive = zip::ZipArchive::new(Cursor::new(buf))
+        .context("Failed to open DOCX package")?;
+    let mut package = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for index in 0..archive.len() {
+        let file = archive.by_index_raw(index)?;
+        if file.name() == DOCUMENT_PART {
+            let name = file.name().to_string();
+            drop(file);
+            package.start_file(name, zip::write::FileOptions::default())?;
+            package.write_all(writer.get_ref())?;
+        } else {
+            package.raw_copy_file(file)?;
+        }
+    }
+
+    Ok(Some(package.finish()?.into_inner()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the plain text and note references of run text, in order
+    fn segments(text: &str) -> Vec<String> {
+        text_segments(text).into_iter().map(|segment| match segment {
+            TextSegment::Text(text) => text.to_string(),
+            TextSegment::Note(kind, id) => format!("{:?} {}", kind, id),
+        }).collect()
+    }
+
+    #[test]
+    fn test_text_segments() {
+        assert_eq!(segments("See\u{E000}f2\u{E001} and\u{E000}e1\u{E001}"), ["See", "Footnote 2", " and", "Endnote 1"]);
+
+        // Malformed placeholders are plain text
+        assert_eq!(segments("\u{E000}\u{E001}"), ["\u{E000}\u{E001}"]);
+        assert_eq!(segments("a\u{E000}é1\u{E001}b"), ["a\u{E000}é1\u{E001}", "b"]);
+        assert_eq!(segments("\u{E000}f\u{E001}"), ["\u{E000}f\u{E001}"]);
+        assert_eq!(segments("open \u{E000}f1"), ["open \u{E000}f1"]);
+    }
 }
@@ -0,0 +1,375 @@
+//! Font selection for PDF output.
+//! Fonts are given as a family name, looked up in the standard font directories
+//! like fontconfig does, or as a directory of TTF/OTF files.
+
+use std::path::{Path, PathBuf};
+use anyhow::{Result, Context};
+use genpdf::fonts::{FontData, FontFamily};
+use log::{debug, warn};
+use walkdir::WalkDir;
+
+/// Font directories searched for system fonts
+const SYSTEM_FONT_DIRS: &[&str] = &["/usr/share/fonts", "/usr/local/share/fonts"];
+
+/// Font directories searched for user fonts, relative to the home directory
+const USER_FONT_DIRS: &[&str] = &[".local/share/fonts", ".fonts"];
+
+/// Weight class of regular text
+const REGULAR_WEIGHT: u16 = 400;
+
+/// Weight class of bold text
+const BOLD_WEIGHT: u16 = 700;
+
+/// Width class of fonts that are neither condensed nor expanded
+const NORMAL_WIDTH: u16 = 5;
+
+/// A font file and the style of the face it contains
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontFace {
+    /// Path to the font file
+    pub path: PathBuf,
+    /// Family name of the face, e.g. `Noto Sans`
+    pub family: String,
+    /// Weight class, from 100 (thin) to 900 (black)
+    pub weight: u16,
+    /// Width class, from 1 (ultra-condensed) to 9 (ultra-expanded)
+    pub width: u16,
+    /// Whether the face is italic or oblique
+    pub italic: bool,
+}
+
+impl FontFace {
+    /// Reads the family name and style of a font file
+    ///
+    /// The names and style are read from the `name`, `OS/2` and `head` tables of
+    /// the font. If the font has no usable names, they are guessed from the file name.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to a TTF or OTF file
+    ///
+    /// # Returns
+    ///
+    /// * `Result<FontFace>` - The font face or an error
+    pub fn read(path: &Path) -> Result<FontFace> {
+        let data = std::fs::read(path)
+            .context(format!("Failed to read font file: {}", path.display()))?;
+        let stem = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+        let (stem_family, stem_style) = stem.split_once('-').unwrap_or((&stem, ""));
+        let stem_style = stem_style.to_lowercase();
+
+        let tables = font_tables(&data);
+        let table = |tag: &[u8; 4]| tables.iter()
+            .find(|(name, _)| name == tag)
+            .and_then(|(_, range)| data.get(range.clone()));
+
+        let names = table(b"name").map(font_names).unwrap_or_default();
+        let name = |id: u16| names.iter().find(|(name_id, _)| *name_id == id).map(|(_, name)| name.clone());
+        // Typographic names group all weights of a family; the legacy names only group four styles
+        let family = name(16).or_else(|| name(1)).unwrap_or_else(|| stem_family.to_string());
+
+        let os2 = table(b"OS/2");
+        let weight = os2.and_then(|os2| read_u16(os2, 4))
+            .unwrap_or(if stem_style.contains("bold") { BOLD_WEIGHT } else { REGULAR_WEIGHT });
+        let width = os2.and_then(|os2| read_u16(os2, 6)).unwrap_or(NORMAL_WIDTH);
+        let italic = match (os2.and_then(|os2| read_u16(os2, 62)), table(b"head").and_then(|head| read_u16(head, 44))) {
+            (Some(selection), _) => selection & 1 != 0,
+            (None, Some(mac_style)) => mac_style & 2 != 0,
+            (None, None) => stem_style.contains("italic") || stem_style.contains("oblique"),
+        };
+
+        Ok(FontFace { path: path.to_path_buf(), family, weight, width, italic })
+    }
+
+    /// Returns how far the face is from the wanted style; lower is better
+    fn distance(&self, weight: u16, italic: bool) -> u32 {
+        let italic_penalty = if self.italic == italic { 0 } else { 10_000 };
+        italic_penalty + 100 * u32::from(self.width.abs_diff(NORMAL_WIDTH)) + u32::from(self.weight.abs_diff(weight))
+    }
+}
+
+/// The font files chosen for the four variants of a font family
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontFiles {
+    /// Family name of the fonts
+    pub family: String,
+    /// Upright regular face
+    pub regular: PathBuf,
+    /// Upright bold face, or the regular face if the family has no bold
+    pub bold: PathBuf,
+    /// Italic face, or the regular face if the family has no italic
+    pub italic: PathBuf,
+    /// Bold italic face, falling back to the italic or bold face
+    pub bold_italic: PathBuf,
+}
+
+impl FontFiles {
+    /// Chooses the closest face of a family for each variant
+    ///
+    /// # Arguments
+    ///
+    /// * `family` - Family name of the faces
+    /// * `faces` - Faces of the family; must not be empty
+    ///
+    /// # Returns
+    ///
+    /// * `FontFiles` - The files of the four variants
+    fn choose(family: &str, faces: &[FontFace]) -> FontFiles {
+        let closest = |weight: u16, italic: bool| faces.iter()
+            .min_by_key(|face| face.distance(weight, italic))
+            .map(|face| face.path.clone())
+            .unwrap_or_default();
+
+        FontFiles {
+            family: family.to_string(),
+            regular: closest(REGULAR_WEIGHT, false),
+            bold: closest(BOLD_WEIGHT, false),
+            italic: closest(REGULAR_WEIGHT, true),
+            bold_italic: closest(BOLD_WEIGHT, true),
+        }
+    }
+
+    /// Loads the font files as a genpdf font family
+    ///
+    /// # Returns
+    ///
+    /// * `Result<FontFamily<FontData>>` - The loaded font family or an error
+    pub fn load(&self) -> Result<FontFamily<FontData>> {
+        let load_variant = |path: &Path| -> Result<FontData> {
+            let data = std::fs::read(path)
+                .context(format!("Failed to read font file: {}", path.display()))?;
+            FontData::new(data, None)
+                .context(format!("Failed to load font data: {}", path.display()))
+        };
+
+        Ok(FontFamily {
+            regular: load_variant(&self.regular)?,
+            bold: load_variant(&self.bold)?,
+            italic: load_variant(&self.italic)?,
+            bold_italic: load_variant(&self.bold_italic)?,
+        })
+    }
+}
+
+/// Finds the font files for a font given as a directory or a family name
+///
+/// # Arguments
+///
+/// * `font` - A directory of TTF/OTF files, or the family name of an installed font
+///
+/// # Returns
+///
+/// * `Result<FontFiles>` - The files of the regular, bold, italic and bold italic variants or an error
+pub fn find_font(font: &str) -> Result<FontFiles> {
+    let path = Path::new(font);
+    if path.is_dir() {
+        find_font_in_dir(path)
+    } else {
+        find_system_font(font)
+    }
+}
+
+/// Finds the font family in a directory of font files
+///
+/// If the directory holds several families, the one with the most faces is used.
+///
+/// # Arguments
+///
+/// * `dir` - Directory containing TTF/OTF files
+///
+/// # Returns
+///
+/// * `Result<FontFiles>` - The files of the family or an error
+pub fn find_font_in_dir(dir: &Path) -> Result<FontFiles> {
+    let faces = read_faces(font_files(dir));
+
+    let mut families: Vec<(&str, usize)> = Vec::new();
+    for face in &faces {
+        match families.iter_mut().find(|(family, _)| *family == face.family) {
+            Some((_, count)) => *count += 1,
+            None => families.push((&face.family, 1)),
+        }
+    }
+    let (family, _) = families.iter()
+        .max_by_key(|(_, count)| *count)
+        .context(format!("No TTF or OTF fonts found in directory: {}", dir.display()))?;
+
+    let family_faces: Vec<FontFace> = faces.iter().filter(|face| face.family == *family).cloned().collect();
+    debug!("Found {} faces of font family '{}' in {}", family_faces.len(), family, dir.display());
+    Ok(FontFiles::choose(family, &family_faces))
+}
+
+/// Finds an installed font family by name in the standard Linux font directories
+///
+/// The user's font directories are searched before the system ones, following
+/// the XDG base directory conventions that fontconfig uses.
+///
+/// # Arguments
+///
+/// * `name` - Family name of the font, e.g. `Noto Sans`
+///
+/// # Returns
+///
+/// * `Result<FontFiles>` - The files of the family or an error
+pub fn find_system_font(name: &str) -> Result<FontFiles> {
+    find_font_by_name(name, &font_dirs())
+}
+
+/// Finds a font family by name in the given font directories
+///
+/// Names are compared without regard to case, spaces, hyphens and underscores.
+/// Files whose names start with the family name are checked first, so that not
+/// every installed font has to be read.
+///
+/// # Arguments
+///
+/// * `name` - Family name of the font
+/// * `dirs` - Directories searched for font files, including their subdirectories
+///
+/// # Returns
+///
+/// * `Result<FontFiles>` - The files of the family or an error
+pub fn find_font_by_name(name: &str, dirs: &[PathBuf]) -> Result<FontFiles> {
+    let wanted = normalize_name(name);
+    let files: Vec<PathBuf> = dirs.iter().flat_map(|dir| font_files(dir)).collect();
+
+    let (likely, others): (Vec<PathBuf>, Vec<PathBuf>) = files.into_iter().partition(|path| {
+        path.file_stem().is_some_and(|stem| normalize_name(&stem.to_string_lossy()).starts_with(&wanted))
+    });
+    for candidates in [likely, others] {
+        let faces: Vec<FontFace> = read_faces(candidates).into_iter()
+            .filter(|face| normalize_name(&face.family) == wanted)
+            .collect();
+        if let Some(face) = faces.first() {
+            debug!("Found {} faces of font family '{}'", faces.len(), face.family);
+            return Ok(FontFiles::choose(&face.family, &faces));
+        }
+    }
+
+    anyhow::bail!("Font family not found: {} (searched {})", name,
+        dirs.iter().map(|dir| dir.display().to_string()).collect::<Vec<_>>().join(", "))
+}
+
+/// Returns the directories searched for installed fonts, in order of precedence
+fn font_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(data_home) = std::env::var_os("XDG_DATA_HOME") {
+        dirs.push(PathBuf::from(data_home).join("fonts"));
+    }
+    if let Some(home) = std::env::var_os("HOME") {
+        dirs.extend(USER_FONT_DIRS.iter().map(|dir| Path::new(&home).join(dir)));
+    }
+    if let Some(data_dirs) = std::env::var_os("XDG_DATA_DIRS") {
+        dirs.extend(std::env::split_paths(&data_dirs).map(|dir| dir.join("fonts")));
+    }
+    dirs.extend(SYSTEM_FONT_DIRS.iter().map(PathBuf::from));
+
+    let mut unique: Vec<PathBuf> = Vec::new();
+    for dir in dirs {
+        if dir.is_dir() && !unique.contains(&dir) {
+            unique.push(dir);
+        }
+    }
+    unique
+}
+
+/// Returns the TTF and OTF files in a directory and its subdirectories, sorted by path
+fn font_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = WalkDir::new(dir)
+        .follow_links(true)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.into_path())
+        .filter(|path| path.is_file())
+        .filter(|path| path.extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .is_some_and(|ext| ext == "ttf" || ext == "otf"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Reads the faces of font files, skipping files that cannot be read
+fn read_faces(files: Vec<PathBuf>) -> Vec<FontFace> {
+    files.iter()
+        .filter_map(|path| match FontFace::read(path) {
+            Ok(face) => Some(face),
+            Err(err) => {
+                warn!("Skipping font: {:#}", err);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Lowercases a font name and drops spaces, hyphens and underscores
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '_'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Returns the tag and byte range of each table in an OpenType font
+pub(crate) fn font_tables(data: &[u8]) -> Vec<([u8; 4], std::ops::Range<usize>)> {
+    let count = read_u16(data, 4).unwrap_or(0) as usize;
+    (0..count)
+        .filter_map(|index| {
+            let record = 12 + 16 * index;
+            let tag: [u8; 4] = data.get(record..record + 4)?.try_into().ok()?;
+            let offset = read_u32(data, record + 8)? as usize;
+            let length = read_u32(data, record + 12)? as usize;
+            Some((tag, offset..offset + length))
+        })
+        .collect()
+}
+
+/// Returns the English names of a font's `name` table, as pairs of name ID and text
+///
+/// Windows Unicode names are preferred; Macintosh Roman names are used for IDs
+/// that have no Windows name.
+pub(crate) fn font_names(table: &[u8]) -> Vec<(u16, String)> {
+    let count = read_u16(table, 2).unwrap_or(0) as usize;
+    let storage = read_u16(table, 4).unwrap_or(0) as usize;
+    let mut windows = Vec::new();
+    let mut mac = Vec::new();
+
+    for index in 0..count {
+        let record = 6 + 12 * index;
+        let field = |offset: usize| read_u16(table, record + offset);
+        let (Some(platform), Some(encoding), Some(language), Some(name_id), Some(length), Some(offset)) =
+            (field(0), field(2), field(4), field(6), field(8), field(10)) else {
+            break;
+        };
+        let start = storage + offset as usize;
+        let Some(bytes) = table.get(start..start + length as usize) else {
+            continue;
+        };
+
+        match (platform, encoding, language) {
+            (3, 0 | 1 | 10, 0x409) => {
+                let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+                windows.push((name_id, String::from_utf16_lossy(&units)));
+            },
+            (1, 0, 0) => mac.push((name_id, bytes.iter().map(|&byte| char::from(byte)).collect())),
+            _ => {}
+        }
+    }
+
+    for (name_id, name) in mac {
+        if !windows.iter().any(|(id, _)| *id == name_id) {
+            windows.push((name_id, name));
+        }
+    }
+    windows
+}
+
+/// Reads a big-endian 16-bit value
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+/// Reads a big-endian 32-bit value
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
@@ -0,0 +1,242 @@
+//! Subsetting of TrueType fonts embedded in PDF files.
+//! The outlines of unused glyphs are emptied rather than removed, so the remaining
+//! glyphs keep their ids and text encoded with Identity-H needs no changes.
+
+use std::collections::BTreeSet;
+use anyhow::{Result, Context};
+
+use crate::converter::font_loader::{font_names, font_tables, read_u16, read_u32};
+
+/// Tables kept in subset fonts, in the order of their tags; layout tables are not used in PDF files
+const KEPT_TABLES: [&[u8; 4]; 13] = [
+    b"OS/2", b"cmap", b"cvt ", b"fpgm", b"glyf", b"head", b"hhea", b"hmtx", b"loca", b"maxp", b"name", b"post", b"prep",
+];
+
+/// Version of `post` tables without glyph names
+const POST_VERSION_3: u32 = 0x0003_0000;
+
+/// Length of the `post` table header, which is all that is left of version 3 tables
+const POST_HEADER_LENGTH: usize = 32;
+
+/// Offset of `checkSumAdjustment` in the `head` table
+const CHECKSUM_ADJUSTMENT_OFFSET: usize = 8;
+
+/// Offset of `indexToLocFormat` in the `head` table
+const LOCA_FORMAT_OFFSET: usize = 50;
+
+/// Value the checksum of a whole font adds up to with its `checkSumAdjustment`
+const CHECKSUM_MAGIC: u32 = 0xB1B0_AFBA;
+
+/// Name ID of the PostScript name in the `name` table
+const POSTSCRIPT_NAME_ID: u16 = 6;
+
+/// Composite glyph flag: the component offsets are 16-bit values
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+
+/// Composite glyph flag: the component has a uniform scale
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+
+/// Composite glyph flag: further components follow
+const MORE_COMPONENTS: u16 = 0x0020;
+
+/// Composite glyph flag: the component has separate horizontal and vertical scales
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+
+/// Composite glyph flag: the component has a 2×2 transformation
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+/// Reduces a TrueType font to the given glyphs
+///
+/// Glyph 0 (`.notdef`) and the components of composite glyphs are always kept.
+/// Glyph names and layout tables are dropped.
+///
+/// # Arguments
+///
+/// * `font` - The TrueType font program
+/// * `glyphs` - Ids of the glyphs used
+///
+/// # Returns
+///
+/// * `Result<Option<Vec<u8>>>` - The subset font, `None` if the font has no TrueType
+///   outlines (e.g. CFF-based OpenType fonts), or an error for a damaged font
+pub fn subset_truetype(font: &[u8], glyphs: &BTreeSet<u16>) -> Result<Option<Vec<u8>>> {
+    let tables = font_tables(font);
+    let table = |tag: &[u8; 4]| tables.iter()
+        .find(|(name, _)| name == tag)
+        .and_then(|(_, range)| font.get(range.clone()));
+    let (Some(head), Some(maxp), Some(loca), Some(glyf)) = (table(b"head"), table(b"maxp"), table(b"loca"), table(b"glyf")) else {
+        return Ok(None);
+    };
+
+    let glyph_count = read_u16(maxp, 4).context("Invalid maxp table")? as usize;
+    let long_offsets = read_u16(head, LOCA_FORMAT_OFFSET).context("Invalid head table")? == 1;
+    let offsets = (0..=glyph_count)
+        .map(|index| match long_offsets {
+            true => read_u32(loca, 4 * index).map(|offset| offset as usize),
+            false => read_u16(loca, 2 * index).map(|offset| offset as usize * 2),
+        })
+        .collect::<Option<Vec<usize>>>()
+        .context("Invalid loca table")?;
+    let outline = |glyph: usize| glyf.get(offsets[glyph]..offsets[glyph + 1])
+        .context(format!("Invalid outline of glyph {}", glyph));
+
+    // Composite glyphs are drawn from other glyphs, which must be kept as well
+    let mut kept: BTreeSet<usize> = glyphs.iter()
+        .map(|&glyph| usize::from(glyph))
+        .filter(|&glyph| glyph < glyph_count)
+        .chain([0])
+        .collect();
+    let mut pending: Vec<usize> = kept.iter().copied().collect();
+    while let Some(glyph) = pending.pop() {
+        for component in composite_components(outline(glyph)?) {
+            if component < glyph_count && kept.insert(component) {
+                pending.push(component);
+            }
+        }
+    }
+
+    // Offsets of the new outlines are always written in the long format
+    let mut new_glyf = Vec::new();
+    let mut new_loca = Vec::with_capacity(4 * (glyph_count + 1));
+    for glyph in 0..glyph_count {
+        new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+        if kept.contains(&glyph) {
+            new_glyf.extend_from_slice(outline(glyph)?);
+            new_glyf.resize(new_glyf.len().next_multiple_of(4), 0);
+        }
+    }
+    new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+
+    let mut new_head = head.to_vec();
+    new_head[CHECKSUM_ADJUSTMENT_OFFSET..CHECKSUM_ADJUSTMENT_OFFSET + 4].fill(0);
+    new_head[LOCA_FORMAT_OFFSET..LOCA_FORMAT_OFFSET + 2].copy_from_slice(&1u16.to_be_bytes());
+
+    let subset_tables: Vec<([u8; 4], Vec<u8>)> = KEPT_TABLES.iter()
+        .filter_map(|&tag| {
+            let data = match tag {
+                b"glyf" => new_glyf.clone(),
+                b"loca" => new_loca.clone(),
+                b"head" => new_head.clone(),
+                b"post" => {
+                    let mut post = table(tag)?.get(..POST_HEADER_LENGTH)?.to_vec();
+                    post[..4].copy_from_slice(&POST_VERSION_3.to_be_bytes());
+                    post
+                },
+                _ => table(tag)?.to_vec(),
+            };
+            Some((*tag, data))
+        })
+        .collect();
+
+    let version = read_u32(font, 0).context("Invalid font header")?;
+    Ok(Some(write_font(version, &subset_tables)))
+}
+
+/// Returns the PostScript name of a font, e.g. `DejaVuSans-Bold`
+///
+/// # Arguments
+///
+/// * `font` - The font program
+///
+/// # Returns
+///
+/// * `Option<String>` - The name, limited to characters allowed in PDF names, or `None` if the font has none
+pub fn postscript_name(font: &[u8]) -> Option<String> {
+    let (_, range) = font_tables(font).into_iter().find(|(tag, _)| tag == b"name")?;
+    let (_, name) = font_names(font.get(range)?).into_iter().find(|(id, _)| *id == POSTSCRIPT_NAME_ID)?;
+    let name: String = name.chars().filter(|c| c.is_ascii_alphanumeric() || *c == '-').collect();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Returns the glyph ids a composite glyph is drawn from
+///
+/// # Arguments
+///
+/// * `outline` - The `glyf` data of a glyph
+///
+/// # Returns
+///
+/// * `Vec<usize>` - The component glyphs, empty for simple glyphs
+fn composite_components(outline: &[u8]) -> Vec<usize> {
+    let mut components = Vec::new();
+    // Composite glyphs have a negative number of contours
+    if read_u16(outline, 0).is_none_or(|contours| (contours as i16) >= 0) {
+        return components;
+    }
+
+    let mut offset = 10;
+    while let (Some(flags), Some(glyph)) = (read_u16(outline, offset), read_u16(outline, offset + 2)) {
+        components.push(usize::from(glyph));
+
+        offset += 4 + if flags & ARG_1_AND_2_ARE_WORDS != 0 { 4 } else { 2 };
+        if flags & WE_HAVE_A_SCALE != 0 {
+            offset += 2;
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            offset += 4;
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            offset += 8;
+        }
+        if flags & MORE_COMPONENTS == 0 {
+            break;
+        }
+    }
+    components
+}
+
+/// Writes a font file from its tables
+///
+/// # Arguments
+///
+/// * `version` - The sfnt version of the font
+/// * `tables` - The tag and data of each table, sorted by tag
+///
+/// # Returns
+///
+/// * `Vec<u8>` - The font file with table checksums and `checkSumAdjustment` filled in
+fn write_font(version: u32, tables: &[([u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let count = tables.len() as u16;
+    let entry_selector = count.max(1).ilog2() as u16;
+    let search_range = 16 << entry_selector;
+
+    let mut font = Vec::new();
+    font.extend_from_slice(&version.to_be_bytes());
+    for value in [count, search_range, entry_selector, count * 16 - search_range] {
+        font.extend_from_slice(&value.to_be_bytes());
+    }
+
+    let mut offset = 12 + 16 * tables.len();
+    for (tag, data) in tables {
+        font.extend_from_slice(tag);
+        font.extend_from_slice(&checksum(data).to_be_bytes());
+        font.extend_from_slice(&(offset as u32).to_be_bytes());
+        font.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        offset += data.len().next_multiple_of(4);
+    }
+
+    let mut head_offset = None;
+    for (tag, data) in tables {
+        if tag == b"head" {
+            head_offset = Some(font.len());
+        }
+        font.extend_from_slice(data);
+        font.resize(font.len().next_multiple_of(4), 0);
+    }
+
+    if let Some(head_offset) = head_offset {
+        let adjustment = CHECKSUM_MAGIC.wrapping_sub(checksum(&font));
+        let offset = head_offset + CHECKSUM_ADJUSTMENT_OFFSET;
+        font[offset..offset + 4].copy_from_slice(&adjustment.to_be_bytes());
+    }
+    font
+}
+
+/// Returns the checksum of a font table, the sum of its big-endian 32-bit words
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4)
+        .map(|chunk| {
+            let mut word = [0; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            u32::from_be_bytes(word)
+        })
+        .fold(0, u32::wrapping_add)
+}
@@ -1,19 +1,36 @@
 //! Module for generating Markdown files from extracted document content.
 
 use std::path::{Path, PathBuf};
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::Write;
 use anyhow::{Result, Context};
 use log::{info, debug};
 
-use crate::converter::docx_reader::DocxContent;
+use crate::converter::docx_reader::{Block, DocxContent, Image, LinkTarget, Note, Paragraph, Run, VerticalAlign};
+use crate::converter::metadata::DocumentProperties;
+use crate::converter::number_format::Locale;
+use crate::converter::options::{ConversionOptions, FormulaDisplay};
+use crate::converter::table::{Table, TableCell};
 use crate::converter::xlsx_reader::Sheet;
 
+/// Deepest heading level supported by Markdown
+const MAX_HEADING_LEVEL: usize = 6;
+
+/// Number of spaces used to indent each nested list level
+const LIST_INDENT: usize = 4;
+
+/// Suffix of the folder next to the Markdown file that holds extracted images
+const ASSETS_DIR_SUFFIX: &str = "_assets";
+
+/// Heading of the per-sheet list of formulas
+const FORMULAS_HEADING: &str = "Formulas";
+
 /// Creates a Markdown file from Word document content
 ///
 /// # Arguments
 ///
 /// * `content` - The extracted content from a Word document
+/// * `properties` - The title, author and other properties of the document
 /// * `input_path` - Path to the original Word document
 /// * `output_dir` - Directory where the Markdown will be saved
 ///
@@ -22,59 +39,70 @@ use crate::converter::xlsx_reader::Sheet;
 /// * `Result<PathBuf>` - Path to the generated Markdown file or an error
 pub fn create_markdown_from_docx(
     content: &DocxContent,
+    properties: &DocumentProperties,
     input_path: &Path,
     output_dir: &Path,
 ) -> Result<PathBuf> {
     let output_filename = generate_output_filename(input_path, output_dir)?;
     info!("Creating Markdown from Word document: {}", output_filename.display());
 
-    let mut markdown_content = String::new();
+    let mut markdown_content = render_front_matter(properties);
 
-    // Add title based on filename
-    if let Some(file_stem) = input_path.file_stem() {
-        let title = file_stem.to_string_lossy();
-        markdown_content.push_str(&format!("# {}\n\n", title));
+    // Add the title of the document, unless the document starts with a title of its own
+    let starts_with_title = matches!(content.blocks.first(),
+                                     Some(Block::Paragraph(paragraph)) if paragraph.heading_level() == Some(1));
+    if !starts_with_title {
+        markdown_content.push_str(&render_title(properties, input_path));
     }
 
-    // Add paragraphs
-    for paragraph in &content.paragraphs {
-        markdown_content.push_str(&format!("{}\n\n", paragraph));
-    }
+    // Add paragraphs and tables in document order
+    for (index, block) in content.blocks.iter().enumerate() {
+        match block {
+            Block::Paragraph(paragraph) => {
+                if let Some(level) = paragraph.heading_level() {
+                    let marker = "#".repeat(level.min(MAX_HEADING_LEVEL));
+                    let text = match &paragraph.list {
+                        Some(item) => format!("{} {}", item.marker, paragraph.text()),
+                        None => paragraph.text(),
+                    };
+                    markdown_content.push_str(&format!("{} {}{}\n\n", marker, bookmark_anchors(paragraph), text));
+                } else if let Some(item) = &paragraph.list {
+                    let indent = " ".repeat(LIST_INDENT * item.level);
+                    let marker = if item.ordered { format!("{}.", item.number) } else { "-".to_string() };
+                    markdown_content.push_str(&format!("{}{} {}{}\n", indent, marker,
+                        bookmark_anchors(paragraph), render_runs(&paragraph.runs)));
 
-    // Add tables
-    for table_data in &content.tables {
-        if !table_data.is_empty() {
-            // Create table header based on first row
-            if let Some(first_row) = table_data.first() {
-                // Table header
-                markdown_content.push_str("|");
-                for cell in first_row {
-                    markdown_content.push_str(&format!(" {} |", cell));
-                }
-                markdown_content.push_str("\n|");
-
-                // Table separator
-                for _ in first_row {
-                    markdown_content.push_str(" --- |");
-                }
-                markdown_content.push_str("\n");
-
-                // Table rows (skip first row if it was used as header)
-                let data_rows = if table_data.len() > 1 { &table_data[1..] } else { &[] };
-                for row in data_rows {
-                    markdown_content.push_str("|");
-                    for cell in row {
-                        // Escape pipe characters in cell content
-                        let escaped_cell = cell.replace("|", "\\|");
-                        markdown_content.push_str(&format!(" {} |", escaped_cell));
+                    // A blank line ends the list unless the next block continues it
+                    if !matches!(content.blocks.get(index + 1), Some(Block::Paragraph(next)) if next.list.is_some() && next.heading_level().is_none()) {
+                        markdown_content.push('\n');
                     }
-                    markdown_content.push_str("\n");
+                } else {
+                    markdown_content.push_str(&format!("{}{}\n\n", bookmark_anchors(paragraph), render_runs(&paragraph.runs)));
+                }
+            },
+            Block::Table(table) => {
+                // Pipe tables cannot merge cells, so tables with spans are written as HTML
+                if table.has_spans() {
+                    markdown_content.push_str(&render_html_table(table));
+                } else {
+                    markdown_content.push_str(&render_table(&table.grid()));
                 }
-                markdown_content.push_str("\n");
+            },
+            Block::Image(image) => {
+                let image_path = write_image_asset(image, &output_filename)?;
+                let alt_text = image.alt_text.as_deref().unwrap_or_default()
+                    .replace('[', "\\[")
+                    .replace(']', "\\]");
+                markdown_content.push_str(&format!("![{}]({})\n\n", alt_text, link_destination(&LinkTarget::Url(image_path))));
             }
         }
     }
 
+    // Footnotes and endnotes become footnote definitions at the end of the document
+    for note in &content.notes {
+        markdown_content.push_str(&render_note(note));
+    }
+
     // Write to file
     let mut file = File::create(&output_filename)
         .context(format!("Failed to create Markdown file: {}", output_filename.display()))?;
@@ -91,66 +119,66 @@ pub fn create_markdown_from_docx(
 /// # Arguments
 ///
 /// * `sheets` - The extracted sheets from an Excel workbook
+/// * `properties` - The title, author and other properties of the workbook
 /// * `input_path` - Path to the original Excel file
 /// * `output_dir` - Directory where the Markdown will be saved
+/// * `options` - Options controlling how the sheets are shown
 ///
 /// # Returns
 ///
 /// * `Result<PathBuf>` - Path to the generated Markdown file or an error
 pub fn create_markdown_from_xlsx(
     sheets: &[Sheet],
+    properties: &DocumentProperties,
     input_path: &Path,
     output_dir: &Path,
+    options: &ConversionOptions,
 ) -> Result<PathBuf> {
     let output_filename = generate_output_filename(input_path, output_dir)?;
     info!("Creating Markdown from Excel spreadsheet: {}", output_filename.display());
 
-    let mut markdown_content = String::new();
+    let mut markdown_content = render_front_matter(properties);
 
-    // Add title based on filename
-    if let Some(file_stem) = input_path.file_stem() {
-        let title = file_stem.to_string_lossy();
-        markdown_content.push_str(&format!("# {}\n\n", title));
-    }
+    // Add the title of the document
+    markdown_content.push_str(&render_title(properties, input_path));
+
+    // Cells are shown with their number formats, using the separators of the chosen or the user's locale
+    let locale = options.locale();
 
     // Process each sheet
     for (i, sheet) in sheets.iter().enumerate() {
         // Add sheet name as heading
         markdown_content.push_str(&format!("## Sheet: {}\n\n", sheet.name));
 
-        if !sheet.data.is_empty() {
-            // Create table header based on first row
-            if let Some(first_row) = sheet.data.first() {
-                // Table header
-                markdown_content.push_str("|");
-                for cell in first_row {
-                    // Escape pipe characters in cell content
-                    let escaped_cell = cell.replace("|", "\\|");
-                    markdown_content.push_str(&format!(" {} |", escaped_cell));
-                }
-                markdown_content.push_str("\n|");
+        // Only the tables and named ranges of the sheet are shown when converting tables only
+        let mut formulas = Vec::new();
+        if !options.tables_only {
+            if !sheet.data.is_empty() {
+                markdown_content.push_str(&render_sheet_cells(sheet, options, &locale));
+            } else {
+                markdown_content.push_str("*(Empty sheet)*\n\n");
+            }
+            formulas.extend(sheet.formulas());
+        }
 
-                // Table separator
-                for _ in first_row {
-                    markdown_content.push_str(" --- |");
-                }
-                markdown_content.push_str("\n");
-
-                // Table rows (skip first row if it was used as header)
-                let data_rows = if sheet.data.len() > 1 { &sheet.data[1..] } else { &[] };
-                for row in data_rows {
-                    markdown_content.push_str("|");
-                    for cell in row {
-                        // Escape pipe characters in cell content
-                        let escaped_cell = cell.replace("|", "\\|");
-                        markdown_content.push_str(&format!(" {} |", escaped_cell));
+        for table in &sheet.tables {
+            markdown_content.push_str(&format!("### {}: {} ({})\n\n", table.kind, table.name, table.range));
+            markdown_content.push_str(&render_sheet_cells(&table.cells, options, &locale));
+            if options.tables_only {
+                for formula in table.cells.formulas() {
+                    if !formulas.contains(&formula) {
+                        formulas.push(formula);
                     }
-                    markdown_content.push_str("\n");
                 }
-                markdown_content.push_str("\n");
             }
-        } else {
-            markdown_content.push_str("*(Empty sheet)*\n\n");
+        }
+
+        // List the formulas of the sheet after its data
+        if options.formulas == FormulaDisplay::Appendix && !formulas.is_empty() {
+            markdown_content.push_str(&format!("### {}\n\n", FORMULAS_HEADING));
+            let mut rows = vec![vec!["Cell".to_string(), "Formula".to_string()]];
+            rows.extend(formulas.into_iter().map(|(reference, formula)| vec![reference, formula]));
+            markdown_content.push_str(&render_table(&rows));
         }
 
         // Add separator between sheets (except for the last one)
@@ -170,6 +198,339 @@ pub fn create_markdown_from_xlsx(
     Ok(output_filename)
 }
 
+/// Renders the cells of a sheet, or of a table on it, as a Markdown table
+///
+/// # Arguments
+///
+/// * `sheet` - The sheet cells to render
+/// * `options` - Options controlling how formulas and grid labels are shown
+/// * `locale` - Locale used to format the cell values
+///
+/// # Returns
+///
+/// * `String` - The rendered table
+fn render_sheet_cells(sheet: &Sheet, options: &ConversionOptions, locale: &Locale) -> String {
+    let rows = match options.formulas {
+        FormulaDisplay::Inline => sheet.display_rows_with_formulas(locale),
+        _ => sheet.display_rows(locale),
+    };
+    let (preamble, table) = if options.grid_labels {
+        (Vec::new(), sheet.add_grid_labels(sheet.table(&rows)))
+    } else {
+        sheet.body_table(&rows)
+    };
+
+    // Title blocks and notes above the header are written as paragraphs
+    let mut markdown = String::new();
+    for line in preamble {
+        markdown.push_str(&format!("{}\n\n", line));
+    }
+
+    // Markdown tables have a single header row, so multi-row headers are flattened;
+    // without a header, the column letters are used
+    let mut table = table.flatten_header();
+    if table.header_rows == 0 {
+        let letters = sheet.column_letters()
+            .into_iter()
+            .enumerate()
+            .map(|(column, letters)| TableCell::new(letters, column))
+            .collect();
+        table.rows.insert(0, letters);
+        table.header_rows = 1;
+    }
+
+    // Pipe tables cannot merge cells, so sheets with merged cells are written as HTML
+    if table.has_spans() {
+        markdown.push_str(&render_html_table(&table));
+    } else {
+        markdown.push_str(&render_table(&table.grid()));
+    }
+
+    markdown
+}
+
+/// Renders the title of a document as a level-one heading
+///
+/// # Arguments
+///
+/// * `properties` - The title, author and other properties of the document
+/// * `input_path` - Path to the original document, whose file name is used if it has no title
+///
+/// # Returns
+///
+/// * `String` - The heading followed by a blank line, or an empty string if there is no title
+fn render_title(properties: &DocumentProperties, input_path: &Path) -> String {
+    properties.title.clone()
+        .or_else(|| Some(input_path.file_stem()?.to_string_lossy().into_owned()))
+        .map(|title| format!("# {}\n\n", title))
+        .unwrap_or_default()
+}
+
+/// Renders the document properties as YAML front matter
+///
+/// # Arguments
+///
+/// * `properties` - The title, author and other properties of the document
+///
+/// # Returns
+///
+/// * `String` - The front matter followed by a blank line, or an empty string if no property is set
+fn render_front_matter(properties: &DocumentProperties) -> String {
+    if properties.is_empty() {
+        return String::new();
+    }
+
+    let mut front_matter = String::from("---\n");
+    for (name, value) in properties.entries() {
+        // Double-quoted YAML strings keep colons and other indicators literal
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+        front_matter.push_str(&format!("{}: \"{}\"\n", name, value));
+    }
+    front_matter.push_str("---\n\n");
+    front_matter
+}
+
+/// Writes an image to the assets folder next to a Markdown file
+///
+/// # Arguments
+///
+/// * `image` - The image to write
+/// * `markdown_path` - Path of the Markdown file referencing the image
+///
+/// # Returns
+///
+/// * `Result<String>` - Path of the image relative to the Markdown file, or an error
+fn write_image_asset(image: &Image, markdown_path: &Path) -> Result<String> {
+    let file_stem = markdown_path.file_stem()
+        .context("Failed to get file name")?
+        .to_string_lossy();
+    let assets_name = format!("{}{}", file_stem, ASSETS_DIR_SUFFIX);
+    let assets_dir = markdown_path.with_file_name(&assets_name);
+
+    fs::create_dir_all(&assets_dir)
+        .context(format!("Failed to create assets directory: {}", assets_dir.display()))?;
+
+    let image_path = assets_dir.join(&image.name);
+    fs::write(&image_path, &image.data)
+        .context(format!("Failed to write image: {}", image_path.display()))?;
+    debug!("Wrote image asset: {}", image_path.display());
+
+    Ok(format!("{}/{}", assets_name, image.name))
+}
+
+/// Renders formatted runs as inline Markdown
+///
+/// Bold, italic, strikethrough and monospace runs use Markdown syntax; underline,
+/// superscript and subscript have no Markdown equivalent and use inline HTML.
+/// Consecutive runs of the same hyperlink become a single Markdown link.
+///
+/// # Arguments
+///
+/// * `runs` - The runs of a paragraph
+///
+/// # Returns
+///
+/// * `String` - The paragraph text with inline formatting
+fn render_runs(runs: &[Run]) -> String {
+    let mut text = String::new();
+
+    for group in runs.chunk_by(|a, b| a.link == b.link) {
+        let rendered: String = group.iter().map(render_run).collect();
+        match &group[0].link {
+            Some(link) => {
+                // Keep surrounding whitespace outside the link text
+                let core = rendered.trim();
+                let leading = &rendered[..rendered.len() - rendered.trim_start().len()];
+                let trailing = &rendered[rendered.trim_end().len()..];
+                text.push_str(&format!("{}[{}]({}){}", leading, core, link_destination(link), trailing));
+            },
+            None => text.push_str(&rendered),
+        }
+    }
+
+    text
+}
+
+/// Renders a single run as inline Markdown
+fn render_run(run: &Run) -> String {
+    if run.note.is_some() {
+        return format!("[^{}]", run.text);
+    }
+
+    // Emphasis markers must hug the text, so keep surrounding whitespace outside them
+    let core = run.text.trim();
+    if core.is_empty() {
+        return run.text.clone();
+    }
+    let leading = &run.text[..run.text.len() - run.text.trim_start().len()];
+    let trailing = &run.text[run.text.trim_end().len()..];
+
+    let mut inline = if run.format.monospace {
+        code_span(core)
+    } else {
+        core.to_string()
+    };
+    match run.format.vertical_align {
+        VerticalAlign::Superscript => inline = format!("<sup>{}</sup>", inline),
+        VerticalAlign::Subscript => inline = format!("<sub>{}</sub>", inline),
+        VerticalAlign::Baseline => {}
+    }
+    // Links are underlined by the Hyperlink style; the Markdown link already shows that
+    if run.format.underline && run.link.is_none() {
+        inline = format!("<u>{}</u>", inline);
+    }
+    if run.format.strike {
+        inline = format!("~~{}~~", inline);
+    }
+    if run.format.italic {
+        inline = format!("*{}*", inline);
+    }
+    if run.format.bold {
+        inline = format!("**{}**", inline);
+    }
+
+    format!("{}{}{}", leading, inline, trailing)
+}
+
+/// Renders a footnote or endnote as a Markdown footnote definition
+///
+/// Paragraphs after the first are indented so they stay part of the definition.
+fn render_note(note: &Note) -> String {
+    let mut definition = format!("[^{}]:", note.label);
+    for (index, paragraph) in note.paragraphs.iter().enumerate() {
+        if index == 0 {
+            definition.push_str(&format!(" {}\n", paragraph));
+        } else {
+            definition.push_str(&format!("\n{}{}\n", " ".repeat(LIST_INDENT), paragraph));
+        }
+    }
+    if note.paragraphs.is_empty() {
+        definition.push('\n');
+    }
+    definition.push('\n');
+    definition
+}
+
+/// Returns the Markdown link destination for a hyperlink target
+fn link_destination(link: &LinkTarget) -> String {
+    match link {
+        LinkTarget::Url(url) if url.contains(|c: char| c.is_whitespace() || c == '(' || c == ')') => {
+            format!("<{}>", url)
+        },
+        LinkTarget::Url(url) => url.clone(),
+        LinkTarget::Anchor(anchor) => format!("#{}", anchor),
+    }
+}
+
+/// Returns HTML anchors for the bookmarks of a paragraph, so internal links can point at it
+fn bookmark_anchors(paragraph: &Paragraph) -> String {
+    paragraph.bookmarks.iter()
+        .map(|name| format!("<a id=\"{}\"></a>", name))
+        .collect()
+}
+
+/// Wraps text in a code span, using enough backticks to enclose any it contains
+fn code_span(text: &str) -> String {
+    let mut fence = String::from("`");
+    while text.contains(&fence) {
+        fence.push('`');
+    }
+
+    if fence.len() > 1 {
+        format!("{} {} {}", fence, text, fence)
+    } else {
+        format!("{}{}{}", fence, text, fence)
+    }
+}
+
+/// Renders rows of cells as a Markdown pipe table, using the first row as the header
+///
+/// # Arguments
+///
+/// * `rows` - The table data as rows of cells
+///
+/// # Returns
+///
+/// * `String` - The Markdown table followed by a blank line, or an empty string if there are no rows
+fn render_table(rows: &[Vec<String>]) -> String {
+    let mut table = String::new();
+
+    if let Some(first_row) = rows.first() {
+        // Table header
+        table.push('|');
+        for cell in first_row {
+            // Escape pipe characters in cell content
+            table.push_str(&format!(" {} |", escape_cell(cell)));
+        }
+        table.push_str("\n|");
+
+        // Table separator
+        for _ in first_row {
+            table.push_str(" --- |");
+        }
+        table.push('\n');
+
+        // Table rows (skip first row since it was used as header)
+        for row in &rows[1..] {
+            table.push('|');
+            for cell in row {
+                table.push_str(&format!(" {} |", escape_cell(cell)));
+            }
+            table.push('\n');
+        }
+        table.push('\n');
+    }
+
+    table
+}
+
+/// Renders a table with merged cells as an HTML table, using its header rows or else the first row as the header
+///
+/// # Arguments
+///
+/// * `table` - The table to render
+///
+/// # Returns
+///
+/// * `String` - The HTML table followed by a blank line, or an empty string if there are no rows
+fn render_html_table(table: &Table) -> String {
+    if table.is_empty() {
+        return String::new();
+    }
+
+    let mut html = String::from("<table>\n");
+    for (index, row) in table.rows.iter().enumerate() {
+        let tag = if index < table.header_rows.max(1) { "th" } else { "td" };
+        html.push_str("  <tr>");
+        for cell in row {
+            html.push_str(&format!("<{}", tag));
+            if cell.row_span > 1 {
+                html.push_str(&format!(" rowspan=\"{}\"", cell.row_span));
+            }
+            if cell.col_span > 1 {
+                html.push_str(&format!(" colspan=\"{}\"", cell.col_span));
+            }
+            html.push_str(&format!(">{}</{}>", escape_html(&cell.text), tag));
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n\n");
+
+    html
+}
+
+/// Escapes characters with special meaning in HTML text
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Escapes characters that would break a Markdown table cell
+fn escape_cell(cell: &str) -> String {
+    cell.replace('|', "\\|")
+}
+
 /// Generates an output filename for the Markdown based on the input file
 ///
 /// # Arguments
@@ -0,0 +1,357 @@
+//! Document properties such as the title and author of source documents.
+//! Office Open XML packages (DOCX, XLSX) keep them in `docProps/core.xml` and
+//! `docProps/app.xml`, OpenDocument files in `meta.xml`.
+
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::{Result, Context};
+use log::{debug, warn};
+use quick_xml::events::Event;
+
+use crate::converter::number_format;
+use crate::converter::package;
+
+/// Package parts holding the properties of Office Open XML documents
+const OOXML_PROPERTY_PARTS: [&str; 2] = ["docProps/core.xml", "docProps/app.xml"];
+
+/// Package part holding the properties of OpenDocument files
+const ODF_PROPERTY_PART: &str = "meta.xml";
+
+/// Descriptive properties of a document
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DocumentProperties {
+    /// Title of the document
+    pub title: Option<String>,
+    /// Author who created the document
+    pub author: Option<String>,
+    /// Subject of the document
+    pub subject: Option<String>,
+    /// Keywords, as written in the document (usually separated by commas or semicolons)
+    pub keywords: Option<String>,
+    /// Creation date and time, as a W3C date such as `2024-03-01T09:30:00Z`
+    pub created: Option<String>,
+    /// Date and time of the last modification, as a W3C date
+    pub modified: Option<String>,
+    /// Company the document belongs to
+    pub company: Option<String>,
+}
+
+impl DocumentProperties {
+    /// Returns `true` if no property is set
+    pub fn is_empty(&self) -> bool {
+        *self == DocumentProperties::default()
+    }
+
+    /// Returns the name and value of each property that is set, in a fixed order
+    pub fn entries(&self) -> Vec<(&'static str, &str)> {
+        [
+            ("title", &self.title),
+            ("author", &self.author),
+            ("subject", &self.subject),
+            ("keywords", &self.keywords),
+            ("created", &self.created),
+            ("modified", &self.modified),
+            ("company", &self.company),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value.as_deref()?)))
+        .collect()
+    }
+}
+
+/// Reads the properties of a document
+///
+/// Legacy XLS workbooks keep their properties in a binary stream that is not read,
+/// so they have no properties.
+///
+/// # Arguments
+///
+/// * `path` - Path to the document
+///
+/// # Returns
+///
+/// * `Result<DocumentProperties>` - The properties found in the document, or an error
+pub fn read_properties(path: &Path) -> Result<DocumentProperties> {
+    let extension = path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let parts: &[&str] = match extension.as_str() {
+        "xls" => {
+            debug!("Properties of XLS workbooks are not read: {}", path.display());
+            return Ok(DocumentProperties::default());
+        },
+        "ods" => &[ODF_PROPERTY_PART],
+        _ => &OOXML_PROPERTY_PARTS,
+    };
+
+    let buf = std::fs::read(path)
+        .context(format!("Failed to read document: {}", path.display()))?;
+    let mut properties = DocumentProperties::default();
+    for part in parts {
+        if let Some(xml) = package::read_package_part(&buf, part)? {
+            read_property_elements(&xml, &mut properties);
+        }
+    }
+
+    debug!("Read document properties: {:?}", properties);
+    Ok(properties)
+}
+
+/// Reads the property elements of a metadata part into the properties
+///
+/// The elements are matched by local name, which covers Dublin Core and the Office
+/// and OpenDocument namespaces alike.
+///
+/// # Arguments
+///
+/// * `xml` - The XML of the metadata part
+/// * `properties` - The properties to fill in
+fn read_property_elements(xml: &str, properties: &mut DocumentProperties) {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut element: Vec<u8> = Vec::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(start)) => element = start.local_name().as_ref().to_vec(),
+            Ok(Event::End(_)) => element.clear(),
+            Ok(Event::Text(text)) => {
+                let Ok(text) = text.unescape() else {
+                    continue;
+                };
+                let text = text.trim();
+                if text.is_empty() {
+                    continue;
+                }
+                let text = text.to_string();
+
+                match element.as_slice() {
+                    b"title" => properties.title = Some(text),
+                    // OpenDocument names the last editor dc:creator and the author meta:initial-creator
+                    b"initial-creator" => properties.author = Some(text),
+                    b"creator" => { properties.author.get_or_insert(text); },
+                    b"subject" => properties.subject = Some(text),
+                    b"keywords" => properties.keywords = Some(text),
+                    // OpenDocument lists each keyword in its own element
+                    b"keyword" => match &mut properties.keywords {
+                        Some(keywords) => {
+                            keywords.push_str(", ");
+                            keywords.push_str(&text);
+                        },
+                        None => properties.keywords = Some(text),
+                    },
+                    b"created" | b"creation-date" => properties.created = Some(text),
+                    b"modified" | b"date" => properties.modified = Some(text),
+                    b"Company" => properties.company = Some(text),
+                    _ => {}
+                }
+            },
+            Ok(Event::Eof) => break,
+            Err(err) => {
+                warn!("Failed to parse document properties: {}", err);
+                break;
+            },
+            _ => {}
+        }
+    }
+}
+
+/// A date and time, as read from a W3C date or taken at the start of a conversion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    year: u32,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+    /// Offset from UTC in minutes, `None` for local time without an offset
+    offset: Option<i32>,
+}
+
+impl Timestamp {
+    /// Parses a W3C date such as `2024-03-01`, `2024-03-01T09:30:00Z` or `2024-03-01T09:30:00.5+01:00`
+    pub fn parse(date: &str) -> Option<Timestamp> {
+        let date = date.trim();
+        let (day_part, time_part) = date.split_once('T').unwrap_or((date, ""));
+        let mut fields = day_part.splitn(3, '-').map(|field| field.parse::<u32>().ok());
+        let year = fields.next()??;
+        let month = fields.next().unwrap_or(Some(1))?;
+        let day = fields.next().unwrap_or(Some(1))?;
+        if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+            return None;
+        }
+
+        // The time is followed by `Z`, an offset such as `+01:00`, or nothing for local time
+        let (time, offset) = match time_part.find(['Z', 'z', '+', '-']) {
+            Some(index) => {
+                let (time, zone) = time_part.split_at(index);
+                let offset = match zone {
+                    "Z" | "z" => 0,
+                    zone => {
+                        let sign = if zone.starts_with('-') { -1 } else { 1 };
+                        // Offsets are written as two-digit hours and minutes, e.g. `+01:00`
+                        let (hours, minutes) = zone[1..].split_once(':')?;
+                        let field = |value: &str, max: i32| -> Option<i32> {
+                            if value.len() != 2 || !value.bytes().all(|byte| byte.is_ascii_digit()) {
+                                return None;
+                            }
+                            value.parse::<i32>().ok().filter(|value| *value <= max)
+                        };
+                        sign * (field(hours, 23)? * 60 + field(minutes, 59)?)
+                    },
+                };
+                (time, Some(offset))
+            },
+            None if time_part.is_empty() => ("", Some(0)),
+            None => (time_part, None),
+        };
+
+        let mut fields = time.split(':').filter(|field| !field.is_empty());
+        let mut field = |max: u32| -> Option<u32> {
+            match fields.next() {
+                // Fractions of seconds are dropped
+                Some(value) => value.split('.').next()?.parse::<u32>().ok().filter(|value| *value <= max),
+                None => Some(0),
+            }
+        };
+        Some(Timestamp { year, month, day, hour: field(23)?, minute: field(59)?, second: field(60)?, offset })
+    }
+
+    /// Parses a PDF date such as `D:20240301093000Z` or `D:20240301093000+01'00'`
+    pub fn parse_pdf(date: &str) -> Option<Timestamp> {
+        let date = date.trim();
+        let date = date.strip_prefix("D:").unwrap_or(date);
+        let (time, zone) = date.split_at(date.find(|c: char| !c.is_ascii_digit()).unwrap_or(date.len()));
+        if time.len() < 4 || time.len() > 14 || time.len() % 2 != 0 {
+            return None;
+        }
+
+        // Fields after the year may be left out, from the right
+        let field = |start: usize, default: u32| -> Option<u32> {
+            match time.get(start..start + 2) {
+                Some(value) => value.parse().ok(),
+                None => Some(default),
+            }
+        };
+        let year = time[..4].parse().ok()?;
+        let (month, day) = (field(4, 1)?, field(6, 1)?);
+        if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+            return None;
+        }
+        let (hour, minute, second) = (field(8, 0)?, field(10, 0)?, field(12, 0)?);
+        if hour > 23 || minute > 59 || second > 60 {
+            return None;
+        }
+
+        // The time is followed by `Z`, an offset such as `+01'00'`, or nothing for local time
+        let offset = match zone.chars().next() {
+            None => None,
+            Some('Z') => Some(0),
+            Some(sign @ ('+' | '-')) => {
+                let zone = zone[1..].replace('\'', "");
+                let hours = zone.get(..2)?.parse::<i32>().ok()?;
+                let minutes = zone.get(2..4).map_or(Some(0), |minutes| minutes.parse::<i32>().ok())?;
+                Some(if sign == '-' { -1 } else { 1 } * (hours * 60 + minutes))
+            },
+            Some(_) => return None,
+        };
+        Some(Timestamp { year, month, day, hour, minute, second, offset })
+    }
+
+    /// Returns `true` if both times are the same point in time; times without an offset are compared as written
+    pub fn same_time(self, other: Timestamp) -> bool {
+        match (self.offset, other.offset) {
+            (Some(offset), Some(other_offset)) => {
+                self.seconds() - i64::from(offset) * 60 == other.seconds() - i64::from(other_offset) * 60
+            },
+            _ => self == other,
+        }
+    }
+
+    /// Returns the seconds since 1970-01-01 of the time as written, ignoring its offset
+    fn seconds(self) -> i64 {
+        number_format::days_from_civil(i64::from(self.year), self.month, self.day) * 86_400
+            + i64::from(self.hour * 3600 + self.minute * 60 + self.second)
+    }
+
+    /// Returns the current time in UTC
+    pub fn now() -> Timestamp {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let (year, month, day) = number_format::civil_from_days((seconds / 86_400) as i64);
+        let time = seconds % 86_400;
+        Timestamp {
+            year: year as u32,
+            month,
+            day,
+            hour: (time / 3600) as u32,
+            minute: (time / 60 % 60) as u32,
+            second: (time % 60) as u32,
+            offset: Some(0),
+        }
+    }
+
+    /// Formats the time as a W3C date, e.g. `2024-03-01T09:30:00Z`
+    pub fn to_w3c(self) -> String {
+        let offset = match self.offset {
+            Some(0) => "Z".to_string(),
+            Some(offset) => format!("{}{:02}:{:02}", if offset < 0 { '-' } else { '+' }, offset.abs() / 60, offset.abs() % 60),
+            None => String::new(),
+        };
+        format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}",
+                self.year, self.month, self.day, self.hour, self.minute, self.second, offset)
+    }
+
+    /// Formats the time as a PDF date, e.g. `D:20240301093000Z`
+    pub fn to_pdf(self) -> String {
+        let offset = match self.offset {
+            Some(0) => "Z".to_string(),
+            Some(offset) => format!("{}{:02}'{:02}'", if offset < 0 { '-' } else { '+' }, offset.abs() / 60, offset.abs() % 60),
+            None => String::new(),
+        };
+        format!("D:{:04}{:02}{:02}{:02}{:02}{:02}{}",
+                self.year, self.month, self.day, self.hour, self.minute, self.second, offset)
+    }
+
+    /// Formats the day as YYYY-MM-DD
+    pub fn to_date(self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// Returns the number of days in a month of the Gregorian calendar
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Returns a W3C date in normalized form, as used in XMP metadata
+///
+/// # Arguments
+///
+/// * `date` - A W3C date
+///
+/// # Returns
+///
+/// * `Option<String>` - The date with seconds, e.g. `2024-03-01T09:30:00Z`, or `None` if it cannot be read
+pub fn w3c_date(date: &str) -> Option<String> {
+    Timestamp::parse(date).map(Timestamp::to_w3c)
+}
+
+/// Converts a W3C date to a PDF date, as used in the document information dictionary
+///
+/// # Arguments
+///
+/// * `date` - A W3C date
+///
+/// # Returns
+///
+/// * `Option<String>` - The PDF date, e.g. `D:20240301093000Z`, or `None` if it cannot be read
+pub fn pdf_date(date: &str) -> Option<String> {
+    Timestamp::parse(date).map(Timestamp::to_pdf)
+}
@@ -2,13 +2,27 @@
 //! This module contains functionality for converting various document formats to PDF and Markdown.
 
 pub mod docx_reader;
+pub mod font_loader;
+pub mod font_subset;
 pub mod xlsx_reader;
 pub mod pdf_writer;
+pub mod pdf_elements;
+pub mod pdf_archive;
+pub mod pdf_postprocess;
 pub mod markdown_writer;
+pub mod metadata;
+pub mod number_format;
+pub mod options;
+pub mod package;
+pub mod sheet_layout;
+pub mod table;
 
 use std::path::{Path, PathBuf};
 use anyhow::{Result, Context};
-use log::{info, error};
+use log::{info, warn, error};
+
+pub use metadata::DocumentProperties;
+pub use options::{parse_line_spacing, MIN_FONT_SIZE, ConversionOptions, FormulaDisplay, Orientation, PageMargins, PageSetup, PaperSize, PdfOptions, SheetRange};
 
 /// Converts a document to PDF format.
 /// 
@@ -21,6 +35,23 @@ use log::{info, error};
 /// 
 /// * `Result<PathBuf>` - Path to the generated PDF file or an error
 pub fn convert_to_pdf(input_path: &Path, output_dir: &Path) -> Result<PathBuf> {
+    convert_to_pdf_with_options(input_path, output_dir, &ConversionOptions::default())
+}
+
+/// Converts a document to PDF format with the given options.
+/// 
+/// # Arguments
+/// 
+/// * `input_path` - Path to the input document
+/// * `output_dir` - Directory where the output PDF will be saved
+/// * `options` - Options controlling the conversion
+/// 
+/// # Returns
+/// 
+/// * `Result<PathBuf>` - Path to the generated PDF file or an error
+pub fn convert_to_pdf_with_options(input_path: &Path, output_dir: &Path, options: &ConversionOptions) -> Result<PathBuf> {
+    options.pdf.validate()?;
+
     let file_name = input_path.file_name()
         .context("Failed to get file name")?;
 
@@ -34,22 +65,28 @@ pub fn convert_to_pdf(input_path: &Path, output_dir: &Path) -> Result<PathBuf> {
         "docx" => {
             info!("Detected Word document");
             let content = docx_reader::extract_content(input_path)?;
-            match pdf_writer::create_pdf_from_docx(&content, input_path, output_dir) {
+            let properties = document_properties(input_path);
+            match pdf_writer::create_pdf_from_docx(&content, &properties, input_path, output_dir, options) {
                 Ok(pdf_path) => pdf_path,
+                // A Markdown file is no substitute for an archival PDF
+                Err(err) if options.pdf.pdf_a => return Err(err),
                 Err(err) => {
                     error!("Failed to create PDF: {}. Falling back to Markdown.", err);
-                    markdown_writer::create_markdown_from_docx(&content, input_path, output_dir)?
+                    markdown_writer::create_markdown_from_docx(&content, &properties, input_path, output_dir)?
                 }
             }
         },
-        "xlsx" | "xls" => {
-            info!("Detected Excel spreadsheet");
-            let sheets = xlsx_reader::extract_sheets(input_path)?;
-            match pdf_writer::create_pdf_from_xlsx(&sheets, input_path, output_dir) {
+        "xlsx" | "xlsm" | "xlsb" | "xls" | "ods" => {
+            info!("Detected spreadsheet");
+            let sheets = xlsx_reader::extract_sheets(input_path, options)?;
+            let properties = document_properties(input_path);
+            match pdf_writer::create_pdf_from_xlsx(&sheets, &properties, input_path, output_dir, options) {
                 Ok(pdf_path) => pdf_path,
+                // A Markdown file is no substitute for an archival PDF
+                Err(err) if options.pdf.pdf_a => return Err(err),
                 Err(err) => {
                     error!("Failed to create PDF: {}. Falling back to Markdown.", err);
-                    markdown_writer::create_markdown_from_xlsx(&sheets, input_path, output_dir)?
+                    markdown_writer::create_markdown_from_xlsx(&sheets, &properties, input_path, output_dir, options)?
                 }
             }
         },
@@ -74,6 +111,21 @@ pub fn convert_to_pdf(input_path: &Path, output_dir: &Path) -> Result<PathBuf> {
 /// 
 /// * `Result<PathBuf>` - Path to the generated Markdown file or an error
 pub fn convert_to_markdown(input_path: &Path, output_dir: &Path) -> Result<PathBuf> {
+    convert_to_markdown_with_options(input_path, output_dir, &ConversionOptions::default())
+}
+
+/// Converts a document to Markdown format with the given options.
+/// 
+/// # Arguments
+/// 
+/// * `input_path` - Path to the input document
+/// * `output_dir` - Directory where the output Markdown will be saved
+/// * `options` - Options controlling the conversion
+/// 
+/// # Returns
+/// 
+/// * `Result<PathBuf>` - Path to the generated Markdown file or an error
+pub fn convert_to_markdown_with_options(input_path: &Path, output_dir: &Path, options: &ConversionOptions) -> Result<PathBuf> {
     let file_name = input_path.file_name()
         .context("Failed to get file name")?;
 
@@ -87,12 +139,14 @@ pub fn convert_to_markdown(input_path: &Path, output_dir: &Path) -> Result<PathB
         "docx" => {
             info!("Detected Word document");
             let content = docx_reader::extract_content(input_path)?;
-            markdown_writer::create_markdown_from_docx(&content, input_path, output_dir)?
+            let properties = document_properties(input_path);
+            markdown_writer::create_markdown_from_docx(&content, &properties, input_path, output_dir)?
         },
-        "xlsx" | "xls" => {
-            info!("Detected Excel spreadsheet");
-            let sheets = xlsx_reader::extract_sheets(input_path)?;
-            markdown_writer::create_markdown_from_xlsx(&sheets, input_path, output_dir)?
+        "xlsx" | "xlsm" | "xlsb" | "xls" | "ods" => {
+            info!("Detected spreadsheet");
+            let sheets = xlsx_reader::extract_sheets(input_path, options)?;
+            let properties = document_properties(input_path);
+            markdown_writer::create_markdown_from_xlsx(&sheets, &properties, input_path, output_dir, options)?
         },
         _ => {
             error!("Unsupported file format: {}", extension);
@@ -104,6 +158,22 @@ pub fn convert_to_markdown(input_path: &Path, output_dir: &Path) -> Result<PathB
     Ok(result)
 }
 
+/// Reads the properties of a document, which are left empty if they cannot be read.
+/// 
+/// # Arguments
+/// 
+/// * `input_path` - Path to the input document
+/// 
+/// # Returns
+/// 
+/// * `DocumentProperties` - The title, author and other properties of the document
+fn document_properties(input_path: &Path) -> DocumentProperties {
+    metadata::read_properties(input_path).unwrap_or_else(|err| {
+        warn!("Failed to read document properties of {}: {:#}", input_path.display(), err);
+        DocumentProperties::default()
+    })
+}
+
 /// Batch converts all supported documents in a directory to PDF.
 /// 
 /// # Arguments
@@ -140,16 +210,13 @@ pub fn batch_convert(input_dir: &Path, output_dir: &Path) -> Result<Vec<PathBuf>
         }
 
         // Check if file extension is supported
-        if let Some(ext) = path.extension() {
-            let ext_str = ext.to_string_lossy().to_lowercase();
-            if ext_str == "docx" || ext_str == "xlsx" || ext_str == "xls" {
-                match convert_to_pdf(path, output_dir) {
-                    Ok(pdf_path) => {
-                        results.push(pdf_path);
-                    },
-                    Err(err) => {
-                        error!("Failed to convert {}: {}", path.display(), err);
-                    }
+        if crate::utils::is_supported_file(path) {
+            match convert_to_pdf(path, output_dir) {
+                Ok(pdf_path) => {
+                    results.push(pdf_path);
+                },
+                Err(err) => {
+                    error!("Failed to convert {}: {}", path.display(), err);
                 }
             }
         }
@@ -195,16 +262,13 @@ pub fn batch_convert_to_markdown(input_dir: &Path, output_dir: &Path) -> Result<
         }
 
         // Check if file extension is supported
-        if let Some(ext) = path.extension() {
-            let ext_str = ext.to_string_lossy().to_lowercase();
-            if ext_str == "docx" || ext_str == "xlsx" || ext_str == "xls" {
-                match convert_to_markdown(path, output_dir) {
-                    Ok(md_path) => {
-                        results.push(md_path);
-                    },
-                    Err(err) => {
-                        error!("Failed to convert {} to Markdown: {}", path.display(), err);
-                    }
+        if crate::utils::is_supported_file(path) {
+            match convert_to_markdown(path, output_dir) {
+                Ok(md_path) => {
+                    results.push(md_path);
+                },
+                Err(err) => {
+                    error!("Failed to convert {} to Markdown: {}", path.display(), err);
                 }
             }
         }
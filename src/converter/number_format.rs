@@ -0,0 +1,974 @@
+//! Excel-style display of spreadsheet cell values.
+//! Number format codes are applied the way Excel shows them, using the decimal
+//! and thousands separators of a locale.
+
+use crate::converter::xlsx_reader::CellValue;
+
+/// Serial number of the fictitious 29 February 1900 kept by Excel for Lotus compatibility
+const LEAP_BUG_SERIAL: i64 = 60;
+
+/// Days from 31 December 1899 (serial 0) to the Unix epoch, ignoring the leap year bug
+const UNIX_EPOCH_SERIAL: i64 = 25568;
+
+/// Serial number of 31 December 9999, the last date Excel can show
+const MAX_DATE_SERIAL: f64 = 2_958_465.0;
+
+/// Text Excel shows for values it cannot display, such as negative dates
+const INVALID_VALUE_TEXT: &str = "#######";
+
+/// Seconds in a day
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+/// Significant digits Excel keeps for numbers
+const EXCEL_PRECISION: i32 = 15;
+
+/// Significant digits shown by the General format
+const GENERAL_DIGITS: i32 = 10;
+
+/// Abbreviated month names, as shown by "mmm"
+const MONTH_ABBREVIATIONS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// Full month names, as shown by "mmmm"
+const MONTH_NAMES: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June",
+    "July", "August", "September", "October", "November", "December",
+];
+
+/// Abbreviated day names starting on Sunday, as shown by "ddd"
+const DAY_ABBREVIATIONS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+/// Full day names starting on Sunday, as shown by "dddd"
+const DAY_NAMES: [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+
+/// Built-in format ID of the system short date
+const SHORT_DATE_FORMAT_ID: u32 = 14;
+
+/// Built-in format ID of the system short date with time
+const SHORT_DATE_TIME_FORMAT_ID: u32 = 22;
+
+/// Number format of a spreadsheet cell
+#[derive(Debug, Clone, PartialEq)]
+pub enum NumberFormat {
+    /// One of Excel's built-in formats, by ID
+    Builtin(u32),
+    /// A custom format code such as `#,##0.00 "€"`
+    Custom(String),
+}
+
+/// Separators and date order used to display numbers
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locale {
+    /// Character between the integer and fractional digits
+    pub decimal_separator: char,
+    /// Character between groups of three integer digits
+    pub thousands_separator: char,
+    /// Format code of the system short date, used by built-in formats 14 and 22
+    pub short_date: String,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale {
+            decimal_separator: '.',
+            thousands_separator: ',',
+            short_date: "m/d/yyyy".to_string(),
+        }
+    }
+}
+
+impl Locale {
+    /// Creates the locale for a language tag such as "de-DE" or "fr_FR.UTF-8"
+    ///
+    /// Unknown languages use the United States conventions, as Excel does by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag` - BCP 47 or POSIX locale name
+    ///
+    /// # Returns
+    ///
+    /// * `Locale` - The separators and short date format of the locale
+    pub fn from_tag(tag: &str) -> Locale {
+        let name = tag.split(['.', '@']).next().unwrap_or_default();
+        let mut parts = name.split(['-', '_']);
+        let language = parts.next().unwrap_or_default().to_lowercase();
+        let region = parts.next().unwrap_or_default().to_uppercase();
+
+        let (decimal_separator, thousands_separator) = match (language.as_str(), region.as_str()) {
+            ("de" | "it" | "fr", "CH") => ('.', '\''),
+            ("es", "MX" | "US") => ('.', ','),
+            ("de" | "nl" | "it" | "es" | "pt" | "da" | "id" | "tr" | "el" | "ro" | "hr" | "sl" | "sr" | "vi", _) => (',', '.'),
+            ("fr" | "ru" | "pl" | "cs" | "sk" | "sv" | "fi" | "nb" | "no" | "nn" | "uk" | "hu" | "bg" | "lt" | "lv" | "et", _) => {
+                (',', '\u{a0}')
+            },
+            _ => ('.', ','),
+        };
+
+        let short_date = match (language.as_str(), region.as_str()) {
+            ("en", "" | "US") => "m/d/yyyy",
+            ("en", "CA") | ("sv" | "lt" | "ko", _) => "yyyy-mm-dd",
+            ("en" | "fr" | "es" | "it" | "pt" | "el", _) => "dd/mm/yyyy",
+            ("de" | "ru" | "pl" | "cs" | "sk" | "fi" | "nb" | "no" | "nn" | "tr" | "uk" | "ro" | "hr" | "bg", _) => "dd.mm.yyyy",
+            ("nl", _) => "d-m-yyyy",
+            ("da", _) => "dd-mm-yyyy",
+            ("hu", _) => "yyyy.mm.dd",
+            ("ja" | "zh", _) => "yyyy/m/d",
+            _ => "m/d/yyyy",
+        };
+
+        Locale {
+            decimal_separator,
+            thousands_separator,
+            short_date: short_date.to_string(),
+        }
+    }
+
+    /// Returns the locale for numbers from the environment (`LC_ALL`, `LC_NUMERIC` or `LANG`)
+    pub fn from_env() -> Locale {
+        ["LC_ALL", "LC_NUMERIC", "LANG"].iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .filter(|value| value != "C" && value != "POSIX" && !value.starts_with("C."))
+            .map(|value| Locale::from_tag(&value))
+            .unwrap_or_default()
+    }
+}
+
+/// Returns the format code of a built-in number format
+///
+/// # Arguments
+///
+/// * `id` - The built-in format ID
+/// * `locale` - Locale providing the system short date
+///
+/// # Returns
+///
+/// * `Option<String>` - The format code, or `None` for IDs without a defined format
+pub fn builtin_format_code(id: u32, locale: &Locale) -> Option<String> {
+    let code = match id {
+        0 => "General",
+        1 => "0",
+        2 => "0.00",
+        3 => "#,##0",
+        4 => "#,##0.00",
+        5 => "$#,##0_);($#,##0)",
+        6 => "$#,##0_);[Red]($#,##0)",
+        7 => "$#,##0.00_);($#,##0.00)",
+        8 => "$#,##0.00_);[Red]($#,##0.00)",
+        9 => "0%",
+        10 => "0.00%",
+        11 => "0.00E+00",
+        12 => "# ?/?",
+        13 => "# ??/??",
+        SHORT_DATE_FORMAT_ID => return Some(locale.short_date.clone()),
+        15 => "d-mmm-yy",
+        16 => "d-mmm",
+        17 => "mmm-yy",
+        18 => "h:mm AM/PM",
+        19 => "h:mm:ss AM/PM",
+        20 => "h:mm",
+        21 => "h:mm:ss",
+        SHORT_DATE_TIME_FORMAT_ID => return Some(format!("{} h:mm", locale.short_date)),
+        37 => "#,##0 ;(#,##0)",
+        38 => "#,##0 ;[Red](#,##0)",
+        39 => "#,##0.00;(#,##0.00)",
+        40 => "#,##0.00;[Red](#,##0.00)",
+        41 => r#"_(* #,##0_);_(* \(#,##0\);_(* "-"_);_(@_)"#,
+        42 => r#"_("$"* #,##0_);_("$"* \(#,##0\);_("$"* "-"_);_(@_)"#,
+        43 => r#"_(* #,##0.00_);_(* \(#,##0.00\);_(* "-"??_);_(@_)"#,
+        44 => r#"_("$"* #,##0.00_);_("$"* \(#,##0.00\);_("$"* "-"??_);_(@_)"#,
+        45 => "mm:ss",
+        46 => "[h]:mm:ss",
+        47 => "mmss.0",
+        48 => "##0.0E+0",
+        49 => "@",
+        _ => return None,
+    };
+    Some(code.to_string())
+}
+
+/// Formats a cell value the way Excel displays it
+///
+/// # Arguments
+///
+/// * `value` - The cell value
+/// * `format` - The number format of the cell, if any
+/// * `locale` - Locale providing the separators and short date format
+///
+/// # Returns
+///
+/// * `String` - The displayed text of the cell
+pub fn format_value(value: &CellValue, format: Option<&NumberFormat>, locale: &Locale) -> String {
+    let code = format.and_then(|format| match format {
+        NumberFormat::Builtin(id) => builtin_format_code(*id, locale),
+        NumberFormat::Custom(code) => Some(code.clone()),
+    });
+
+    match value {
+        CellValue::Empty => String::new(),
+        CellValue::String(text) => match code {
+            Some(code) => format_text(text, &code),
+            None => text.clone(),
+        },
+        CellValue::Bool(value) => if *value { "TRUE" } else { "FALSE" }.to_string(),
+        CellValue::Error(error) => error.clone(),
+        // Padding for column alignment is not kept, as output cells are not aligned by it
+        CellValue::Number(value) => match code {
+            Some(code) => format_number(*value, &code, locale).trim().to_string(),
+            None => format_general(*value, locale),
+        },
+        CellValue::DateTime(serial) => {
+            let code = code.unwrap_or_else(|| default_date_format(*serial, locale));
+            format_number(*serial, &code, locale).trim().to_string()
+        },
+        CellValue::Duration(days) => {
+            let code = code.unwrap_or_else(|| "[h]:mm:ss".to_string());
+            format_number(*days, &code, locale).trim().to_string()
+        },
+    }
+}
+
+/// Returns the format used for dates read without a format code
+fn default_date_format(serial: f64, locale: &Locale) -> String {
+    if serial.fract() == 0.0 {
+        locale.short_date.clone()
+    } else if serial < 1.0 {
+        "h:mm:ss".to_string()
+    } else {
+        format!("{} h:mm", locale.short_date)
+    }
+}
+
+/// A piece of a format code section
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Text shown as is
+    Literal(String),
+    /// Digit placeholder: '0' (always shown), '#' (shown if significant) or '?' (space if not)
+    Digit(char),
+    /// Decimal point
+    Decimal,
+    /// Thousands separator, or scaling by 1000 after the last integer digit
+    Comma,
+    /// Percent sign, multiplying the value by 100
+    Percent,
+    /// Scientific notation, with `true` if positive exponents show a plus sign
+    Exponent(bool),
+    /// Fraction bar between a numerator and a denominator, or a date separator
+    Slash,
+    /// Date or time part in lowercase, e.g. "yyyy", "mm", "[h]" or "am/pm"
+    Date(String),
+    /// Placeholder for the cell text
+    Text,
+    /// The General format
+    General,
+}
+
+/// Splits a format code into its sections, separated by semicolons
+fn split_sections(code: &str) -> Vec<&str> {
+    let mut sections = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut bracketed = false;
+    let mut escaped = false;
+
+    for (index, c) in code.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' | '_' | '*' if !quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '[' if !quoted => bracketed = true,
+            ']' if !quoted => bracketed = false,
+            ';' if !quoted && !bracketed => {
+                sections.push(&code[start..index]);
+                start = index + 1;
+            },
+            _ => {}
+        }
+    }
+    sections.push(&code[start..]);
+
+    sections
+}
+
+/// Splits a format code section into tokens
+fn tokenize(section: &str) -> Vec<Token> {
+    let chars: Vec<char> = section.chars().collect();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+
+    let starts_with = |index: usize, text: &str| {
+        chars[index..].iter().take(text.len()).collect::<String>().eq_ignore_ascii_case(text)
+    };
+
+    while index < chars.len() {
+        let c = chars[index];
+        index += 1;
+        match c {
+            '"' => {
+                let end = chars[index..].iter().position(|&c| c == '"').map_or(chars.len(), |offset| index + offset);
+                tokens.push(Token::Literal(chars[index..end].iter().collect()));
+                index = end + 1;
+            },
+            '\\' => {
+                if let Some(&next) = chars.get(index) {
+                    tokens.push(Token::Literal(next.to_string()));
+                }
+                index += 1;
+            },
+            // Padding as wide as the next character
+            '_' => {
+                tokens.push(Token::Literal(" ".to_string()));
+                index += 1;
+            },
+            // Repeat the next character to fill the cell; there is no cell width to fill
+            '*' => index += 1,
+            '[' => {
+                let end = chars[index..].iter().position(|&c| c == ']').map_or(chars.len(), |offset| index + offset);
+                let content: String = chars[index..end].iter().collect();
+                index = end + 1;
+
+                if let Some(currency) = content.strip_prefix('$') {
+                    // Currency and locale tag, e.g. [$€-407]
+                    let symbol = currency.split('-').next().unwrap_or_default();
+                    if !symbol.is_empty() {
+                        tokens.push(Token::Literal(symbol.to_string()));
+                    }
+                } else {
+                    let lower = content.to_lowercase();
+                    if !lower.is_empty() && lower.chars().all(|c| c == lower.chars().next().unwrap())
+                        && matches!(lower.chars().next(), Some('h' | 'm' | 's')) {
+                        tokens.push(Token::Date(format!("[{}]", lower)));
+                    }
+                    // Colors and conditions do not change the displayed text
+                }
+            },
+            '0' | '#' | '?' => tokens.push(Token::Digit(c)),
+            '.' => tokens.push(Token::Decimal),
+            ',' => tokens.push(Token::Comma),
+            '%' => tokens.push(Token::Percent),
+            '/' => tokens.push(Token::Slash),
+            '@' => tokens.push(Token::Text),
+            'E' | 'e' if matches!(chars.get(index), Some('+' | '-')) => {
+                tokens.push(Token::Exponent(chars[index] == '+'));
+                index += 1;
+            },
+            'G' | 'g' if starts_with(index - 1, "General") => {
+                tokens.push(Token::General);
+                index += "General".len() - 1;
+            },
+            'A' | 'a' if starts_with(index - 1, "AM/PM") => {
+                tokens.push(Token::Date("am/pm".to_string()));
+                index += "AM/PM".len() - 1;
+            },
+            'A' | 'a' if starts_with(index - 1, "A/P") => {
+                tokens.push(Token::Date("a/p".to_string()));
+                index += "A/P".len() - 1;
+            },
+            'y' | 'Y' | 'm' | 'M' | 'd' | 'D' | 'h' | 'H' | 's' | 'S' => {
+                let mut part = c.to_ascii_lowercase().to_string();
+                while chars.get(index).is_some_and(|next| next.eq_ignore_ascii_case(&c)) {
+                    part.push(c.to_ascii_lowercase());
+                    index += 1;
+                }
+                tokens.push(Token::Date(part));
+            },
+            _ => tokens.push(Token::Literal(c.to_string())),
+        }
+    }
+
+    tokens
+}
+
+/// Formats text with the text section of a format code
+fn format_text(text: &str, code: &str) -> String {
+    let sections = split_sections(code);
+    let section = match sections.as_slice() {
+        [.., text_section] if sections.len() == 4 => *text_section,
+        [section] if section.contains('@') => *section,
+        _ => return text.to_string(),
+    };
+
+    tokenize(section).iter()
+        .map(|token| match token {
+            Token::Literal(literal) => literal.clone(),
+            Token::Text | Token::General => text.to_string(),
+            Token::Slash => "/".to_string(),
+            _ => String::new(),
+        })
+        .collect()
+}
+
+/// Formats a number with a format code
+fn format_number(value: f64, code: &str, locale: &Locale) -> String {
+    let sections = split_sections(code);
+    let serial = value;
+
+    // Choose the section for the sign of the value; only the first section adds a minus sign
+    let (section, value, negative) = match sections.as_slice() {
+        [first] => (*first, value.abs(), value < 0.0),
+        [_, negative, ..] if value < 0.0 => (*negative, -value, false),
+        [_, _, zero, ..] if value == 0.0 => (*zero, value, false),
+        [positive, ..] => (*positive, value, false),
+        [] => ("General", value.abs(), value < 0.0),
+    };
+
+    let tokens = tokenize(section);
+    let text = if tokens.iter().any(|token| matches!(token, Token::Date(_))) {
+        // Excel fills the cell with number signs for negative dates and dates after the year 9999
+        if !(0.0..MAX_DATE_SERIAL + 1.0).contains(&serial) {
+            return INVALID_VALUE_TEXT.to_string();
+        }
+        format_date(value, &tokens, locale)
+    } else {
+        format_numeric(value, &tokens, locale)
+    };
+
+    // A value rounded to zero is shown without a minus sign
+    if negative && text.chars().any(|c| c.is_ascii_digit() && c != '0') {
+        format!("-{}", text)
+    } else {
+        text
+    }
+}
+
+/// Formats a non-negative number with the tokens of a numeric section
+fn format_numeric(mut value: f64, tokens: &[Token], locale: &Locale) -> String {
+    if tokens.iter().all(|token| matches!(token, Token::Literal(_) | Token::General | Token::Text)) {
+        return tokens.iter()
+            .map(|token| match token {
+                Token::Literal(literal) => literal.clone(),
+                _ => format_general(value, locale),
+            })
+            .collect();
+    }
+    if let Some(text) = tokens.iter().position(|token| *token == Token::Slash)
+        .and_then(|slash| format_fraction(value, tokens, slash, locale))
+    {
+        return text;
+    }
+
+    let exponent_index = tokens.iter().position(|token| matches!(token, Token::Exponent(_)));
+    let mantissa_tokens = &tokens[..exponent_index.unwrap_or(tokens.len())];
+    let decimal_index = mantissa_tokens.iter().position(|token| *token == Token::Decimal);
+    let integer_tokens = &mantissa_tokens[..decimal_index.unwrap_or(mantissa_tokens.len())];
+
+    let digits = |tokens: &[Token]| -> Vec<char> {
+        tokens.iter()
+            .filter_map(|token| match token {
+                Token::Digit(c) => Some(*c),
+                _ => None,
+            })
+            .collect()
+    };
+    let integer_digits = digits(integer_tokens);
+    let fraction_digits = decimal_index.map(|index| digits(&mantissa_tokens[index + 1..])).unwrap_or_default();
+    let exponent_digits = exponent_index.map(|index| digits(&tokens[index + 1..])).unwrap_or_default();
+
+    // Commas between integer digits group thousands; commas after the last digit scale by 1000
+    let mut grouping = false;
+    let mut scaling = 0;
+    for token in integer_tokens {
+        match token {
+            Token::Comma => scaling += 1,
+            Token::Digit(_) => {
+                grouping |= scaling > 0 && integer_digits.len() > 1;
+                scaling = 0;
+            },
+            _ => {}
+        }
+    }
+    if integer_digits.is_empty() {
+        scaling = 0;
+    }
+    if let Some(index) = decimal_index {
+        // Commas after the last fraction digit also scale, e.g. "0.0,," shows millions
+        let fraction_tokens = &mantissa_tokens[index + 1..];
+        let last_digit = fraction_tokens.iter().rposition(|token| matches!(token, Token::Digit(_)));
+        scaling += fraction_tokens[last_digit.map_or(0, |position| position + 1)..].iter()
+            .filter(|token| **token == Token::Comma)
+            .count() as i32;
+    }
+    value /= 1000f64.powi(scaling);
+    value *= 100f64.powi(tokens.iter().filter(|token| **token == Token::Percent).count() as i32);
+    // Percentages of the largest numbers overflow
+    if !value.is_finite() {
+        return INVALID_VALUE_TEXT.to_string();
+    }
+
+    // Scientific notation uses exponents that are multiples of the integer digit count
+    let mut exponent = 0;
+    if exponent_index.is_some() && value != 0.0 {
+        let step = integer_digits.len().max(1) as i32;
+        exponent = (value.log10().floor() as i32).div_euclid(step) * step;
+        // Divided in two steps, as the power of ten alone is out of range for the smallest numbers
+        let half = exponent / 2;
+        value = value / 10f64.powi(half) / 10f64.powi(exponent - half);
+        // Rounding may carry into another digit, e.g. 9.99 shown as 10.0
+        let rounded: f64 = round_decimal(value, fraction_digits.len()).parse().unwrap_or(value);
+        if rounded >= 10f64.powi(step) {
+            exponent += step;
+            value /= 10f64.powi(step);
+        }
+    }
+
+    let number = render_digits(value, &integer_digits, &fraction_digits, decimal_index.is_some(), grouping, locale);
+
+    let mut text = String::new();
+    let mut number_written = false;
+    let mut in_exponent = false;
+    for token in tokens {
+        match token {
+            Token::Literal(literal) => text.push_str(literal),
+            Token::Percent => text.push('%'),
+            Token::Slash => text.push('/'),
+            Token::Digit(_) | Token::Decimal | Token::Comma if !in_exponent && !number_written => {
+                text.push_str(&number);
+                number_written = true;
+            },
+            Token::Exponent(plus) => {
+                in_exponent = true;
+                text.push('E');
+                if exponent < 0 {
+                    text.push('-');
+                } else if *plus {
+                    text.push('+');
+                }
+                let width = exponent_digits.iter().filter(|c| **c == '0').count();
+                text.push_str(&format!("{:0width$}", exponent.unsigned_abs(), width = width));
+            },
+            Token::General | Token::Text => text.push_str(&format_general(value, locale)),
+            _ => {}
+        }
+    }
+
+    text
+}
+
+/// Formats a non-negative number with the tokens of a fraction section, e.g. "# ?/?" or "?/8"
+///
+/// # Arguments
+///
+/// * `value` - The number
+/// * `tokens` - Tokens of the section
+/// * `slash` - Index of the fraction bar in the tokens
+/// * `locale` - Locale of the digit grouping, unused as fractions are not grouped
+///
+/// # Returns
+///
+/// * `Option<String>` - The formatted number, or `None` if the bar has no numerator or denominator
+fn format_fraction(mut value: f64, tokens: &[Token], slash: usize, locale: &Locale) -> Option<String> {
+    let placeholders = |tokens: &[Token]| -> Vec<char> {
+        tokens.iter()
+            .filter_map(|token| match token {
+                Token::Digit(c) => Some(*c),
+                _ => None,
+            })
+            .collect()
+    };
+
+    // The numerator is the run of digit placeholders before the bar; digits before it show the whole part
+    let numerator_start = tokens[..slash].iter()
+        .rposition(|token| !matches!(token, Token::Digit(_)))
+        .map_or(0, |index| index + 1);
+    let numerator_digits = placeholders(&tokens[numerator_start..slash]);
+    let integer_digits = placeholders(&tokens[..numerator_start]);
+    let integer_start = tokens[..numerator_start].iter().position(|token| matches!(token, Token::Digit(_)));
+
+    // The denominator is a run of digit placeholders, or a fixed number such as 8 or 100
+    let is_denominator = |token: &Token| match token {
+        Token::Digit(_) => true,
+        Token::Literal(literal) => literal.chars().all(|c| c.is_ascii_digit()),
+        _ => false,
+    };
+    let denominator_end = slash + 1 + tokens[slash + 1..].iter().take_while(|token| is_denominator(token)).count();
+    let denominator_tokens = &tokens[slash + 1..denominator_end];
+    if numerator_digits.is_empty() || denominator_tokens.is_empty() {
+        return None;
+    }
+    let fixed_denominator = match denominator_tokens.first() {
+        Some(Token::Literal(_)) => denominator_tokens.iter()
+            .map(|token| match token {
+                Token::Literal(literal) => literal.clone(),
+                _ => "0".to_string(),
+            })
+            .collect::<String>()
+            .parse::<u64>().ok()
+            .filter(|denominator| *denominator > 0),
+        _ => None,
+    };
+    let denominator_digits = placeholders(denominator_tokens);
+
+    value *= 100f64.powi(tokens.iter().filter(|token| **token == Token::Percent).count() as i32);
+    let (mut whole, remainder) = match integer_start {
+        Some(_) => (value.trunc(), value.fract()),
+        None => (0.0, value),
+    };
+    let (mut numerator, denominator) = match fixed_denominator {
+        Some(denominator) => ((remainder * denominator as f64).round() as u64, denominator),
+        None => closest_fraction(remainder, 10u64.pow(denominator_digits.len() as u32) - 1),
+    };
+    if integer_start.is_some() && numerator == denominator {
+        whole += 1.0;
+        numerator = 0;
+    }
+
+    let whole_text = match integer_start {
+        // A whole number is shown even if its only placeholders are '#'
+        Some(_) if whole == 0.0 && numerator == 0 => "0".to_string(),
+        _ => render_digits(whole, &integer_digits, &[], false, false, locale),
+    };
+    let numerator_text = render_digits(numerator as f64, &numerator_digits, &[], false, false, locale);
+    let mut denominator_text = denominator.to_string();
+    // Denominators are aligned on the left, padded with spaces for '?'
+    let padding = denominator_digits.iter().filter(|c| **c == '?').count();
+    if fixed_denominator.is_none() && denominator_text.len() < padding {
+        denominator_text.push_str(&" ".repeat(padding - denominator_text.len()));
+    }
+    let mut fraction_text = format!("{}/{}", numerator_text, denominator_text);
+    // Whole numbers leave the space of the fraction blank
+    if integer_start.is_some() && numerator == 0 {
+        fraction_text = " ".repeat(fraction_text.chars().count());
+    }
+
+    let mut text = String::new();
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            _ if Some(index) == integer_start => text.push_str(&whole_text),
+            _ if index == numerator_start => text.push_str(&fraction_text),
+            _ if index > numerator_start && index < denominator_end => {},
+            Token::Literal(literal) => text.push_str(literal),
+            Token::Percent => text.push('%'),
+            Token::Slash => text.push('/'),
+            _ => {}
+        }
+    }
+    Some(text)
+}
+
+/// Returns the fraction closest to a number, with a denominator of at most `max_denominator`
+fn closest_fraction(value: f64, max_denominator: u64) -> (u64, u64) {
+    let mut closest = (value.round() as u64, 1);
+    let mut closest_error = (value - value.round()).abs();
+    for denominator in 2..=max_denominator.max(1) {
+        let numerator = (value * denominator as f64).round();
+        let error = (value - numerator / denominator as f64).abs();
+        if error < closest_error {
+            closest = (numerator as u64, denominator);
+            closest_error = error;
+        }
+    }
+    closest
+}
+
+/// Renders the digits of a number for the given integer and fraction placeholders
+fn render_digits(
+    value: f64,
+    integer_digits: &[char],
+    fraction_digits: &[char],
+    has_decimal: bool,
+    grouping: bool,
+    locale: &Locale,
+) -> String {
+    let rounded = round_decimal(value, fraction_digits.len());
+    let (integer, fraction) = rounded.split_once('.').unwrap_or((&rounded, ""));
+
+    // Leading zeros for '0' placeholders, spaces for '?'; no digit for a zero integer part with only '#'
+    let mut integer = if integer == "0" { String::new() } else { integer.to_string() };
+    let required = integer_digits.iter().rev()
+        .position(|c| *c == '#')
+        .unwrap_or(integer_digits.len());
+    while integer.len() < required {
+        let placeholder = integer_digits[integer_digits.len() - integer.len() - 1];
+        integer.insert(0, if placeholder == '?' { ' ' } else { '0' });
+    }
+    if grouping {
+        integer = group_thousands(&integer, locale.thousands_separator);
+    }
+
+    // Trailing zeros are dropped for '#' placeholders and replaced by spaces for '?'
+    let mut fraction: Vec<char> = fraction.chars().collect();
+    for (index, placeholder) in fraction_digits.iter().enumerate().rev() {
+        if fraction[index] != '0' || *placeholder == '0' {
+            break;
+        }
+        if *placeholder == '?' {
+            fraction[index] = ' ';
+        } else {
+            fraction.pop();
+        }
+    }
+
+    let mut text = integer;
+    if has_decimal {
+        text.push(locale.decimal_separator);
+        text.extend(fraction);
+    }
+    text
+}
+
+/// Formats a number with a fixed number of decimals, rounding halves away from zero as Excel does
+///
+/// The value is first reduced to the 15 significant digits Excel stores, so that
+/// binary representation errors (e.g. 1.005 stored as 1.00499...) do not change the result.
+fn round_decimal(value: f64, decimals: usize) -> String {
+    if value == 0.0 || !value.is_finite() {
+        return format!("{:.*}", decimals, value);
+    }
+
+    let magnitude = value.abs().log10().floor() as i32;
+    let precise_decimals = (EXCEL_PRECISION - 1 - magnitude).max(0) as usize;
+    if precise_decimals <= decimals {
+        return format!("{:.*}", decimals, value);
+    }
+
+    let precise = format!("{:.*}", precise_decimals, value.abs());
+    let point = precise.find('.').unwrap_or(precise.len());
+    let mut digits: Vec<u8> = precise.bytes().filter(|byte| *byte != b'.').collect();
+    let integer_len = point;
+    let round_up = digits[integer_len + decimals] >= b'5';
+    digits.truncate(integer_len + decimals);
+
+    if round_up {
+        // Propagate the carry from the last kept digit
+        let mut index = digits.len();
+        loop {
+            if index == 0 {
+                digits.insert(0, b'1');
+                break;
+            }
+            index -= 1;
+            if digits[index] == b'9' {
+                digits[index] = b'0';
+            } else {
+                digits[index] += 1;
+                break;
+            }
+        }
+    }
+
+    let integer_len = digits.len() - decimals;
+    let mut text = String::from_utf8_lossy(&digits[..integer_len]).to_string();
+    if text.is_empty() {
+        text.push('0');
+    }
+    if decimals > 0 {
+        text.push('.');
+        text.push_str(&String::from_utf8_lossy(&digits[integer_len..]));
+    }
+    if value < 0.0 {
+        text.insert(0, '-');
+    }
+    text
+}
+
+/// Inserts a separator between groups of three digits
+fn group_thousands(integer: &str, separator: char) -> String {
+    let digits: Vec<char> = integer.chars().collect();
+    let first_digit = digits.iter().position(|c| c.is_ascii_digit()).unwrap_or(digits.len());
+    let mut grouped: String = digits[..first_digit].iter().collect();
+
+    let significant = &digits[first_digit..];
+    for (index, digit) in significant.iter().enumerate() {
+        if index > 0 && (significant.len() - index).is_multiple_of(3) {
+            grouped.push(separator);
+        }
+        grouped.push(*digit);
+    }
+
+    grouped
+}
+
+/// Formats a number with the General format
+fn format_general(value: f64, locale: &Locale) -> String {
+    if value == 0.0 {
+        return "0".to_string();
+    }
+
+    let magnitude = value.abs().log10().floor() as i32;
+    let text = if !(-9..GENERAL_DIGITS + 1).contains(&magnitude) {
+        // Very large and very small numbers use scientific notation, e.g. 1.23457E+15
+        let scientific = format!("{:.5e}", value);
+        let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+        let mantissa = mantissa.trim_end_matches('0').trim_end_matches('.');
+        let exponent: i32 = exponent.parse().unwrap_or(0);
+        format!("{}E{}{:02}", mantissa, if exponent < 0 { '-' } else { '+' }, exponent.abs())
+    } else {
+        let decimals = (GENERAL_DIGITS - 1 - magnitude).max(0) as usize;
+        let fixed = round_decimal(value, decimals);
+        if fixed.contains('.') {
+            fixed.trim_end_matches('0').trim_end_matches('.').to_string()
+        } else {
+            fixed
+        }
+    };
+
+    text.replace('.', &locale.decimal_separator.to_string())
+}
+
+/// Date and time fields of a serial date
+#[derive(Debug, Clone, Copy)]
+struct DateTime {
+    year: i64,
+    month: usize,
+    day: u32,
+    weekday: usize,
+    hour: u32,
+    minute: u32,
+    second: u32,
+    /// Fraction of a second, already rounded to the displayed precision
+    subsecond: f64,
+    /// Whole days, for elapsed time formats
+    days: i64,
+}
+
+/// Converts an Excel serial date (1900 date system) to calendar fields
+fn date_time(serial: f64, subsecond_digits: usize) -> DateTime {
+    // Round to the displayed precision first, so 23:59:59.9 can roll over to the next day
+    let scale = 10f64.powi(subsecond_digits as i32);
+    let total = (serial * SECONDS_PER_DAY * scale).round() / scale;
+    let days = (total / SECONDS_PER_DAY).floor() as i64;
+    let seconds = total - days as f64 * SECONDS_PER_DAY;
+    let whole_seconds = seconds.floor() as u32;
+
+    let (year, month, day) = if days == LEAP_BUG_SERIAL {
+        (1900, 2, 29)
+    } else {
+        // Serials after the fictitious leap day are one day ahead of the real calendar
+        let real_days = if days > LEAP_BUG_SERIAL { days - 1 } else { days };
+        civil_from_days(real_days - UNIX_EPOCH_SERIAL)
+    };
+    let weekday = (days - 1).rem_euclid(7) as usize;
+
+    DateTime {
+        year,
+        month: month as usize,
+        day,
+        weekday,
+        hour: whole_seconds / 3600,
+        minute: whole_seconds / 60 % 60,
+        second: whole_seconds % 60,
+        subsecond: seconds - f64::from(whole_seconds),
+        days,
+    }
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) date in the proleptic Gregorian calendar
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Converts a (year, month, day) date in the proleptic Gregorian calendar to days since 1970-01-01
+pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month_index = i64::from(if month > 2 { month - 3 } else { month + 9 });
+    let day_of_year = (153 * month_index + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Formats a serial date with the tokens of a date or time section
+fn format_date(serial: f64, tokens: &[Token], locale: &Locale) -> String {
+    let is_date = |token: &Token, prefix: char| matches!(token, Token::Date(part) if part.starts_with(prefix));
+    let twelve_hour = tokens.iter().any(|token| matches!(token, Token::Date(part) if part == "am/pm" || part == "a/p"));
+
+    // Fractional seconds are written as a decimal point and zeros after the seconds
+    let subsecond_index = tokens.windows(3)
+        .position(|window| is_date(&window[0], 's') && window[1] == Token::Decimal && window[2] == Token::Digit('0'))
+        .map(|index| index + 1);
+    let subsecond_digits = subsecond_index
+        .map_or(0, |index| tokens[index + 1..].iter().take_while(|token| **token == Token::Digit('0')).count());
+    let time = date_time(serial, subsecond_digits);
+
+    let mut text = String::new();
+    let mut index = 0;
+    while index < tokens.len() {
+        let token = &tokens[index];
+        index += 1;
+        let Token::Date(part) = token else {
+            match token {
+                Token::Literal(literal) => text.push_str(literal),
+                Token::Decimal if subsecond_index == Some(index - 1) => {
+                    let fraction = format!("{:.*}", subsecond_digits, time.subsecond);
+                    text.push(locale.decimal_separator);
+                    text.push_str(fraction.split_once('.').map_or("", |(_, digits)| digits));
+                    index += subsecond_digits;
+                },
+                // Outside fractional seconds these are plain separators, e.g. in "dd.mm.yyyy"
+                Token::Decimal => text.push('.'),
+                Token::Comma => text.push(','),
+                Token::Percent => text.push('%'),
+                Token::Slash => text.push('/'),
+                _ => {}
+            }
+            continue;
+        };
+
+        let width = part.len();
+        let padded = |value: u32| if width >= 2 { format!("{:02}", value) } else { value.to_string() };
+        let rendered = match part.as_str() {
+            "am/pm" => if time.hour < 12 { "AM" } else { "PM" }.to_string(),
+            "a/p" => if time.hour < 12 { "A" } else { "P" }.to_string(),
+            elapsed if elapsed.starts_with('[') => {
+                let digits = elapsed.len() - 2;
+                let total_seconds = time.days * 86_400 + i64::from(time.hour * 3600 + time.minute * 60 + time.second);
+                let value = match elapsed.as_bytes()[1] {
+                    b'h' => total_seconds / 3600,
+                    b'm' => total_seconds / 60,
+                    _ => total_seconds,
+                };
+                format!("{:0digits$}", value, digits = digits)
+            },
+            _ if part.starts_with('y') => {
+                if width <= 2 {
+                    format!("{:02}", time.year.rem_euclid(100))
+                } else {
+                    format!("{:04}", time.year)
+                }
+            },
+            _ if part.starts_with('m') => {
+                // "m" and "mm" are minutes after hours or before seconds, months otherwise
+                let previous = tokens[..index - 1].iter().rev().find(|token| matches!(token, Token::Date(_)));
+                let next = tokens[index..].iter().find(|token| matches!(token, Token::Date(_)));
+                let is_minute = width <= 2
+                    && (previous.is_some_and(|token| is_date(token, 'h') || matches!(token, Token::Date(part) if part.starts_with("[h")))
+                        || next.is_some_and(|token| is_date(token, 's')));
+                match width {
+                    _ if is_minute => padded(time.minute),
+                    1 | 2 => padded(time.month as u32),
+                    3 => MONTH_ABBREVIATIONS[time.month - 1].to_string(),
+                    4 => MONTH_NAMES[time.month - 1].to_string(),
+                    _ => MONTH_NAMES[time.month - 1][..1].to_string(),
+                }
+            },
+            _ if part.starts_with('d') => match width {
+                1 | 2 => padded(time.day),
+                3 => DAY_ABBREVIATIONS[time.weekday].to_string(),
+                _ => DAY_NAMES[time.weekday].to_string(),
+            },
+            _ if part.starts_with('h') => {
+                let hour = match (twelve_hour, time.hour % 12) {
+                    (true, 0) => 12,
+                    (true, hour) => hour,
+                    (false, _) => time.hour,
+                };
+                padded(hour)
+            },
+            _ => padded(time.second),
+        };
+        text.push_str(&rendered);
+    }
+
+    text
+}
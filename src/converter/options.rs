@@ -0,0 +1,436 @@
+//! Options controlling how documents are converted.
+//! The defaults reproduce the plain conversion; each option opts into extra output.
+
+use std::fmt;
+use std::str::FromStr;
+use anyhow::{Result, Context};
+
+use crate::converter::font_loader::FontFiles;
+use crate::converter::number_format::Locale;
+use crate::converter::table::CellRange;
+use crate::utils;
+
+/// Smallest font size of body text in points
+pub const MIN_FONT_SIZE: u8 = 4;
+
+/// How spreadsheet formulas are shown in the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FormulaDisplay {
+    /// Only the computed values are shown
+    #[default]
+    Hidden,
+    /// Each computed value is followed by its formula, e.g. `12 (=SUM(A1:A9))`
+    Inline,
+    /// Formulas are listed with their cell references after each sheet
+    Appendix,
+}
+
+impl FromStr for FormulaDisplay {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<FormulaDisplay> {
+        match value.to_lowercase().as_str() {
+            "hidden" | "none" => Ok(FormulaDisplay::Hidden),
+            "inline" => Ok(FormulaDisplay::Inline),
+            "appendix" => Ok(FormulaDisplay::Appendix),
+            _ => anyhow::bail!("Unknown formula display: {} (expected hidden, inline or appendix)", value),
+        }
+    }
+}
+
+impl fmt::Display for FormulaDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FormulaDisplay::Hidden => "hidden",
+            FormulaDisplay::Inline => "inline",
+            FormulaDisplay::Appendix => "appendix",
+        })
+    }
+}
+
+/// A cell range restricting the data read from a sheet, e.g. `Sheet1!A1:H200`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SheetRange {
+    /// Name of the sheet
+    pub sheet: String,
+    /// The cells to read
+    pub range: CellRange,
+}
+
+impl FromStr for SheetRange {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<SheetRange> {
+        let (sheet, range) = value.rsplit_once('!')
+            .context(format!("Missing sheet name in range (expected e.g. Sheet1!A1:H200): {}", value))?;
+        // Sheet names with spaces or punctuation are quoted as in Excel formulas
+        let sheet = match sheet.strip_prefix('\'').and_then(|name| name.strip_suffix('\'')) {
+            Some(quoted) => quoted.replace("''", "'"),
+            None => sheet.to_string(),
+        };
+        let range = CellRange::parse(&range.replace('$', ""))
+            .context(format!("Invalid cell range: {}", range))?;
+        Ok(SheetRange { sheet, range })
+    }
+}
+
+/// Paper size of PDF pages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaperSize {
+    /// ISO A4, 210 × 297 mm
+    A4,
+    /// ISO A3, 297 × 420 mm
+    A3,
+    /// US Letter, 8.5 × 11 in
+    Letter,
+    /// US Legal, 8.5 × 14 in
+    Legal,
+    /// A custom size in millimetres, e.g. `200x250`
+    Custom {
+        /// Width in millimetres
+        width: f64,
+        /// Height in millimetres
+        height: f64,
+    },
+}
+
+impl PaperSize {
+    /// Returns the width and height in millimetres, in portrait orientation
+    pub fn size_mm(&self) -> (f64, f64) {
+        match *self {
+            PaperSize::A4 => (210.0, 297.0),
+            PaperSize::A3 => (297.0, 420.0),
+            PaperSize::Letter => (215.9, 279.4),
+            PaperSize::Legal => (215.9, 355.6),
+            PaperSize::Custom { width, height } => (width.min(height), width.max(height)),
+        }
+    }
+}
+
+impl FromStr for PaperSize {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<PaperSize> {
+        match value.to_lowercase().as_str() {
+            "a4" => Ok(PaperSize::A4),
+            "a3" => Ok(PaperSize::A3),
+            "letter" => Ok(PaperSize::Letter),
+            "legal" => Ok(PaperSize::Legal),
+            custom => {
+                let (width, height) = custom.trim_end_matches("mm")
+                    .split_once(['x', '×'])
+                    .context(format!("Unknown paper size: {} (expected A4, A3, Letter, Legal or WIDTHxHEIGHT in mm)", value))?;
+                let width = parse_length(width)?;
+                let height = parse_length(height)?;
+                if width <= 0.0 || height <= 0.0 {
+                    anyhow::bail!("Paper size must be positive: {}", value);
+                }
+                Ok(PaperSize::Custom { width, height })
+            },
+        }
+    }
+}
+
+impl fmt::Display for PaperSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaperSize::A4 => f.write_str("A4"),
+            PaperSize::A3 => f.write_str("A3"),
+            PaperSize::Letter => f.write_str("Letter"),
+            PaperSize::Legal => f.write_str("Legal"),
+            PaperSize::Custom { width, height } => write!(f, "{}x{} mm", width, height),
+        }
+    }
+}
+
+/// Orientation of PDF pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    /// Pages are taller than wide
+    Portrait,
+    /// Pages are wider than tall
+    Landscape,
+}
+
+impl FromStr for Orientation {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Orientation> {
+        match value.to_lowercase().as_str() {
+            "portrait" => Ok(Orientation::Portrait),
+            "landscape" => Ok(Orientation::Landscape),
+            _ => anyhow::bail!("Unknown orientation: {} (expected portrait or landscape)", value),
+        }
+    }
+}
+
+impl fmt::Display for Orientation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Orientation::Portrait => "portrait",
+            Orientation::Landscape => "landscape",
+        })
+    }
+}
+
+/// Page margins in millimetres
+///
+/// Margins are written like in CSS: one value for all edges, two for vertical and
+/// horizontal edges, three for top, horizontal and bottom edges, or four for the
+/// top, right, bottom and left edges, e.g. `25,20,25,20`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageMargins {
+    /// Top margin
+    pub top: f64,
+    /// Right margin
+    pub right: f64,
+    /// Bottom margin
+    pub bottom: f64,
+    /// Left margin
+    pub left: f64,
+}
+
+impl PageMargins {
+    /// Creates margins of the same width on all edges
+    pub fn uniform(margin: f64) -> PageMargins {
+        PageMargins { top: margin, right: margin, bottom: margin, left: margin }
+    }
+}
+
+impl FromStr for PageMargins {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<PageMargins> {
+        let values = value.split(',')
+            .map(parse_length)
+            .collect::<Result<Vec<f64>>>()
+            .context(format!("Invalid margins: {}", value))?;
+        if values.iter().any(|margin| *margin < 0.0) {
+            anyhow::bail!("Margins must not be negative: {}", value);
+        }
+
+        match values[..] {
+            [all] => Ok(PageMargins::uniform(all)),
+            [vertical, horizontal] => Ok(PageMargins { top: vertical, right: horizontal, bottom: vertical, left: horizontal }),
+            [top, horizontal, bottom] => Ok(PageMargins { top, right: horizontal, bottom, left: horizontal }),
+            [top, right, bottom, left] => Ok(PageMargins { top, right, bottom, left }),
+            _ => anyhow::bail!("Expected one to four margins (top, right, bottom, left): {}", value),
+        }
+    }
+}
+
+impl fmt::Display for PageMargins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{},{} mm", self.top, self.right, self.bottom, self.left)
+    }
+}
+
+/// Size and margins of PDF pages in millimetres
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageSetup {
+    /// Page width
+    pub width: f64,
+    /// Page height
+    pub height: f64,
+    /// Page margins
+    pub margins: PageMargins,
+}
+
+impl PageSetup {
+    /// Checks that the margins leave room for content on the page
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - An error if the margins are as wide or as tall as the page
+    pub fn validate(&self) -> Result<()> {
+        if self.content_width() <= 0.0 || self.content_height() <= 0.0 {
+            anyhow::bail!("Margins of {} do not fit {} x {} mm pages", self.margins, self.width, self.height);
+        }
+        Ok(())
+    }
+
+    /// Returns the width of the page between the left and right margins
+    pub fn content_width(&self) -> f64 {
+        self.width - self.margins.left - self.margins.right
+    }
+
+    /// Returns the height of the page between the top and bottom margins
+    pub fn content_height(&self) -> f64 {
+        self.height - self.margins.top - self.margins.bottom
+    }
+}
+
+/// Page layout and text settings of PDF output
+///
+/// Unset page options follow the page setup of the source document if it has one,
+/// and the defaults of the PDF writer otherwise.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PdfOptions {
+    /// Paper size of the pages
+    pub paper_size: Option<PaperSize>,
+    /// Orientation of the pages; spreadsheets whose sheets are all wide switch to landscape if unset
+    pub orientation: Option<Orientation>,
+    /// Margins of the pages
+    pub margins: Option<PageMargins>,
+    /// Font size of body text in points
+    pub font_size: Option<u8>,
+    /// Line spacing as a multiple of the line height
+    pub line_spacing: Option<f64>,
+    /// Template of the header printed on every page, replacing the header of the source document
+    pub header: Option<String>,
+    /// Template of the footer printed on every page, replacing the footer of the source document
+    pub footer: Option<String>,
+    /// Whether a table of contents listing the headings or sheets with their pages is added at the front
+    pub table_of_contents: bool,
+    /// Whether PDF files are written as PDF/A-2b for archiving, failing if they cannot conform
+    pub pdf_a: bool,
+}
+
+impl PdfOptions {
+    /// Checks that the options describe a page with room for content
+    ///
+    /// Margins are checked against the paper size, or A4 if it is not set, in the
+    /// orientation set. Without an orientation they must fit both orientations, as
+    /// wide sheets may switch to landscape pages.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - An error describing the first invalid option
+    pub fn validate(&self) -> Result<()> {
+        if let Some(font_size) = self.font_size {
+            check_font_size(font_size)?;
+        }
+        if let Some(line_spacing) = self.line_spacing {
+            check_line_spacing(line_spacing)?;
+        }
+        if let Some(margins) = self.margins {
+            let (width, height) = self.paper_size.unwrap_or(PaperSize::A4).size_mm();
+            let orientations = match self.orientation {
+                Some(Orientation::Portrait) => vec![(width, height)],
+                Some(Orientation::Landscape) => vec![(height, width)],
+                None => vec![(width, height), (height, width)],
+            };
+            for (width, height) in orientations {
+                PageSetup { width, height, margins }.validate()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Options for converting a document
+#[derive(Debug, Clone, Default)]
+pub struct ConversionOptions {
+    /// How spreadsheet formulas are shown
+    pub formulas: FormulaDisplay,
+    /// Number of consecutive empty spreadsheet rows kept to preserve spacing; further empty rows are dropped
+    pub max_blank_rows: usize,
+    /// Whether spreadsheets are labelled with row numbers and column letters like Excel's grid
+    pub grid_labels: bool,
+    /// Names or glob patterns of the sheets to convert; all sheets are converted if empty
+    pub include_sheets: Vec<String>,
+    /// Names or glob patterns of sheets left out, even if included
+    pub exclude_sheets: Vec<String>,
+    /// Whether hidden and very hidden sheets are converted
+    pub include_hidden_sheets: bool,
+    /// Cell ranges restricting the data read from individual sheets
+    pub sheet_ranges: Vec<SheetRange>,
+    /// Whether only the Excel tables and named ranges of spreadsheets are converted
+    pub tables_only: bool,
+    /// Number of header rows at the top of each sheet; detected from the data if `None`
+    pub header_rows: Option<usize>,
+    /// Font family name or directory of TTF/OTF files used for PDF output; the default font if `None`
+    pub font: Option<String>,
+    /// Files of the font, if `font` has already been resolved; otherwise it is resolved for each file converted
+    pub font_files: Option<FontFiles>,
+    /// Language tag such as "de-DE" whose separators and short date format cells are shown with;
+    /// taken from the environment if `None`
+    pub locale: Option<String>,
+    /// Page layout and text settings of PDF output
+    pub pdf: PdfOptions,
+}
+
+impl ConversionOptions {
+    /// Checks if a sheet is selected by the include and exclude patterns
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the sheet
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - True if the sheet should be converted
+    pub fn selects_sheet(&self, name: &str) -> bool {
+        let included = self.include_sheets.is_empty()
+            || self.include_sheets.iter().any(|pattern| utils::matches_glob(pattern, name));
+        included && !self.exclude_sheets.iter().any(|pattern| utils::matches_glob(pattern, name))
+    }
+
+    /// Returns the locale used to display numbers and dates
+    ///
+    /// # Returns
+    ///
+    /// * `Locale` - The locale of the language tag set, or of the environment if none is set
+    pub fn locale(&self) -> Locale {
+        match &self.locale {
+            Some(tag) => Locale::from_tag(tag),
+            None => Locale::from_env(),
+        }
+    }
+
+    /// Returns the cell range to read from a sheet, if it is restricted
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the sheet
+    ///
+    /// # Returns
+    ///
+    /// * `Option<&CellRange>` - The range to read, or `None` to read the whole used range
+    pub fn sheet_range(&self, name: &str) -> Option<&CellRange> {
+        self.sheet_ranges.iter()
+            .find(|selection| selection.sheet.eq_ignore_ascii_case(name))
+            .map(|selection| &selection.range)
+    }
+}
+
+/// Parses a line spacing, which must be a positive multiple of the line height
+///
+/// # Arguments
+///
+/// * `value` - The line spacing as written, e.g. `1.5`
+///
+/// # Returns
+///
+/// * `Result<f64>` - The line spacing or an error
+pub fn parse_line_spacing(value: &str) -> Result<f64> {
+    let line_spacing = value.trim().parse::<f64>()
+        .context(format!("Invalid line spacing: {}", value.trim()))?;
+    check_line_spacing(line_spacing)?;
+    Ok(line_spacing)
+}
+
+/// Checks that a line spacing is positive and finite
+fn check_line_spacing(line_spacing: f64) -> Result<()> {
+    if !line_spacing.is_finite() || line_spacing <= 0.0 {
+        anyhow::bail!("Line spacing must be a positive number: {}", line_spacing);
+    }
+    Ok(())
+}
+
+/// Checks that a font size is at least the smallest font size
+fn check_font_size(font_size: u8) -> Result<()> {
+    if font_size < MIN_FONT_SIZE {
+        anyhow::bail!("Font size must be at least {} pt: {}", MIN_FONT_SIZE, font_size);
+    }
+    Ok(())
+}
+
+/// Parses a length in millimetres, with an optional `mm` unit
+fn parse_length(value: &str) -> Result<f64> {
+    let number = value.trim().trim_end_matches("mm").trim();
+    number.parse::<f64>()
+        .ok()
+        .filter(|length| length.is_finite())
+        .context(format!("Invalid length in millimetres: {}", value.trim()))
+}
@@ -0,0 +1,59 @@
+//! Access to the parts of zip packages such as Office Open XML (DOCX, XLSX) and
+//! OpenDocument files.
+
+use std::io::{Cursor, Read};
+use anyhow::{Result, Context};
+
+/// Reads a part of a zip package as text
+///
+/// # Arguments
+///
+/// * `buf` - The package file contents
+/// * `part` - Path of the part inside the package (e.g. "word/document.xml")
+///
+/// # Returns
+///
+/// * `Result<Option<String>>` - The part contents, `None` if the package has no such part, or an error
+pub fn read_package_part(buf: &[u8], part: &str) -> Result<Option<String>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(buf))
+        .context("Failed to open package")?;
+
+    let mut file = match archive.by_name(part) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(err).context(format!("Failed to open package part: {}", part)),
+    };
+
+    let mut xml = String::new();
+    file.read_to_string(&mut xml)
+        .context(format!("Failed to read package part: {}", part))?;
+    Ok(Some(xml))
+}
+
+/// Resolves a relationship target to a part name
+///
+/// # Arguments
+///
+/// * `folder` - Folder of the part owning the relationship
+/// * `target` - The relationship target, relative to the folder or absolute
+///
+/// # Returns
+///
+/// * `String` - The part name, relative to the package root
+pub fn resolve_part_target(folder: &str, target: &str) -> String {
+    if let Some(absolute) = target.strip_prefix('/') {
+        return absolute.to_string();
+    }
+
+    let mut segments: Vec<&str> = folder.split('/').filter(|segment| !segment.is_empty()).collect();
+    for segment in target.split('/') {
+        match segment {
+            ".." => {
+                segments.pop();
+            },
+            "." | "" => {},
+            _ => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
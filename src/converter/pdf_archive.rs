@@ -0,0 +1,931 @@
+//! PDF/A-2b archival output.
+//! genpdf writes PDF/X-3 files with complete fonts and no conformance guarantees. Every
+//! file gets subset fonts and compressed streams; archival files also get an sRGB output
+//! intent and the changes PDF/A requires, and are checked for forbidden features once
+//! written, failing the conversion if any remain.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::Path;
+use anyhow::{Result, Context};
+use log::{debug, info};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+use quick_xml::events::Event;
+
+use crate::converter::font_subset;
+use crate::converter::metadata::Timestamp;
+use crate::converter::pdf_postprocess;
+
+/// Part of the PDF/A standard archival files conform to
+pub const PDF_A_PART: u8 = 2;
+
+/// Conformance level of archival files, B for basic (visual appearance)
+pub const PDF_A_CONFORMANCE: &str = "B";
+
+/// PDF version of archival files; PDF/A-2 is based on PDF 1.7
+const PDF_VERSION: &str = "1.7";
+
+/// Comment following the header, whose characters are written as bytes above 127 to mark the file as binary
+const BINARY_COMMENT: &str = "âãÏÓ";
+
+/// Output condition of the sRGB output intent
+const SRGB_CONDITION: &str = "sRGB IEC61966-2.1";
+
+/// Annotation flag: the annotation is hidden
+const ANNOTATION_HIDDEN: i64 = 1 << 1;
+
+/// Annotation flag: the annotation is printed
+const ANNOTATION_PRINT: i64 = 1 << 2;
+
+/// Annotation flags that keep an annotation from being shown: invisible, hidden, no view and toggle no view
+const ANNOTATION_NOT_SHOWN: i64 = 1 | ANNOTATION_HIDDEN | (1 << 5) | (1 << 8);
+
+/// Entries of the document information dictionary and the XMP properties that must hold the same value
+const INFO_XMP_PROPERTIES: &[(&[u8], &str)] = &[
+    (b"Title", "dc:title"), (b"Author", "dc:creator"), (b"Subject", "dc:description"), (b"Keywords", "pdf:Keywords"),
+    (b"Producer", "pdf:Producer"), (b"CreationDate", "xmp:CreateDate"), (b"ModDate", "xmp:ModifyDate"),
+];
+
+/// Annotation types PDF/A does not allow
+const FORBIDDEN_ANNOTATIONS: &[&[u8]] = &[b"Sound", b"Movie", b"Screen", b"3D", b"RichMedia", b"FileAttachment"];
+
+/// Action types PDF/A does not allow
+const FORBIDDEN_ACTIONS: &[&[u8]] = &[
+    b"Launch", b"Sound", b"Movie", b"ResetForm", b"ImportData", b"JavaScript", b"Hide", b"SetOCGState", b"Rendition",
+    b"Trans", b"GoTo3DView",
+];
+
+/// Number of entries of the tone curves in the sRGB profile
+const SRGB_CURVE_ENTRIES: usize = 1024;
+
+/// Subsets the fonts of a rendered document and compresses its streams
+///
+/// genpdf embeds the complete font programs, which would otherwise take most of the
+/// file. The metadata stream is left uncompressed so it can be read without a PDF parser.
+///
+/// # Arguments
+///
+/// * `document` - The PDF document
+///
+/// # Returns
+///
+/// * `Result<()>` - Success or an error
+pub fn compact(document: &mut Document) -> Result<()> {
+    subset_fonts(document)?;
+    for object in document.objects.values_mut() {
+        if let Object::Stream(stream) = object
+            && !stream.dict.type_is(b"Metadata")
+        {
+            stream.compress()?;
+        }
+    }
+    Ok(())
+}
+
+/// Prepares a rendered document for archiving as PDF/A-2b
+///
+/// The PDF/X output intent is replaced by an sRGB one, images are not interpolated,
+/// annotations are printed and the optional content configuration is named. The XMP
+/// metadata is written by `pdf_postprocess`, and the fonts are subset by `compact`.
+///
+/// # Arguments
+///
+/// * `document` - The PDF document
+///
+/// # Returns
+///
+/// * `Result<()>` - Success or an error
+pub fn convert(document: &mut Document) -> Result<()> {
+    set_output_intent(document)?;
+
+    for object in document.objects.values_mut() {
+        let dictionary = match object {
+            Object::Dictionary(dictionary) => dictionary,
+            Object::Stream(stream) => &mut stream.dict,
+            _ => continue,
+        };
+        if is_name(dictionary, b"Subtype", b"Image") {
+            dictionary.remove(b"Interpolate");
+        }
+        if dictionary.type_is(b"Annot") {
+            let flags = dictionary.get(b"F").and_then(Object::as_i64).unwrap_or(0);
+            dictionary.set("F", (flags | ANNOTATION_PRINT) & !ANNOTATION_NOT_SHOWN);
+        }
+    }
+
+    // Each optional content configuration needs a name
+    let catalog = pdf_postprocess::catalog_mut(document)?;
+    if let Ok(Object::Dictionary(properties)) = catalog.get_mut(b"OCProperties")
+        && let Ok(Object::Dictionary(configuration)) = properties.get_mut(b"D")
+    {
+        if !configuration.has(b"Name") {
+            configuration.set("Name", Object::string_literal("Default"));
+        }
+        configuration.remove(b"AS");
+    }
+
+    // The trapping state has no equivalent in the XMP schemas PDF/A predefines
+    if let Ok(info) = document.trailer.get(b"Info").and_then(Object::as_reference)
+        && let Ok(info) = document.get_object_mut(info).and_then(Object::as_dict_mut)
+    {
+        info.remove(b"Trapped");
+    }
+
+    Ok(())
+}
+
+/// Writes an archival PDF file
+///
+/// lopdf writes no binary comment after the header and writes `endstream` right after
+/// the stream data, while PDF/A requires both to be on lines of their own. The comment
+/// is written as part of the version, and each stream gets a line feed after its data
+/// that is not counted in its length.
+///
+/// # Arguments
+///
+/// * `document` - The PDF document, whose streams are changed for writing
+/// * `path` - Path to the PDF file
+///
+/// # Returns
+///
+/// * `Result<()>` - Success or an error
+pub fn save(document: &mut Document, path: &Path) -> Result<()> {
+    document.version = format!("{}\n%{}", PDF_VERSION, BINARY_COMMENT);
+    for object in document.objects.values_mut() {
+        if let Object::Stream(stream) = object {
+            let length = stream.content.len() as i64;
+            stream.content.push(b'\n');
+            stream.dict.set("Length", length);
+        }
+    }
+
+    document.save(path)
+        .context(format!("Failed to write PDF/A file: {}", path.display()))?;
+    Ok(())
+}
+
+/// Checks a written PDF file for conformance with PDF/A-2b
+///
+/// A file that does not conform is removed, so it cannot be mistaken for an archival copy.
+///
+/// # Arguments
+///
+/// * `path` - Path to the PDF file
+///
+/// # Returns
+///
+/// * `Result<()>` - Success, or an error listing the problems found
+pub fn verify(path: &Path) -> Result<()> {
+    let data = std::fs::read(path)
+        .context(format!("Failed to read PDF/A file: {}", path.display()))?;
+    let document = Document::load_mem(&data)
+        .context(format!("Failed to parse PDF/A file: {}", path.display()))?;
+
+    let mut problems = check_file_structure(&data);
+    problems.extend(check(&document));
+    if problems.is_empty() {
+        info!("PDF/A-{}{} check passed: {}", PDF_A_PART, PDF_A_CONFORMANCE.to_lowercase(), path.display());
+        return Ok(());
+    }
+
+    std::fs::remove_file(path)
+        .context(format!("Failed to remove non-conforming PDF file: {}", path.display()))?;
+    anyhow::bail!("PDF/A-{}{} conformance cannot be met:\n  - {}",
+                  PDF_A_PART, PDF_A_CONFORMANCE.to_lowercase(), problems.join("\n  - "))
+}
+
+/// Checks a document for features PDF/A-2b forbids or requires
+///
+/// This is a structural check of the features PDF files may contain, not a full
+/// validation: fonts, colour spaces, metadata, output intents, annotations and actions.
+///
+/// # Arguments
+///
+/// * `document` - The PDF document
+///
+/// # Returns
+///
+/// * `Vec<String>` - A description of each problem found, empty if the document conforms
+pub fn check(document: &Document) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if document.trailer.has(b"Encrypt") {
+        problems.push("The file is encrypted".to_string());
+    }
+    if document.trailer.get(b"ID").and_then(Object::as_array).map_or(true, |id| id.len() != 2) {
+        problems.push("The trailer has no file identifier".to_string());
+    }
+
+    match document.catalog() {
+        Ok(catalog) => check_catalog(document, catalog, &mut problems),
+        Err(_) => problems.push("The document has no catalog".to_string()),
+    }
+
+    for (number, page) in document.get_pages() {
+        check_page(document, number, page, &mut problems);
+    }
+
+    for (&id, object) in &document.objects {
+        let dictionary = match object {
+            Object::Stream(stream) => {
+                check_stream(id, stream, &mut problems);
+                &stream.dict
+            },
+            Object::Dictionary(dictionary) => dictionary,
+            _ => continue,
+        };
+        check_dictionary(document, id, dictionary, &mut problems);
+    }
+
+    problems
+}
+
+/// Subsets the TrueType fonts of a document to the glyphs shown on its pages
+fn subset_fonts(document: &mut Document) -> Result<()> {
+    let used = shown_glyphs(document)?;
+    let fonts: Vec<ObjectId> = document.objects.iter()
+        .filter(|(_, object)| object.as_dict().is_ok_and(|font| is_name(font, b"Subtype", b"Type0")))
+        .map(|(&id, _)| id)
+        .collect();
+
+    for font in fonts {
+        let glyphs = used.get(&font).cloned().unwrap_or_default();
+        subset_font(document, font, &glyphs)
+            .context(format!("Failed to subset font {} {}", font.0, font.1))?;
+    }
+    Ok(())
+}
+
+/// Subsets the font program of a Type 0 font with a TrueType descendant font
+///
+/// # Arguments
+///
+/// * `document` - The PDF document
+/// * `font` - The Type 0 font dictionary
+/// * `glyphs` - The glyph ids shown with the font
+///
+/// # Returns
+///
+/// * `Result<()>` - Success or an error
+fn subset_font(document: &mut Document, font: ObjectId, glyphs: &BTreeSet<u16>) -> Result<()> {
+    let descendant = descendant_font(document, font)?;
+    if !is_name(descendant, b"Subtype", b"CIDFontType2") {
+        return Ok(());
+    }
+    let descriptor = descendant.get(b"FontDescriptor").and_then(Object::as_reference)?;
+    let Ok(file) = document.get_dictionary(descriptor)?.get(b"FontFile2").and_then(Object::as_reference) else {
+        // Fonts that are not embedded are reported by the check
+        return Ok(());
+    };
+    let stream = document.get_object(file)?.as_stream()?;
+    let program = match stream.dict.has(b"Filter") {
+        true => stream.decompressed_content()?,
+        false => stream.content.clone(),
+    };
+
+    let Some(subset) = font_subset::subset_truetype(&program, glyphs)? else {
+        debug!("Font {} {} has no TrueType outlines and is not subset", font.0, font.1);
+        return Ok(());
+    };
+    debug!("Subset font {} {} to {} glyphs: {} of {} bytes", font.0, font.1, glyphs.len(), subset.len(), program.len());
+
+    let mut stream = Stream::new(dictionary! { "Length1" => subset.len() as i64 }, subset);
+    stream.compress()?;
+    document.objects.insert(file, Object::Stream(stream));
+
+    // The character map lists every glyph of the font, like the widths
+    if let Ok(map) = document.get_dictionary(font)?.get(b"ToUnicode").and_then(Object::as_reference) {
+        let stream = document.get_object(map)?.as_stream()?;
+        let content = match stream.dict.has(b"Filter") {
+            true => stream.decompressed_content()?,
+            false => stream.content.clone(),
+        };
+        let content = subset_character_map(&String::from_utf8_lossy(&content), glyphs);
+        document.objects.insert(map, Object::Stream(Stream::new(Dictionary::new(), content.into_bytes())));
+    }
+
+    // Subset fonts are named with a tag identifying the subset
+    let base_name = font_subset::postscript_name(&program).unwrap_or_else(|| {
+        let name = document.get_dictionary(font).and_then(|font| font.get(b"BaseFont")).and_then(Object::as_name_str);
+        name.unwrap_or("Font").to_string()
+    });
+    let name = Object::Name(format!("{}+{}", subset_tag(&base_name, glyphs), base_name).into_bytes());
+    dictionary_mut(document, descriptor)?.set("FontName", name.clone());
+    let font = dictionary_mut(document, font)?;
+    font.set("BaseFont", name.clone());
+    let descendant = match font.get_mut(b"DescendantFonts")?.as_array_mut()?.first_mut() {
+        Some(Object::Reference(id)) => Some(*id),
+        Some(Object::Dictionary(descendant)) => {
+            subset_descendant(descendant, name.clone(), glyphs);
+            None
+        },
+        _ => anyhow::bail!("Missing descendant font"),
+    };
+    if let Some(descendant) = descendant {
+        subset_descendant(dictionary_mut(document, descendant)?, name, glyphs);
+    }
+
+    Ok(())
+}
+
+/// Names the descendant font of a subset font and keeps only the widths of its glyphs
+fn subset_descendant(descendant: &mut Dictionary, name: Object, glyphs: &BTreeSet<u16>) {
+    descendant.set("BaseFont", name);
+    // The glyph ids are unchanged, so character ids still map to them directly
+    descendant.set("CIDToGIDMap", "Identity");
+
+    if let Ok(widths) = descendant.get(b"W").and_then(Object::as_array) {
+        let widths = glyph_widths(widths);
+        let mut subset: Vec<Object> = Vec::new();
+        for (glyph, width) in widths.into_iter().filter(|(glyph, _)| glyphs.contains(glyph)) {
+            // Consecutive glyphs share one array, as in `3 [317 400]`
+            let follows = subset.len() >= 2 && match (&subset[subset.len() - 2], &subset[subset.len() - 1]) {
+                (Object::Integer(first), Object::Array(run)) => *first + run.len() as i64 == i64::from(glyph),
+                _ => false,
+            };
+            match subset.last_mut() {
+                Some(Object::Array(run)) if follows => run.push(width),
+                _ => subset.extend([Object::Integer(i64::from(glyph)), Object::Array(vec![width])]),
+            }
+        }
+        descendant.set("W", subset);
+    }
+}
+
+/// Removes the glyphs that are not shown from a ToUnicode character map
+///
+/// # Arguments
+///
+/// * `map` - The CMap program, listing glyphs in `beginbfchar` blocks
+/// * `glyphs` - The glyph ids shown with the font
+///
+/// # Returns
+///
+/// * `String` - The CMap program with only the entries of the shown glyphs
+fn subset_character_map(map: &str, glyphs: &BTreeSet<u16>) -> String {
+    let mut subset = String::new();
+    let mut entries: Option<Vec<&str>> = None;
+    for line in map.lines() {
+        let text = line.trim();
+        match &mut entries {
+            None if text.ends_with("beginbfchar") => entries = Some(Vec::new()),
+            None => {
+                subset.push_str(line);
+                subset.push('\n');
+            },
+            Some(kept) if text == "endbfchar" => {
+                if !kept.is_empty() {
+                    subset.push_str(&format!("{} beginbfchar\n", kept.len()));
+                    for entry in kept.iter() {
+                        subset.push_str(entry);
+                        subset.push('\n');
+                    }
+                    subset.push_str("endbfchar\n");
+                }
+                entries = None;
+            },
+            // Entries map a glyph to its characters, as in `<0044> <0061>`
+            Some(kept) => {
+                let glyph = text.strip_prefix('<')
+                    .and_then(|entry| entry.split_once('>'))
+                    .and_then(|(glyph, _)| u16::from_str_radix(glyph, 16).ok());
+                if glyph.is_some_and(|glyph| glyphs.contains(&glyph)) {
+                    kept.push(text);
+                }
+            },
+        }
+    }
+    subset
+}
+
+/// Returns the width of each glyph listed in the `W` array of a CID font
+fn glyph_widths(widths: &[Object]) -> BTreeMap<u16, Object> {
+    let mut glyphs = BTreeMap::new();
+    let mut entries = widths.iter().peekable();
+    while let Some(first) = entries.next().and_then(|first| first.as_i64().ok()) {
+        match entries.next() {
+            // `first [w1 w2 ...]` lists the widths of consecutive glyphs
+            Some(Object::Array(run)) => {
+                for (offset, width) in run.iter().enumerate() {
+                    if let Ok(glyph) = u16::try_from(first + offset as i64) {
+                        glyphs.insert(glyph, width.clone());
+                    }
+                }
+            },
+            // `first last w` gives a range of glyphs the same width
+            Some(last) => {
+                let (Ok(last), Some(width)) = (last.as_i64(), entries.next()) else {
+                    break;
+                };
+                for glyph in (first..=last).filter_map(|glyph| u16::try_from(glyph).ok()) {
+                    glyphs.insert(glyph, width.clone());
+                }
+            },
+            None => break,
+        }
+    }
+    glyphs
+}
+
+/// Returns the descendant font of a Type 0 font
+fn descendant_font(document: &Document, font: ObjectId) -> Result<&Dictionary> {
+    let descendant = document.get_dictionary(font)?
+        .get(b"DescendantFonts")
+        .and_then(Object::as_array)?
+        .first()
+        .context("Missing descendant font")?;
+    Ok(resolve(document, descendant).as_dict()?)
+}
+
+/// Returns the six uppercase letters that tag a font subset, e.g. `KJHDSA`
+fn subset_tag(name: &str, glyphs: &BTreeSet<u16>) -> String {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    glyphs.hash(&mut hasher);
+    let mut value = hasher.finish();
+
+    (0..6)
+        .map(|_| {
+            let letter = char::from(b'A' + (value % 26) as u8);
+            value /= 26;
+            letter
+        })
+        .collect()
+}
+
+/// Returns the glyph ids shown with each font on the pages of a document
+///
+/// Text in genpdf documents is encoded with Identity-H, two bytes per glyph id.
+///
+/// # Arguments
+///
+/// * `document` - The PDF document
+///
+/// # Returns
+///
+/// * `Result<BTreeMap<ObjectId, BTreeSet<u16>>>` - The glyph ids by font, or an error
+fn shown_glyphs(document: &Document) -> Result<BTreeMap<ObjectId, BTreeSet<u16>>> {
+    let mut used: BTreeMap<ObjectId, BTreeSet<u16>> = BTreeMap::new();
+    for page in document.get_pages().into_values() {
+        let fonts = page_fonts(document, page);
+        let content = document.get_and_decode_page_content(page)?;
+
+        let mut font = None;
+        for operation in &content.operations {
+            match operation.operator.as_str() {
+                "Tf" => {
+                    font = operation.operands.first()
+                        .and_then(|name| name.as_name().ok())
+                        .and_then(|name| fonts.get(name))
+                        .copied();
+                },
+                "Tj" | "TJ" | "'" | "\"" => {
+                    if let Some(font) = font {
+                        let glyphs = used.entry(font).or_default();
+                        for text in operation.operands.iter().flat_map(text_strings) {
+                            glyphs.extend(text.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])));
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+    Ok(used)
+}
+
+/// Returns the strings of a text operand, which may be an array of strings and spacings
+fn text_strings(operand: &Object) -> Vec<&[u8]> {
+    match operand {
+        Object::String(text, _) => vec![text.as_slice()],
+        Object::Array(items) => items.iter().flat_map(text_strings).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Returns the fonts of a page's resources by resource name
+fn page_fonts(document: &Document, page: ObjectId) -> HashMap<Vec<u8>, ObjectId> {
+    let (direct, inherited) = document.get_page_resources(page);
+    let mut fonts = HashMap::new();
+    for resources in direct.into_iter().chain(inherited.iter().filter_map(|&id| document.get_dictionary(id).ok())) {
+        let Ok(entries) = resources.get(b"Font").map(|fonts| resolve(document, fonts)).and_then(Object::as_dict) else {
+            continue;
+        };
+        for (name, font) in entries.iter() {
+            if let Ok(id) = font.as_reference() {
+                fonts.entry(name.clone()).or_insert(id);
+            }
+        }
+    }
+    fonts
+}
+
+/// Replaces the output intents with an sRGB output intent for PDF/A
+fn set_output_intent(document: &mut Document) -> Result<()> {
+    let profile = document.add_object(Stream::new(dictionary! { "N" => 3 }, srgb_profile()));
+    let intent = dictionary! {
+        "Type" => "OutputIntent",
+        "S" => "GTS_PDFA1",
+        "OutputConditionIdentifier" => Object::string_literal(SRGB_CONDITION),
+        "Info" => Object::string_literal(SRGB_CONDITION),
+        "RegistryName" => Object::string_literal("http://www.color.org"),
+        "DestOutputProfile" => profile,
+    };
+    pdf_postprocess::catalog_mut(document)?.set("OutputIntents", vec![Object::Dictionary(intent)]);
+    Ok(())
+}
+
+/// Builds an ICC version 2 display profile of the sRGB colour space
+///
+/// The colorants are the sRGB primaries adapted to the D50 white point of the
+/// profile connection space, and the tone curves are sampled from the sRGB curve.
+fn srgb_profile() -> Vec<u8> {
+    let xyz = |values: [f64; 3]| {
+        let mut tag = b"XYZ \0\0\0\0".to_vec();
+        for value in values {
+            tag.extend_from_slice(&((value * 65536.0).round() as i32).to_be_bytes());
+        }
+        tag
+    };
+    let mut description = b"desc\0\0\0\0".to_vec();
+    description.extend_from_slice(&(SRGB_CONDITION.len() as u32 + 1).to_be_bytes());
+    description.extend_from_slice(SRGB_CONDITION.as_bytes());
+    // Terminating zero, empty Unicode and ScriptCode descriptions
+    description.resize(description.len() + 1 + 8 + 3 + 67, 0);
+    let mut copyright = b"text\0\0\0\0".to_vec();
+    copyright.extend_from_slice(b"No copyright, use freely\0");
+    let mut curve = b"curv\0\0\0\0".to_vec();
+    curve.extend_from_slice(&(SRGB_CURVE_ENTRIES as u32).to_be_bytes());
+    for index in 0..SRGB_CURVE_ENTRIES {
+        let value = index as f64 / (SRGB_CURVE_ENTRIES - 1) as f64;
+        let linear = if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) };
+        curve.extend_from_slice(&((linear * 65535.0).round() as u16).to_be_bytes());
+    }
+
+    // The red, green and blue tone curves share the same data
+    let tags: [(&[u8; 4], usize); 9] = [
+        (b"desc", 0), (b"cprt", 1), (b"wtpt", 2), (b"rXYZ", 3), (b"gXYZ", 4), (b"bXYZ", 5), (b"rTRC", 6), (b"gTRC", 6), (b"bTRC", 6),
+    ];
+    let data = [
+        description,
+        copyright,
+        xyz([0.9642, 1.0, 0.8249]),
+        xyz([0.4361, 0.2225, 0.0139]),
+        xyz([0.3851, 0.7169, 0.0971]),
+        xyz([0.1431, 0.0606, 0.7141]),
+        curve,
+    ];
+
+    let mut offsets = Vec::new();
+    let mut offset = 128 + 4 + 12 * tags.len();
+    for element in &data {
+        offsets.push(offset);
+        offset += element.len().next_multiple_of(4);
+    }
+
+    let mut profile = Vec::with_capacity(offset);
+    profile.extend_from_slice(&(offset as u32).to_be_bytes());
+    profile.extend_from_slice(&[0; 4]);
+    profile.extend_from_slice(&0x0210_0000u32.to_be_bytes());
+    profile.extend_from_slice(b"mntrRGB XYZ ");
+    // Creation date: 2000-01-01 00:00:00
+    for value in [2000u16, 1, 1, 0, 0, 0] {
+        profile.extend_from_slice(&value.to_be_bytes());
+    }
+    profile.extend_from_slice(b"acsp");
+    // Platform, flags, manufacturer, model, attributes and rendering intent
+    profile.resize(68, 0);
+    profile.extend_from_slice(&xyz([0.9642, 1.0, 0.8249])[8..]);
+    profile.resize(128, 0);
+
+    profile.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+    for (signature, element) in tags {
+        profile.extend_from_slice(signature);
+        profile.extend_from_slice(&(offsets[element] as u32).to_be_bytes());
+        profile.extend_from_slice(&(data[element].len() as u32).to_be_bytes());
+    }
+    for element in &data {
+        profile.extend_from_slice(element);
+        profile.resize(profile.len().next_multiple_of(4), 0);
+    }
+    profile
+}
+
+/// Checks the header and the binary comment following it
+fn check_file_structure(data: &[u8]) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut lines = data.split(|&byte| byte == b'\n' || byte == b'\r');
+    if !lines.next().is_some_and(|header| header.starts_with(b"%PDF-")) {
+        problems.push("The file does not start with a PDF header".to_string());
+    }
+    let binary = lines.next()
+        .and_then(|comment| comment.strip_prefix(b"%"))
+        .is_some_and(|comment| comment.len() >= 4 && comment.iter().take(4).all(|&byte| byte > 127));
+    if !binary {
+        problems.push("The header is not followed by a comment marking the file as binary".to_string());
+    }
+    if !data.trim_ascii_end().ends_with(b"%%EOF") {
+        problems.push("The file does not end with %%EOF".to_string());
+    }
+    problems
+}
+
+/// Checks the metadata, output intents and document-level features of the catalog
+fn check_catalog(document: &Document, catalog: &Dictionary, problems: &mut Vec<String>) {
+    // The XMP metadata identifies the PDF/A part and conformance level
+    let metadata = catalog.get(b"Metadata")
+        .map(|metadata| resolve(document, metadata))
+        .and_then(Object::as_stream);
+    match metadata {
+        Ok(metadata) if metadata.dict.has(b"Filter") => problems.push("The XMP metadata is compressed".to_string()),
+        Ok(metadata) => {
+            let xmp = String::from_utf8_lossy(&metadata.content);
+            let identified = xmp.contains(&format!("<pdfaid:part>{}</pdfaid:part>", PDF_A_PART))
+                && xmp.contains(&format!("<pdfaid:conformance>{}</pdfaid:conformance>", PDF_A_CONFORMANCE));
+            if !identified {
+                problems.push(format!("The XMP metadata does not identify the file as PDF/A-{}{}", PDF_A_PART, PDF_A_CONFORMANCE));
+            }
+            check_info(document, &xmp, problems);
+        },
+        Err(_) => problems.push("The document has no XMP metadata".to_string()),
+    }
+
+    // All output intents share one ICC profile, and one of them is for PDF/A
+    let intents: Vec<&Dictionary> = catalog.get(b"OutputIntents")
+        .map(|intents| resolve(document, intents))
+        .and_then(Object::as_array)
+        .map(|intents| intents.iter().filter_map(|intent| resolve(document, intent).as_dict().ok()).collect())
+        .unwrap_or_default();
+    if !intents.iter().any(|intent| is_name(intent, b"S", b"GTS_PDFA1")) {
+        problems.push("The document has no PDF/A output intent".to_string());
+    }
+    let profiles: BTreeSet<ObjectId> = intents.iter()
+        .filter_map(|intent| intent.get(b"DestOutputProfile").and_then(Object::as_reference).ok())
+        .collect();
+    if profiles.len() > 1 {
+        problems.push("The output intents use different ICC profiles".to_string());
+    }
+    for &profile in &profiles {
+        let header = document.get_object(profile).and_then(Object::as_stream).map(|profile| match profile.dict.has(b"Filter") {
+            true => profile.decompressed_content().unwrap_or_default(),
+            false => profile.content.clone(),
+        });
+        let valid = header.is_ok_and(|header| {
+            header.len() >= 128 && header[8] <= 4 && (&header[16..20] == b"RGB " || &header[16..20] == b"GRAY")
+        });
+        if !valid {
+            problems.push("The output intent profile is not an RGB or grey ICC profile of version 4 or earlier".to_string());
+        }
+    }
+
+    if catalog.has(b"AA") {
+        problems.push("The document has additional actions".to_string());
+    }
+    if matches!(catalog.get(b"NeedsRendering"), Ok(Object::Boolean(true))) {
+        problems.push("The document needs dynamic form rendering".to_string());
+    }
+    if let Ok(names) = catalog.get(b"Names").map(|names| resolve(document, names)).and_then(Object::as_dict) {
+        for (key, feature) in [(b"JavaScript".as_slice(), "JavaScript"), (b"EmbeddedFiles".as_slice(), "embedded files")] {
+            if names.has(key) {
+                problems.push(format!("The document contains {}", feature));
+            }
+        }
+    }
+    if let Ok(form) = catalog.get(b"AcroForm").map(|form| resolve(document, form)).and_then(Object::as_dict)
+        && form.has(b"XFA")
+    {
+        problems.push("The document contains an XFA form".to_string());
+    }
+
+    if let Ok(properties) = catalog.get(b"OCProperties").map(|properties| resolve(document, properties)).and_then(Object::as_dict) {
+        let configurations = properties.get(b"D").into_iter()
+            .chain(properties.get(b"Configs").and_then(Object::as_array).into_iter().flatten())
+            .filter_map(|configuration| resolve(document, configuration).as_dict().ok());
+        for configuration in configurations {
+            if !configuration.has(b"Name") || configuration.has(b"AS") {
+                problems.push("An optional content configuration has no name or has automatic states".to_string());
+            }
+        }
+    }
+}
+
+/// Checks that the entries of the document information dictionary match the XMP metadata
+fn check_info(document: &Document, xmp: &str, problems: &mut Vec<String>) {
+    let Ok(info) = document.trailer.get(b"Info").map(|info| resolve(document, info)).and_then(Object::as_dict) else {
+        return;
+    };
+    let properties = xmp_properties(xmp);
+
+    for &(key, property) in INFO_XMP_PROPERTIES {
+        let Ok(value) = info.get(key).map(|value| resolve(document, value)).and_then(Object::as_str) else {
+            continue;
+        };
+        let value = pdf_postprocess::decode_text_string(value);
+        let xmp_value = properties.get(property);
+        // Dates are compared as points in time, since both forms are written differently
+        let matches = match key {
+            b"CreationDate" | b"ModDate" => Timestamp::parse_pdf(&value)
+                .zip(xmp_value.and_then(|xmp_value| Timestamp::parse(xmp_value)))
+                .is_some_and(|(date, xmp_date)| date.same_time(xmp_date)),
+            _ => xmp_value == Some(&value),
+        };
+        if !matches {
+            problems.push(format!("The {} of the document information does not match {} in the XMP metadata",
+                                  String::from_utf8_lossy(key), property));
+        }
+    }
+}
+
+/// Reads the XMP properties of an XMP packet by qualified name
+///
+/// Each property holds its first text, which is the simple value, the default language
+/// alternative or the first item of an array, as written by the converter.
+fn xmp_properties(xmp: &str) -> HashMap<String, String> {
+    let mut reader = quick_xml::Reader::from_str(xmp);
+    reader.trim_text(true);
+    let mut path: Vec<Vec<u8>> = Vec::new();
+    let mut properties = HashMap::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(start)) => path.push(start.name().as_ref().to_vec()),
+            Ok(Event::End(_)) => { path.pop(); },
+            Ok(Event::Text(text)) => {
+                let property = path.iter()
+                    .position(|name| name == b"rdf:Description")
+                    .and_then(|index| path.get(index + 1));
+                if let Some(property) = property
+                    && let Ok(text) = text.unescape()
+                {
+                    properties.entry(String::from_utf8_lossy(property).to_string()).or_insert_with(|| text.to_string());
+                }
+            },
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {},
+        }
+    }
+
+    properties
+}
+
+/// Checks the content of a page for colours and glyphs PDF/A does not allow
+fn check_page(document: &Document, number: u32, page: ObjectId, problems: &mut Vec<String>) {
+    if document.get_dictionary(page).is_ok_and(|page| page.has(b"AA")) {
+        problems.push(format!("Page {} has additional actions", number));
+    }
+
+    let Ok(content) = document.get_and_decode_page_content(page) else {
+        problems.push(format!("The content of page {} cannot be read", number));
+        return;
+    };
+    let fonts = page_fonts(document, page);
+    let mut font = None;
+    let mut reported = BTreeSet::new();
+    for operation in &content.operations {
+        let problem = match operation.operator.as_str() {
+            // The output intent is RGB, so CMYK colours are not allowed
+            "k" | "K" => Some("uses CMYK colours".to_string()),
+            "cs" | "CS" if operation.operands.first().and_then(|name| name.as_name().ok()) == Some(b"DeviceCMYK") => {
+                Some("uses CMYK colours".to_string())
+            },
+            "Tf" => {
+                font = operation.operands.first().and_then(|name| name.as_name().ok()).map(<[u8]>::to_vec);
+                None
+            },
+            // Glyph 0 is shown for characters the font does not have
+            "Tj" | "TJ" | "'" | "\"" => {
+                let missing = operation.operands.iter()
+                    .flat_map(text_strings)
+                    .any(|text| text.chunks_exact(2).any(|pair| pair == [0, 0]));
+                let is_cid_font = font.as_ref()
+                    .and_then(|name| fonts.get(name))
+                    .and_then(|&id| document.get_dictionary(id).ok())
+                    .is_some_and(|font| is_name(font, b"Subtype", b"Type0"));
+                (missing && is_cid_font).then(|| "shows characters missing from its font".to_string())
+            },
+            _ => None,
+        };
+        if let Some(problem) = problem
+            && reported.insert(problem.clone())
+        {
+            problems.push(format!("Page {} {}", number, problem));
+        }
+    }
+}
+
+/// Checks a stream for external data and filters PDF/A does not allow
+fn check_stream(id: ObjectId, stream: &Stream, problems: &mut Vec<String>) {
+    if stream.dict.has(b"F") || stream.dict.has(b"FFilter") || stream.dict.has(b"FDecodeParms") {
+        problems.push(format!("Stream {} {} refers to external data", id.0, id.1));
+    }
+    if stream.filters().is_ok_and(|filters| filters.iter().any(|filter| filter == "LZWDecode")) {
+        problems.push(format!("Stream {} {} is LZW compressed", id.0, id.1));
+    }
+}
+
+/// Checks fonts, images, annotations, actions and graphics states
+fn check_dictionary(document: &Document, id: ObjectId, dictionary: &Dictionary, problems: &mut Vec<String>) {
+    let object = format!("{} {}", id.0, id.1);
+
+    if let Ok(action) = dictionary.get(b"S").and_then(Object::as_name)
+        && FORBIDDEN_ACTIONS.contains(&action)
+        && (dictionary.type_is(b"Action") || !dictionary.has(b"Type"))
+    {
+        problems.push(format!("Object {} is a {} action", object, String::from_utf8_lossy(action)));
+    }
+
+    if dictionary.type_is(b"Font") {
+        check_font(document, &object, dictionary, problems);
+    }
+
+    if dictionary.type_is(b"Annot") {
+        let subtype = dictionary.get(b"Subtype").and_then(Object::as_name).unwrap_or_default();
+        if FORBIDDEN_ANNOTATIONS.contains(&subtype) {
+            problems.push(format!("Annotation {} is a {} annotation", object, String::from_utf8_lossy(subtype)));
+        }
+        let flags = dictionary.get(b"F").and_then(Object::as_i64).unwrap_or(0);
+        if flags & ANNOTATION_PRINT == 0 || flags & ANNOTATION_NOT_SHOWN != 0 {
+            problems.push(format!("Annotation {} is not printed or not shown", object));
+        }
+        if !dictionary.has(b"AP") && subtype != b"Link" && subtype != b"Popup" {
+            problems.push(format!("Annotation {} has no appearance", object));
+        }
+    }
+
+    match dictionary.get(b"Subtype").and_then(Object::as_name).unwrap_or_default() {
+        b"Image" => {
+            if matches!(dictionary.get(b"Interpolate"), Ok(Object::Boolean(true))) {
+                problems.push(format!("Image {} is interpolated", object));
+            }
+            if dictionary.has(b"Alternates") || dictionary.has(b"OPI") {
+                problems.push(format!("Image {} has alternate images", object));
+            }
+            if dictionary.get(b"ColorSpace").and_then(Object::as_name).ok() == Some(b"DeviceCMYK") {
+                problems.push(format!("Image {} uses CMYK colours", object));
+            }
+        },
+        b"PS" => problems.push(format!("Object {} is a PostScript XObject", object)),
+        _ => {}
+    }
+
+    if dictionary.type_is(b"ExtGState") {
+        let custom_transfer = dictionary.get(b"TR2").and_then(Object::as_name).map_or(dictionary.has(b"TR2"), |name| name != b"Default");
+        if dictionary.has(b"TR") || custom_transfer {
+            problems.push(format!("Graphics state {} has a transfer function", object));
+        }
+    }
+}
+
+/// Checks that a font is embedded as PDF/A requires
+fn check_font(document: &Document, object: &str, font: &Dictionary, problems: &mut Vec<String>) {
+    let subtype = font.get(b"Subtype").and_then(Object::as_name).unwrap_or_default();
+    let name = font.get(b"BaseFont").and_then(Object::as_name_str).unwrap_or(object);
+    match subtype {
+        // Type 0 fonts are checked through their descendant font, Type 3 fonts are drawn with PDF operators
+        b"Type0" | b"Type3" => return,
+        b"CIDFontType2" if !font.has(b"CIDToGIDMap") => {
+            problems.push(format!("Font {} has no CIDToGIDMap", name));
+        },
+        _ => {}
+    }
+
+    let descriptor = font.get(b"FontDescriptor")
+        .map(|descriptor| resolve(document, descriptor))
+        .and_then(Object::as_dict);
+    let program = descriptor.ok().and_then(|descriptor| {
+        [b"FontFile".as_slice(), b"FontFile2", b"FontFile3"].into_iter()
+            .find_map(|key| Some((key, descriptor.get(key).ok()?)))
+    });
+    let Some((key, program)) = program else {
+        problems.push(format!("Font {} is not embedded", name));
+        return;
+    };
+
+    // FontFile2 holds TrueType fonts; OpenType fonts with CFF outlines and collections need other embeddings
+    if key == b"FontFile2"
+        && let Ok(program) = resolve(document, program).as_stream()
+    {
+        let data = match program.dict.has(b"Filter") {
+            true => program.decompressed_content().unwrap_or_default(),
+            false => program.content.clone(),
+        };
+        if !(data.starts_with(&[0, 1, 0, 0]) || data.starts_with(b"true")) {
+            problems.push(format!("Font {} is not a TrueType font", name));
+        }
+    }
+}
+
+/// Returns the object a reference points to, or the object itself if it is not a reference
+fn resolve<'a>(document: &'a Document, object: &'a Object) -> &'a Object {
+    document.dereference(object).map_or(object, |(_, object)| object)
+}
+
+/// Returns a dictionary object of a document for modification
+fn dictionary_mut(document: &mut Document, id: ObjectId) -> Result<&mut Dictionary> {
+    Ok(document.get_object_mut(id).and_then(Object::as_dict_mut)?)
+}
+
+/// Returns `true` if a dictionary entry is the given name
+fn is_name(dictionary: &Dictionary, key: &[u8], name: &[u8]) -> bool {
+    dictionary.get(key).and_then(Object::as_name).ok() == Some(name)
+}
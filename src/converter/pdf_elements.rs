@@ -0,0 +1,702 @@
+//! Custom genpdf elements used by the PDF writer.
+//! These cover layout features that the built-in genpdf elements do not support.
+
+use std::collections::VecDeque;
+use genpdf::{elements, render, style, Alignment, Context, Element, Margins, Mm, PageDecorator, Position, RenderResult, Size};
+use genpdf::error::Error;
+use image::GenericImageView;
+
+use crate::converter::docx_reader::{VerticalAlign, PAGE_COUNT_PLACEHOLDER, PAGE_NUMBER_PLACEHOLDER};
+
+/// Scale applied to the font size of superscript and subscript text
+const SCRIPT_SCALE: f64 = 0.65;
+
+/// Font size of page header and footer lines
+const HEADER_FONT_SIZE: u8 = 9;
+
+/// Number of levels per color channel that survive in the content stream,
+/// which stores color components with two decimals
+const COLOR_LEVELS: u16 = 100;
+
+/// Red channel level that identifies position markers
+///
+/// genpdf does not expose absolute positions, so elements draw zero-length lines
+/// in a reserved color instead. The post-processing pass reads their coordinates
+/// from the content stream and removes them again.
+const MARKER_RED_LEVEL: u16 = 99;
+
+/// Largest marker ID that can be encoded in the green and blue channels
+pub const MAX_MARKER_ID: u16 = COLOR_LEVELS * COLOR_LEVELS - 1;
+
+/// A span of text with a single style inside a `RichParagraph`
+#[derive(Debug, Clone, Default)]
+pub struct Span {
+    /// Text of the span
+    pub text: String,
+    /// genpdf style (font, weight, slant, size and color)
+    pub style: style::Style,
+    /// Draw a line below the text
+    pub underline: bool,
+    /// Draw a line through the text
+    pub strike: bool,
+    /// Superscript or subscript position
+    pub vertical_align: VerticalAlign,
+    /// Marker ID of the hyperlink the span belongs to
+    pub link: Option<u16>,
+}
+
+impl Span {
+    /// Creates a span of plain text with the given style
+    pub fn new(text: impl Into<String>, style: style::Style) -> Span {
+        Span {
+            text: text.into(),
+            style,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the style used to print the span, with script text scaled down
+    fn print_style(&self) -> style::Style {
+        match self.vertical_align {
+            VerticalAlign::Baseline => self.style,
+            _ => {
+                let size = (f64::from(self.style.font_size()) * SCRIPT_SCALE).round().max(1.0);
+                self.style.with_font_size(size as u8)
+            }
+        }
+    }
+}
+
+/// A word (including its trailing whitespace) that belongs to a span
+#[derive(Debug, Clone)]
+struct Piece {
+    /// Index of the span the word belongs to
+    span: usize,
+    /// Text of the word
+    text: String,
+}
+
+/// A paragraph made of styled spans, with support for underline,
+/// strikethrough and superscript/subscript text
+#[derive(Debug, Clone, Default)]
+pub struct RichParagraph {
+    spans: Vec<Span>,
+    anchor: Option<u16>,
+    pending: VecDeque<Piece>,
+    prepared: bool,
+}
+
+impl RichParagraph {
+    /// Creates an empty rich paragraph
+    pub fn new() -> RichParagraph {
+        RichParagraph::default()
+    }
+
+    /// Adds a span to the end of the paragraph
+    pub fn push(&mut self, span: Span) {
+        self.spans.push(span);
+    }
+
+    /// Adds a span to the end of the paragraph and returns the paragraph
+    pub fn span(mut self, span: Span) -> Self {
+        self.push(span);
+        self
+    }
+
+    /// Sets the marker ID recording where the paragraph starts, for internal links
+    pub fn with_anchor(mut self, anchor: u16) -> Self {
+        self.anchor = Some(anchor);
+        self
+    }
+
+    /// Applies the inherited style and splits the spans into words
+    fn prepare(&mut self, style: style::Style) {
+        for (index, span) in self.spans.iter_mut().enumerate() {
+            span.style = style.and(span.style);
+
+            let mut word = String::new();
+            for c in span.text.chars() {
+                if !c.is_whitespace() && word.ends_with(char::is_whitespace) {
+                    self.pending.push_back(Piece { span: index, text: std::mem::take(&mut word) });
+                }
+                word.push(c);
+            }
+            if !word.is_empty() {
+                self.pending.push_back(Piece { span: index, text: word });
+            }
+        }
+        self.prepared = true;
+    }
+
+    /// Takes the words that fit on the next line of the given width
+    fn take_line(&mut self, context: &Context, width: Mm) -> Vec<Piece> {
+        let mut line: Vec<Piece> = Vec::new();
+        let mut line_width = Mm::default();
+
+        while let Some(mut piece) = self.pending.pop_front() {
+            let style = self.spans[piece.span].print_style();
+            let word_width = style.str_width(&context.font_cache, piece.text.trim_end());
+
+            if line_width + word_width > width {
+                if !line.is_empty() {
+                    self.pending.push_front(piece);
+                    break;
+                }
+
+                // A single word wider than the line is broken between characters
+                let mut split = piece.text.len();
+                for (index, _) in piece.text.char_indices().skip(1) {
+                    if style.str_width(&context.font_cache, &piece.text[..index]) > width {
+                        break;
+                    }
+                    split = index;
+                }
+                if split < piece.text.len() {
+                    let rest = piece.text.split_off(split);
+                    self.pending.push_front(Piece { span: piece.span, text: rest });
+                }
+                line.push(piece);
+                break;
+            }
+
+            line_width += style.str_width(&context.font_cache, &piece.text);
+            line.push(piece);
+        }
+
+        line
+    }
+
+    /// Prints a line of words and draws their decorations
+    fn render_line(
+        &self,
+        context: &Context,
+        area: &render::Area<'_>,
+        line: &[Piece],
+        line_height: Mm,
+    ) -> Result<Mm, Error> {
+        // Group consecutive words of the same span so they are printed together
+        let mut groups: Vec<(usize, String)> = Vec::new();
+        for piece in line {
+            match groups.last_mut() {
+                Some((span, text)) if *span == piece.span => text.push_str(&piece.text),
+                _ => groups.push((piece.span, piece.text.clone())),
+            }
+        }
+        if let Some((_, text)) = groups.last_mut() {
+            let trimmed_len = text.trim_end().len();
+            text.truncate(trimmed_len);
+        }
+
+        let baseline = line.iter()
+            .map(|piece| glyph_height(context, &self.spans[piece.span].style))
+            .fold(Mm::default(), Mm::max);
+
+        let mut x = Mm::default();
+        for (index, text) in &groups {
+            let span = &self.spans[*index];
+            let print_style = span.print_style();
+            let text_height = glyph_height(context, &print_style);
+            let full_height = glyph_height(context, &span.style);
+            let shift = match span.vertical_align {
+                VerticalAlign::Baseline => Mm::default(),
+                VerticalAlign::Superscript => full_height * -0.4,
+                VerticalAlign::Subscript => full_height * 0.2,
+            };
+            let top = baseline - text_height + shift;
+
+            area.print_str(&context.font_cache, Position::new(x, top), print_style, text)?;
+            let text_width = print_style.str_width(&context.font_cache, text);
+
+            if span.underline {
+                let y = baseline + shift + text_height * 0.15;
+                area.draw_line(vec![Position::new(x, y), Position::new(x + text_width, y)], print_style);
+            }
+            if span.strike {
+                let y = baseline + shift - text_height * 0.3;
+                area.draw_line(vec![Position::new(x, y), Position::new(x + text_width, y)], print_style);
+            }
+            if let Some(link) = span.link {
+                // Opposite corners of the clickable rectangle
+                draw_marker(area, Position::new(x, 0), link);
+                draw_marker(area, Position::new(x + text_width, line_height), link);
+            }
+
+            x += text_width;
+        }
+
+        Ok(x)
+    }
+}
+
+impl Element for RichParagraph {
+    fn render(
+        &mut self,
+        context: &Context,
+        mut area: render::Area<'_>,
+        style: style::Style,
+    ) -> Result<RenderResult, Error> {
+        let mut result = RenderResult::default();
+
+        if !self.prepared {
+            self.prepare(style);
+        }
+
+        while !self.pending.is_empty() {
+            let line = self.take_line(context, area.size().width);
+            let line_height = line.iter()
+                .map(|piece| self.spans[piece.span].style.line_height(&context.font_cache))
+                .fold(Mm::default(), Mm::max);
+
+            if line_height > area.size().height {
+                // Put the words back so they are rendered on the next page
+                for piece in line.into_iter().rev() {
+                    self.pending.push_front(piece);
+                }
+                result.has_more = true;
+                break;
+            }
+
+            if let Some(anchor) = self.anchor.take() {
+                draw_marker(&area, Position::default(), anchor);
+            }
+            let line_width = self.render_line(context, &area, &line, line_height)?;
+            result.size = result.size.stack_vertical(Size::new(line_width, line_height));
+            area.add_offset(Position::new(0, line_height));
+        }
+
+        Ok(result)
+    }
+}
+
+/// An element that can be copied before it is rendered
+///
+/// genpdf elements consume their content while rendering, so repeated table
+/// header rows are rendered from fresh copies.
+trait CellElement: Element {
+    /// Returns a copy of the element in its current state
+    fn boxed_clone(&self) -> Box<dyn CellElement>;
+}
+
+impl<E: Element + Clone + 'static> CellElement for E {
+    fn boxed_clone(&self) -> Box<dyn CellElement> {
+        Box::new(self.clone())
+    }
+}
+
+/// A cell of a `GridTable`
+pub struct GridCell {
+    element: Box<dyn CellElement>,
+    column: usize,
+    col_span: usize,
+    row_span: usize,
+}
+
+impl GridCell {
+    /// Creates a cell starting at a grid column and covering `col_span` columns
+    pub fn new(element: impl Element + Clone + 'static, column: usize, col_span: usize) -> GridCell {
+        GridCell {
+            element: Box::new(element),
+            column,
+            col_span: col_span.max(1),
+            row_span: 1,
+        }
+    }
+
+    /// Sets the number of rows the cell covers, starting at its own row
+    pub fn with_row_span(mut self, row_span: usize) -> GridCell {
+        self.row_span = row_span.max(1);
+        self
+    }
+
+    /// Returns a copy of the cell that has not been rendered yet
+    fn duplicate(&self) -> GridCell {
+        GridCell {
+            element: self.element.boxed_clone(),
+            column: self.column,
+            col_span: self.col_span,
+            row_span: self.row_span,
+        }
+    }
+}
+
+/// A cell spanning several rows whose rows have not all been rendered yet
+struct PendingSpan {
+    /// Number of covered rows left, including the row being rendered
+    rows: usize,
+    /// Height of the cell not yet taken up by the rendered rows
+    height: Mm,
+}
+
+/// A table of grid columns whose cells may span several columns
+///
+/// Columns are equally wide unless weights are given. Cells spanning several rows start
+/// in their first row and run down beside the following rows, which leave the covered
+/// positions empty; the last covered row grows if the cell needs more room than the
+/// rows take up. Like genpdf's `TableLayout`,
+/// a row that does not fit is continued on the next page, where the header rows
+/// are repeated above it.
+pub struct GridTable {
+    column_count: usize,
+    column_weights: Vec<usize>,
+    rows: Vec<Vec<GridCell>>,
+    header_rows: usize,
+    /// Unrendered copies of the header rows, taken before the table is first rendered
+    header: Vec<Vec<GridCell>>,
+    header_height: Mm,
+    render_idx: usize,
+}
+
+impl GridTable {
+    /// Creates an empty table with the given number of grid columns
+    pub fn new(column_count: usize) -> GridTable {
+        GridTable {
+            column_count,
+            column_weights: vec![1; column_count],
+            rows: Vec::new(),
+            header_rows: 0,
+            header: Vec::new(),
+            header_height: Mm::default(),
+            render_idx: 0,
+        }
+    }
+
+    /// Sets the relative widths of the grid columns; missing weights count as one
+    pub fn with_column_weights(mut self, weights: &[usize]) -> GridTable {
+        self.column_weights = (0..self.column_count)
+            .map(|column| weights.get(column).copied().unwrap_or(1).max(1))
+            .collect();
+        self
+    }
+
+    /// Sets the number of leading rows repeated at the top of each page the table continues onto
+    pub fn with_header_rows(mut self, header_rows: usize) -> GridTable {
+        self.header_rows = header_rows;
+        self
+    }
+
+    /// Returns the offset of a grid column from the left edge, for a table of the given width
+    fn column_offset(&self, column: usize, width: Mm) -> Mm {
+        let total: usize = self.column_weights.iter().sum();
+        let before: usize = self.column_weights[..column].iter().sum();
+        width * (before as f64 / total as f64)
+    }
+
+    /// Adds a row of cells; cells outside the grid are dropped
+    pub fn push_row(&mut self, cells: Vec<GridCell>) {
+        let column_count = self.column_count;
+        self.rows.push(cells.into_iter()
+            .filter(|cell| cell.column < column_count)
+            .collect());
+    }
+
+    /// Renders a row of cells at the top of an area and returns its size
+    ///
+    /// Cells spanning several rows are added to the pending spans instead of setting
+    /// the height of the row. The row grows to fit the spans that end in it.
+    ///
+    /// # Arguments
+    ///
+    /// * `cells` - The cells of the row
+    /// * `offsets` - Offset of each grid column boundary from the left edge
+    /// * `pending` - Cells of earlier rows spanning this row
+    /// * `context` - The render context
+    /// * `area` - The area to render into
+    /// * `style` - The inherited style
+    ///
+    /// # Returns
+    ///
+    /// * `Result<RenderResult, Error>` - The size of the row and whether a cell continues on the next page
+    fn render_cells(
+        cells: &mut [GridCell],
+        offsets: &[Mm],
+        pending: &mut Vec<PendingSpan>,
+        context: &Context,
+        area: &render::Area<'_>,
+        style: style::Style,
+    ) -> Result<RenderResult, Error> {
+        let mut result = RenderResult::default();
+        let column_count = offsets.len() - 1;
+
+        for cell in cells {
+            let end = (cell.column + cell.col_span).min(column_count);
+            let mut cell_area = area.clone();
+            cell_area.add_offset(Position::new(offsets[cell.column], 0));
+            cell_area.set_width(offsets[end] - offsets[cell.column]);
+
+            let cell_result = cell.element.render(context, cell_area, style)?;
+            result.has_more |= cell_result.has_more;
+            if cell.row_span > 1 {
+                pending.push(PendingSpan { rows: cell.row_span, height: cell_result.size.height });
+            } else {
+                result.size.height = result.size.height.max(cell_result.size.height);
+            }
+        }
+        result.size.width = area.size().width;
+
+        // The row takes up the rest of the spans ending in it, and part of the others
+        for span in pending.iter().filter(|span| span.rows == 1) {
+            result.size.height = result.size.height.max(span.height);
+        }
+        for span in pending.iter_mut() {
+            span.rows -= 1;
+            span.height = (span.height - result.size.height).max(Mm::default());
+        }
+        pending.retain(|span| span.rows > 0);
+
+        Ok(result)
+    }
+}
+
+impl Element for GridTable {
+    fn render(
+        &mut self,
+        context: &Context,
+        mut area: render::Area<'_>,
+        style: style::Style,
+    ) -> Result<RenderResult, Error> {
+        let mut result = RenderResult::default();
+        if self.column_count == 0 {
+            return Ok(result);
+        }
+
+        let width = area.size().width;
+        let offsets: Vec<Mm> = (0..=self.column_count).map(|column| self.column_offset(column, width)).collect();
+        result.size.width = width;
+
+        let header_rows = self.header_rows.min(self.rows.len());
+        if self.render_idx == 0 && self.header.is_empty() {
+            self.header = self.rows[..header_rows].iter()
+                .map(|row| row.iter().map(GridCell::duplicate).collect())
+                .collect();
+        }
+
+        // Continue below a fresh copy of the header rows, unless they would leave little room for the rows
+        if self.render_idx >= header_rows && self.render_idx > 0 && header_rows > 0
+            && self.header_height * 2.0 <= area.size().height {
+            let mut pending = Vec::new();
+            for row in &self.header {
+                let mut cells: Vec<GridCell> = row.iter().map(GridCell::duplicate).collect();
+                let row_result = GridTable::render_cells(&mut cells, &offsets, &mut pending, context, &area, style)?;
+                result.size.height += row_result.size.height;
+                area.add_offset(Position::new(0, row_result.size.height));
+            }
+        }
+
+        // Spans are tracked on each page; a span cut by a page break has been drawn on the earlier page
+        let mut pending = Vec::new();
+        while self.render_idx < self.rows.len() {
+            let row_result = GridTable::render_cells(&mut self.rows[self.render_idx], &offsets, &mut pending, context, &area, style)?;
+            result.size.height += row_result.size.height;
+            area.add_offset(Position::new(0, row_result.size.height));
+            if self.render_idx < header_rows {
+                self.header_height += row_result.size.height;
+            }
+            if row_result.has_more {
+                break;
+            }
+            self.render_idx += 1;
+        }
+        result.has_more = self.render_idx < self.rows.len();
+        if !result.has_more {
+            // Spans reaching past the last row extend the table
+            result.size.height += pending.iter().map(|span| span.height).fold(Mm::default(), Mm::max);
+        }
+
+        Ok(result)
+    }
+}
+
+/// An image shown at its display size, shrunk to fit the available space
+pub struct FittedImage {
+    image: elements::Image,
+    pixel_width: u32,
+    width: Mm,
+    height: Mm,
+    max_height: Mm,
+    deferred: bool,
+}
+
+impl FittedImage {
+    /// Creates an image with the given display size
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The decoded image, without an alpha channel
+    /// * `width` - Display width
+    /// * `height` - Display height
+    /// * `max_height` - Height of the page content area; taller images are shrunk
+    ///
+    /// # Returns
+    ///
+    /// * `Result<FittedImage, Error>` - The image element or an error
+    pub fn new(data: image::DynamicImage, width: Mm, height: Mm, max_height: Mm) -> Result<FittedImage, Error> {
+        let pixel_width = data.width();
+        let image = elements::Image::from_dynamic_image(data)?
+            .with_alignment(Alignment::Center);
+
+        Ok(FittedImage {
+            image,
+            pixel_width,
+            width,
+            height,
+            max_height,
+            deferred: false,
+        })
+    }
+}
+
+impl Element for FittedImage {
+    fn render(
+        &mut self,
+        context: &Context,
+        area: render::Area<'_>,
+        style: style::Style,
+    ) -> Result<RenderResult, Error> {
+        let mut scale = 1.0_f64
+            .min(f64::from(area.size().width) / f64::from(self.width))
+            .min(f64::from(self.max_height) / f64::from(self.height));
+
+        if self.height * scale > area.size().height {
+            if !self.deferred {
+                // Move the image to the next page rather than shrinking it
+                self.deferred = true;
+                return Ok(RenderResult { has_more: true, ..Default::default() });
+            }
+            scale = scale.min(f64::from(area.size().height) / f64::from(self.height));
+        }
+
+        // genpdf derives the printed size from the pixel count and resolution
+        let width_inches = f64::from(self.width * scale) / 25.4;
+        self.image.set_dpi(f64::from(self.pixel_width) / width_inches);
+        self.image.render(context, area, style)
+    }
+}
+
+/// A page decorator that adds margins and prints header and footer lines on every page
+///
+/// Lines are centered and printed in a small font; the page number placeholder is
+/// replaced with the number of the page being decorated, and the page count
+/// placeholder with the total number of pages if it is known.
+#[derive(Debug, Clone)]
+pub struct HeaderFooterDecorator {
+    margins: Margins,
+    header: Vec<String>,
+    footer: Vec<String>,
+    page: usize,
+    page_count: Option<usize>,
+}
+
+impl HeaderFooterDecorator {
+    /// Creates a decorator with the given margins, header lines and footer lines
+    pub fn new(margins: impl Into<Margins>, header: Vec<String>, footer: Vec<String>) -> HeaderFooterDecorator {
+        HeaderFooterDecorator {
+            margins: margins.into(),
+            header,
+            footer,
+            page: 0,
+            page_count: None,
+        }
+    }
+
+    /// Sets the total number of pages printed for the page count placeholder
+    ///
+    /// Without a page count, the placeholder is printed as a question mark.
+    pub fn with_page_count(mut self, page_count: Option<usize>) -> HeaderFooterDecorator {
+        self.page_count = page_count;
+        self
+    }
+
+    /// Checks if any header or footer line shows the total number of pages
+    pub fn shows_page_count(&self) -> bool {
+        self.header.iter().chain(&self.footer).any(|line| line.contains(PAGE_COUNT_PLACEHOLDER))
+    }
+
+    /// Prints lines centered from the top of an area and returns their total height
+    fn print_lines(&self, context: &Context, area: &render::Area<'_>, lines: &[String], style: style::Style) -> Result<Mm, Error> {
+        let line_height = style.line_height(&context.font_cache);
+        let page_count = self.page_count.map_or_else(|| "?".to_string(), |count| count.to_string());
+        let mut y = Mm::default();
+
+        for line in lines {
+            let text = line.replace(PAGE_NUMBER_PLACEHOLDER, &self.page.to_string())
+                .replace(PAGE_COUNT_PLACEHOLDER, &page_count);
+            let width = style.str_width(&context.font_cache, &text);
+            let x = ((area.size().width - width) / 2.0).max(Mm::default());
+            area.print_str(&context.font_cache, Position::new(x, y), style, &text)?;
+            y += line_height;
+        }
+
+        Ok(y)
+    }
+}
+
+impl PageDecorator for HeaderFooterDecorator {
+    fn decorate_page<'a>(
+        &mut self,
+        context: &Context,
+        mut area: render::Area<'a>,
+        style: style::Style,
+    ) -> Result<render::Area<'a>, Error> {
+        self.page += 1;
+        area.add_margins(self.margins);
+
+        let style = style.with_font_size(HEADER_FONT_SIZE);
+        let line_height = style.line_height(&context.font_cache);
+
+        if !self.header.is_empty() {
+            let height = self.print_lines(context, &area, &self.header, style)?;
+            // Leave a blank line between the header and the page content
+            area.add_offset(Position::new(0, height + line_height));
+        }
+
+        if !self.footer.is_empty() {
+            let height = line_height * self.footer.len() as f64;
+            let content_height = area.size().height - height - line_height;
+            let mut footer_area = area.clone();
+            footer_area.add_offset(Position::new(0, content_height + line_height));
+            // Set the height exactly, as rounding errors could leave too little room for the last line
+            footer_area.set_height(height);
+            self.print_lines(context, &footer_area, &self.footer, style)?;
+            area.set_height(content_height);
+        }
+
+        Ok(area)
+    }
+}
+
+/// Returns the height of the glyphs printed with the given style
+fn glyph_height(context: &Context, style: &style::Style) -> Mm {
+    style.font(&context.font_cache).glyph_height(style.font_size())
+}
+
+/// Draws an invisible position marker that the post-processing pass can locate
+///
+/// # Arguments
+///
+/// * `area` - The area to draw on
+/// * `position` - Position of the marker relative to the area
+/// * `id` - Marker ID, encoded in the green and blue channels of the stroke color
+pub fn draw_marker(area: &render::Area<'_>, position: Position, id: u16) {
+    let channel = |level: u16| (f64::from(level) * 255.0 / f64::from(COLOR_LEVELS)).round() as u8;
+    let color = style::Color::Rgb(
+        channel(MARKER_RED_LEVEL),
+        channel(id / COLOR_LEVELS),
+        channel(id % COLOR_LEVELS),
+    );
+    area.draw_line(vec![position, position], style::Style::new().with_color(color));
+}
+
+/// Returns the marker ID encoded in an RGB stroke color, if it is a marker color
+///
+/// # Arguments
+///
+/// * `rgb` - Color components between 0 and 1, as stored in the content stream
+///
+/// # Returns
+///
+/// * `Option<u16>` - The marker ID, or `None` for other colors
+pub fn decode_marker_color(rgb: [f64; 3]) -> Option<u16> {
+    let [red, green, blue] = rgb.map(|value| (value * f64::from(COLOR_LEVELS)).round() as u16);
+    (red == MARKER_RED_LEVEL && green < COLOR_LEVELS && blue < COLOR_LEVELS)
+        .then(|| green * COLOR_LEVELS + blue)
+}
@@ -0,0 +1,653 @@
+//! Post-processing of rendered PDF files.
+//! genpdf cannot create annotations or outlines, so clickable links and bookmarks are
+//! added to the finished file with lopdf, using the position markers drawn by `pdf_elements`.
+//! The document properties are written to the information dictionary and XMP metadata,
+//! the fonts are subset and archival files are converted to PDF/A by `pdf_archive`.
+
+use std::collections::HashMap;
+use std::path::Path;
+use anyhow::{Result, Context};
+use log::{debug, warn};
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Document, Object, ObjectId};
+use quick_xml::escape::escape;
+
+use crate::converter::docx_reader::LinkTarget;
+use crate::converter::metadata::{DocumentProperties, Timestamp};
+use crate::converter::pdf_archive;
+use crate::converter::pdf_elements::{decode_marker_color, MAX_MARKER_ID};
+
+/// Name of the application recorded as the producer of PDF files
+const PRODUCER: &str = "Aqon";
+
+/// Prefix of the anchor names given to outline entries, for links from the table of contents
+const OUTLINE_ANCHOR_PREFIX: &str = "_Outline";
+
+/// An entry of the document outline
+#[derive(Debug, Clone)]
+struct OutlineEntry {
+    /// Title shown in the outline
+    title: String,
+    /// One-based nesting level
+    level: usize,
+    /// Marker ID of the position the entry leads to, if one was available
+    id: Option<u16>,
+}
+
+/// Marker IDs for the hyperlinks, bookmarks and outline entries of a document
+#[derive(Debug, Clone, Default)]
+pub struct LinkMap {
+    next_id: u16,
+    links: HashMap<LinkTarget, u16>,
+    anchors: HashMap<String, u16>,
+    outline: Vec<OutlineEntry>,
+}
+
+impl LinkMap {
+    /// Creates an empty link map
+    pub fn new() -> LinkMap {
+        LinkMap::default()
+    }
+
+    /// Returns the marker ID for a hyperlink target, allocating one if needed
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The hyperlink target
+    ///
+    /// # Returns
+    ///
+    /// * `Option<u16>` - The marker ID, or `None` if all IDs are in use
+    pub fn link(&mut self, target: &LinkTarget) -> Option<u16> {
+        if let Some(id) = self.links.get(target) {
+            return Some(*id);
+        }
+        let id = self.allocate()?;
+        self.links.insert(target.clone(), id);
+        Some(id)
+    }
+
+    /// Returns a marker ID for a paragraph carrying the given bookmarks
+    ///
+    /// # Arguments
+    ///
+    /// * `names` - The bookmark names of the paragraph
+    ///
+    /// # Returns
+    ///
+    /// * `Option<u16>` - The marker ID, or `None` if there are no bookmarks or all IDs are in use
+    pub fn anchor(&mut self, names: &[String]) -> Option<u16> {
+        if names.is_empty() {
+            return None;
+        }
+        let id = self.allocate()?;
+        for name in names {
+            self.anchors.insert(name.clone(), id);
+        }
+        Some(id)
+    }
+
+    /// Returns a marker ID for a heading, adding it to the document outline
+    ///
+    /// The heading can be linked to by the anchor name returned by `outline_anchor`
+    /// for the index of its outline entry, besides its own bookmarks.
+    ///
+    /// # Arguments
+    ///
+    /// * `title` - Title of the outline entry
+    /// * `level` - One-based nesting level of the entry
+    /// * `names` - The bookmark names of the heading paragraph
+    ///
+    /// # Returns
+    ///
+    /// * `Option<u16>` - The marker ID, or `None` if all IDs are in use
+    pub fn heading(&mut self, title: &str, level: usize, names: &[String]) -> Option<u16> {
+        let title = title.trim();
+        if title.is_empty() {
+            return self.anchor(names);
+        }
+
+        let id = self.allocate();
+        if let Some(id) = id {
+            for name in names.iter().cloned().chain([outline_anchor(self.outline.len())]) {
+                self.anchors.insert(name, id);
+            }
+        }
+        self.outline.push(OutlineEntry { title: title.to_string(), level: level.max(1), id });
+        id
+    }
+
+    /// Returns `true` if no markers were allocated
+    pub fn is_empty(&self) -> bool {
+        self.next_id == 0
+    }
+
+    /// Allocates the next free marker ID
+    fn allocate(&mut self) -> Option<u16> {
+        if self.next_id > MAX_MARKER_ID {
+            warn!("Too many links in document; remaining links are not clickable");
+            return None;
+        }
+        self.next_id += 1;
+        Some(self.next_id - 1)
+    }
+}
+
+/// A position marker found in a page content stream
+#[derive(Debug, Clone, Copy)]
+struct Marker {
+    /// Page the marker was drawn on
+    page: ObjectId,
+    /// Horizontal position in points from the left edge
+    x: f64,
+    /// Vertical position in points from the bottom edge
+    y: f64,
+}
+
+/// Returns the anchor name of an outline entry, for linking to its heading
+///
+/// # Arguments
+///
+/// * `index` - Index of the entry in the order the headings were added
+///
+/// # Returns
+///
+/// * `String` - The anchor name
+pub fn outline_anchor(index: usize) -> String {
+    format!("{}{}", OUTLINE_ANCHOR_PREFIX, index)
+}
+
+/// Returns the page number of each outline entry of a rendered PDF
+///
+/// # Arguments
+///
+/// * `data` - The rendered PDF
+/// * `links` - The link map used while building the document
+///
+/// # Returns
+///
+/// * `Result<Vec<Option<usize>>>` - The one-based page number of each entry, `None` if its heading was not found
+pub fn outline_pages(data: &[u8], links: &LinkMap) -> Result<Vec<Option<usize>>> {
+    let mut document = Document::load_mem(data)
+        .context("Failed to load PDF to find the pages of its headings")?;
+    let markers = take_markers(&mut document)?;
+    let page_numbers: HashMap<ObjectId, usize> = document.get_pages().into_iter()
+        .map(|(number, page)| (page, number as usize))
+        .collect();
+
+    Ok(links.outline.iter()
+        .map(|entry| {
+            let marker = markers.get(&entry.id?)?.first()?;
+            page_numbers.get(&marker.page).copied()
+        })
+        .collect())
+}
+
+/// Adds link annotations, the document outline and the document properties to a rendered PDF
+///
+/// The fonts are subset to the glyphs shown and the streams are compressed; only archival
+/// files fail if this is not possible.
+///
+/// # Arguments
+///
+/// * `path` - Path to the PDF file, which is rewritten in place
+/// * `links` - The link map used while building the document
+/// * `properties` - The title, author and other properties of the document
+/// * `now` - Time of the conversion, for the metadata date and missing dates
+/// * `archival` - Whether the file is converted to PDF/A-2b and checked for conformance
+///
+/// # Returns
+///
+/// * `Result<()>` - Success or an error, including a file that cannot be made to conform
+pub fn finish(path: &Path, links: &LinkMap, properties: &DocumentProperties, now: Timestamp, archival: bool) -> Result<()> {
+    let mut document = Document::load(path)
+        .context(format!("Failed to load PDF for post-processing: {}", path.display()))?;
+
+    if !links.is_empty() {
+        add_navigation(&mut document, links)?;
+    }
+    set_metadata(&mut document, properties, now, archival)?;
+    if archival {
+        pdf_archive::convert(&mut document)?;
+        pdf_archive::compact(&mut document)?;
+    } else {
+        // A file with full fonts is still usable, unlike an archival file that cannot conform,
+        // so fonts that cannot be subset are kept whole rather than failing the conversion
+        let mut compacted = document.clone();
+        match pdf_archive::compact(&mut compacted) {
+            Ok(()) => document = compacted,
+            Err(err) => warn!("Failed to subset the fonts of {}; keeping full fonts: {:#}", path.display(), err),
+        }
+    }
+
+    // Drop content streams, fonts and metadata replaced while post-processing
+    document.prune_objects();
+
+    if archival {
+        pdf_archive::save(&mut document, path)?;
+        return pdf_archive::verify(path);
+    }
+    document.save(path)
+        .context(format!("Failed to write post-processed PDF: {}", path.display()))?;
+
+    Ok(())
+}
+
+/// Adds link annotations and the document outline, removing the position markers
+///
+/// # Arguments
+///
+/// * `document` - The PDF document
+/// * `links` - The link map used while building the document
+///
+/// # Returns
+///
+/// * `Result<()>` - Success or an error
+fn add_navigation(document: &mut Document, links: &LinkMap) -> Result<()> {
+    let markers = take_markers(document)?;
+
+    // Resolve each bookmark to the position of its paragraph
+    let destinations: HashMap<&str, Marker> = links.anchors.iter()
+        .filter_map(|(name, id)| Some((name.as_str(), *markers.get(id)?.first()?)))
+        .collect();
+
+    let mut annotation_count = 0;
+    for (target, id) in &links.links {
+        let action = match target {
+            LinkTarget::Url(url) => ("A", Object::Dictionary(dictionary! {
+                "S" => "URI",
+                "URI" => Object::string_literal(url.as_str()),
+            })),
+            LinkTarget::Anchor(anchor) => match destinations.get(anchor.as_str()) {
+                Some(destination) => ("Dest", Object::Array(vec![
+                    destination.page.into(),
+                    "XYZ".into(),
+                    destination.x.into(),
+                    destination.y.into(),
+                    Object::Null,
+                ])),
+                None => {
+                    warn!("Link target not found in document: #{}", anchor);
+                    continue;
+                }
+            },
+        };
+
+        // Markers come in pairs marking opposite corners of each linked text segment
+        for corners in markers.get(id).map(Vec::as_slice).unwrap_or_default().chunks_exact(2) {
+            let (start, end) = (corners[0], corners[1]);
+            let annotation = document.add_object(dictionary! {
+                "Type" => "Annot",
+                "Subtype" => "Link",
+                "Rect" => vec![
+                    start.x.min(end.x).into(),
+                    start.y.min(end.y).into(),
+                    start.x.max(end.x).into(),
+                    start.y.max(end.y).into(),
+                ],
+                "Border" => vec![0.into(), 0.into(), 0.into()],
+                action.0 => action.1.clone(),
+            });
+            add_annotation(document, start.page, annotation)?;
+            annotation_count += 1;
+        }
+    }
+    debug!("Added {} link annotations", annotation_count);
+
+    add_outline(document, &links.outline, &markers)
+}
+
+/// Writes the document properties to the information dictionary and the XMP metadata
+///
+/// The XMP metadata written by genpdf only holds the title, so it is replaced. Dates
+/// missing from the properties are set to the time of the conversion.
+///
+/// # Arguments
+///
+/// * `document` - The PDF document
+/// * `properties` - The title, author and other properties of the document
+/// * `now` - Time of the conversion
+/// * `archival` - Whether the metadata identifies the file as PDF/A
+///
+/// # Returns
+///
+/// * `Result<()>` - Success or an error
+fn set_metadata(document: &mut Document, properties: &DocumentProperties, now: Timestamp, archival: bool) -> Result<()> {
+    // Dates that cannot be read are replaced by the time of the conversion, like missing dates
+    let date = |value: &Option<String>| {
+        let timestamp = value.as_deref().and_then(Timestamp::parse).unwrap_or(now);
+        (timestamp.to_pdf(), timestamp.to_w3c())
+    };
+    let (created_pdf, created_xmp) = date(&properties.created);
+    let (modified_pdf, modified_xmp) = date(&properties.modified);
+    let producer = format!("{} {}", PRODUCER, env!("CARGO_PKG_VERSION"));
+
+    let mut info = match document.trailer.get(b"Info").and_then(Object::as_reference) {
+        Ok(id) => document.get_dictionary(id).cloned().unwrap_or_default(),
+        Err(_) => lopdf::Dictionary::new(),
+    };
+    for (key, value) in [
+        ("Title", &properties.title),
+        ("Author", &properties.author),
+        ("Subject", &properties.subject),
+        ("Keywords", &properties.keywords),
+        ("Company", &properties.company),
+    ] {
+        match value {
+            Some(value) => info.set(key, text_string(value)),
+            None => { info.remove(key.as_bytes()); },
+        }
+    }
+    info.set("Producer", text_string(&producer));
+    info.set("CreationDate", Object::string_literal(created_pdf));
+    info.set("ModDate", Object::string_literal(modified_pdf));
+    // genpdf claims PDF/X conformance only in the metadata that is replaced
+    info.remove(b"GTS_PDFXVersion");
+    let info = document.add_object(info);
+    document.trailer.set("Info", info);
+
+    let xmp = xmp_packet(properties, &producer, &created_xmp, &modified_xmp, &now.to_w3c(), archival);
+    let stream = lopdf::Stream::new(dictionary! { "Type" => "Metadata", "Subtype" => "XML" }, xmp.into_bytes());
+    let stream = document.add_object(stream);
+    catalog_mut(document)?.set("Metadata", stream);
+
+    Ok(())
+}
+
+/// Builds an XMP packet with the document properties
+///
+/// # Arguments
+///
+/// * `properties` - The title, author and other properties of the document
+/// * `producer` - Name and version of the producing application
+/// * `created` - Creation date as a W3C date
+/// * `modified` - Modification date as a W3C date
+/// * `metadata_date` - Date the metadata was written, as a W3C date
+/// * `archival` - Whether the packet identifies the file as PDF/A
+///
+/// # Returns
+///
+/// * `String` - The XMP packet
+fn xmp_packet(
+    properties: &DocumentProperties,
+    producer: &str,
+    created: &str,
+    modified: &str,
+    metadata_date: &str,
+    archival: bool,
+) -> String {
+    let mut fields = String::new();
+    let mut field = |name: &str, value: String| fields.push_str(&format!("      <{0}>{1}</{0}>\n", name, value));
+
+    field("dc:format", "application/pdf".to_string());
+    let alternative = |text: &str| format!("<rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt>", escape(text));
+    if let Some(title) = &properties.title {
+        field("dc:title", alternative(title));
+    }
+    if let Some(author) = &properties.author {
+        field("dc:creator", format!("<rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq>", escape(author)));
+    }
+    if let Some(subject) = &properties.subject {
+        field("dc:description", alternative(subject));
+    }
+    if let Some(company) = &properties.company {
+        field("dc:publisher", format!("<rdf:Bag><rdf:li>{}</rdf:li></rdf:Bag>", escape(company)));
+    }
+    if let Some(keywords) = &properties.keywords {
+        field("pdf:Keywords", escape(keywords).to_string());
+    }
+    field("pdf:Producer", escape(producer).to_string());
+    field("xmp:CreateDate", created.to_string());
+    field("xmp:ModifyDate", modified.to_string());
+    field("xmp:MetadataDate", metadata_date.to_string());
+    if archival {
+        field("pdfaid:part", pdf_archive::PDF_A_PART.to_string());
+        field("pdfaid:conformance", pdf_archive::PDF_A_CONFORMANCE.to_string());
+    }
+
+    format!(concat!(
+        "<?xpacket begin=\"{}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n",
+        "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n",
+        "  <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n",
+        "    <rdf:Description rdf:about=\"\"\n",
+        "        xmlns:dc=\"http://purl.org/dc/elements/1.1/\"\n",
+        "        xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\"\n",
+        "        xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"{}>\n",
+        "{}",
+        "    </rdf:Description>\n",
+        "  </rdf:RDF>\n",
+        "</x:xmpmeta>\n",
+        "<?xpacket end=\"w\"?>",
+    ), '\u{feff}', if archival { "\n        xmlns:pdfaid=\"http://www.aiim.org/pdfa/ns/id/\"" } else { "" }, fields)
+}
+
+/// Returns the catalog dictionary of a document for modification
+pub(crate) fn catalog_mut(document: &mut Document) -> Result<&mut lopdf::Dictionary> {
+    let catalog = document.trailer.get(b"Root")
+        .and_then(Object::as_reference)
+        .context("Failed to find document catalog")?;
+    document.get_object_mut(catalog)
+        .and_then(Object::as_dict_mut)
+        .context("Failed to find document catalog")
+}
+
+/// Adds an outline tree of the headings to the document catalog
+///
+/// Entries are nested below the closest preceding entry of a lower level, and the
+/// viewer is asked to show the outline when the document is opened.
+///
+/// # Arguments
+///
+/// * `document` - The PDF document
+/// * `entries` - The outline entries in document order
+/// * `markers` - Marker positions by ID
+///
+/// # Returns
+///
+/// * `Result<()>` - Success or an error
+fn add_outline(document: &mut Document, entries: &[OutlineEntry], markers: &HashMap<u16, Vec<Marker>>) -> Result<()> {
+    // Entries whose heading was not drawn are left out
+    let entries: Vec<(&OutlineEntry, Marker)> = entries.iter()
+        .filter_map(|entry| Some((entry, *markers.get(&entry.id?)?.first()?)))
+        .collect();
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let root = document.new_object_id();
+    let ids: Vec<ObjectId> = entries.iter().map(|_| document.new_object_id()).collect();
+
+    // Find the parent of each entry, and the children of each entry and of the root
+    let mut parents: Vec<Option<usize>> = Vec::with_capacity(entries.len());
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); entries.len()];
+    let mut top_level = Vec::new();
+    let mut open: Vec<usize> = Vec::new();
+    for (index, (entry, _)) in entries.iter().enumerate() {
+        while open.last().is_some_and(|&last| entries[last].0.level >= entry.level) {
+            open.pop();
+        }
+        match open.last() {
+            Some(&parent) => children[parent].push(index),
+            None => top_level.push(index),
+        }
+        parents.push(open.last().copied());
+        open.push(index);
+    }
+
+    // Entries are shown expanded, so each counts all of its descendants
+    let mut descendants = vec![0i64; entries.len()];
+    for index in (0..entries.len()).rev() {
+        descendants[index] = children[index].iter().map(|&child| 1 + descendants[child]).sum();
+    }
+
+    for (index, (entry, marker)) in entries.iter().enumerate() {
+        let siblings = match parents[index] {
+            Some(parent) => &children[parent],
+            None => &top_level,
+        };
+        let position = siblings.iter().position(|&sibling| sibling == index).unwrap_or_default();
+
+        let mut item = dictionary! {
+            "Title" => text_string(&entry.title),
+            "Parent" => parents[index].map_or(root, |parent| ids[parent]),
+            "Dest" => vec![marker.page.into(), "XYZ".into(), marker.x.into(), marker.y.into(), Object::Null],
+        };
+        if position > 0 {
+            item.set("Prev", ids[siblings[position - 1]]);
+        }
+        if let Some(&next) = siblings.get(position + 1) {
+            item.set("Next", ids[next]);
+        }
+        if let (Some(&first), Some(&last)) = (children[index].first(), children[index].last()) {
+            item.set("First", ids[first]);
+            item.set("Last", ids[last]);
+            item.set("Count", descendants[index]);
+        }
+        document.objects.insert(ids[index], Object::Dictionary(item));
+    }
+
+    document.objects.insert(root, Object::Dictionary(dictionary! {
+        "Type" => "Outlines",
+        "First" => ids[top_level[0]],
+        "Last" => ids[top_level[top_level.len() - 1]],
+        "Count" => entries.len() as i64,
+    }));
+
+    let catalog = catalog_mut(document)?;
+    catalog.set("Outlines", root);
+    catalog.set("PageMode", "UseOutlines");
+    debug!("Added {} outline entries", entries.len());
+
+    Ok(())
+}
+
+/// Encodes a PDF text string, using UTF-16 for text outside ASCII
+fn text_string(text: &str) -> Object {
+    if text.is_ascii() {
+        return Object::string_literal(text);
+    }
+    let mut bytes = vec![0xFE, 0xFF];
+    bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+    Object::String(bytes, lopdf::StringFormat::Hexadecimal)
+}
+
+/// Decodes a PDF text string, written in UTF-16BE with a byte order mark or in PDFDocEncoding
+///
+/// Characters of PDFDocEncoding outside Latin-1 are read as their Latin-1 equivalents.
+pub(crate) fn decode_text_string(bytes: &[u8]) -> String {
+    match bytes.strip_prefix(&[0xFE, 0xFF]) {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+            String::from_utf16_lossy(&units)
+        },
+        None => bytes.iter().map(|&byte| byte as char).collect(),
+    }
+}
+
+/// Collects the position markers of all pages and removes them from the content streams
+///
+/// # Arguments
+///
+/// * `document` - The PDF document
+///
+/// # Returns
+///
+/// * `Result<HashMap<u16, Vec<Marker>>>` - Marker positions by ID, in drawing order
+fn take_markers(document: &mut Document) -> Result<HashMap<u16, Vec<Marker>>> {
+    let mut markers: HashMap<u16, Vec<Marker>> = HashMap::new();
+
+    for page in document.get_pages().into_values() {
+        let data = document.get_page_content(page)
+            .context("Failed to read page content")?;
+        let content = match Content::decode(&data) {
+            Ok(content) => content,
+            Err(err) => {
+                warn!("Skipping page with unreadable content: {}", err);
+                continue;
+            }
+        };
+
+        let mut operations = Vec::with_capacity(content.operations.len());
+        // Operations of a path stroked in a marker color, held back until the path ends
+        let mut held: Vec<Operation> = Vec::new();
+        let mut current: Option<u16> = None;
+        for operation in content.operations {
+            if current.is_none() {
+                current = (operation.operator == "RG").then(|| marker_id(&operation)).flatten();
+                if current.is_none() {
+                    operations.push(operation);
+                    continue;
+                }
+            }
+
+            let ends_path = operation.operator == "S";
+            held.push(operation);
+            if let Some(id) = current.filter(|_| ends_path) {
+                current = None;
+                // Only zero-length lines are markers; anything else is put back
+                match marker_position(&held) {
+                    Some((x, y)) => {
+                        markers.entry(id).or_default().push(Marker { page, x, y });
+                        held.clear();
+                    },
+                    None => operations.append(&mut held),
+                }
+            }
+        }
+        operations.append(&mut held);
+
+        let content = Content { operations }.encode()
+            .context("Failed to encode page content")?;
+        document.change_page_content(page, content)
+            .context("Failed to update page content")?;
+    }
+
+    Ok(markers)
+}
+
+/// Returns the marker ID encoded in a stroke color operation, if it is a marker color
+fn marker_id(operation: &Operation) -> Option<u16> {
+    match operation.operands.as_slice() {
+        [red, green, blue] => decode_marker_color([number(red)?, number(green)?, number(blue)?]),
+        _ => None,
+    }
+}
+
+/// Returns the position of a marker path (color, move, line to the same point, stroke)
+fn marker_position(path: &[Operation]) -> Option<(f64, f64)> {
+    let [_, start, end, _] = path else {
+        return None;
+    };
+    let point = |operation: &Operation| match operation.operands.as_slice() {
+        [x, y] => Some((number(x)?, number(y)?)),
+        _ => None,
+    };
+
+    match (start.operator.as_str(), end.operator.as_str()) {
+        ("m", "l") if point(start)? == point(end)? => point(start),
+        _ => None,
+    }
+}
+
+/// Reads a numeric PDF object
+fn number(object: &Object) -> Option<f64> {
+    match object {
+        Object::Integer(value) => Some(*value as f64),
+        Object::Real(value) => Some(*value),
+        _ => None,
+    }
+}
+
+/// Appends an annotation to the annotation array of a page
+fn add_annotation(document: &mut Document, page: ObjectId, annotation: ObjectId) -> Result<()> {
+    let page = document.get_object_mut(page)
+        .and_then(Object::as_dict_mut)
+        .context("Failed to find page for annotation")?;
+
+    match page.get_mut(b"Annots").and_then(Object::as_array_mut) {
+        Ok(annotations) => annotations.push(annotation.into()),
+        Err(_) => page.set("Annots", vec![Object::from(annotation)]),
+    }
+
+    Ok(())
+}
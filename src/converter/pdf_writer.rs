@@ -3,89 +3,227 @@
 use std::path::{Path, PathBuf};
 use anyhow::{Result, Context};
 use genpdf::{elements, fonts, style, Element};
+use image::GenericImageView;
 use log::{info, debug, warn};
 
-use crate::converter::docx_reader::DocxContent;
+use crate::converter::docx_reader::{Block, DocxContent, Image, LinkTarget, Paragraph, VerticalAlign, PAGE_COUNT_PLACEHOLDER, PAGE_NUMBER_PLACEHOLDER};
+use crate::converter::font_loader;
+use crate::converter::metadata::{DocumentProperties, Timestamp};
+use crate::converter::number_format::Locale;
+use crate::converter::options::{ConversionOptions, FormulaDisplay, Orientation, PageMargins, PageSetup, PaperSize, PdfOptions};
+use crate::converter::pdf_elements::{FittedImage, GridCell, GridTable, HeaderFooterDecorator, RichParagraph, Span};
+use crate::converter::pdf_postprocess::{self, LinkMap};
+use crate::converter::table::{self, Table};
+use crate::converter::sheet_layout;
 use crate::converter::xlsx_reader::Sheet;
 
-/// Default font to use in generated PDFs
-const DEFAULT_FONT_NAME: &str = "Roboto";
+/// Default font of PDF output, compiled into the binary
+#[cfg(feature = "bundled-font")]
+const BUNDLED_FONT_NAME: &str = "DejaVu Sans";
+
+/// Regular, bold, italic and bold italic variants of the bundled font
+#[cfg(feature = "bundled-font")]
+const BUNDLED_FONT_DATA: [&[u8]; 4] = [
+    include_bytes!("../../fonts/DejaVuSans/DejaVuSans.ttf"),
+    include_bytes!("../../fonts/DejaVuSans/DejaVuSans-Bold.ttf"),
+    include_bytes!("../../fonts/DejaVuSans/DejaVuSans-Oblique.ttf"),
+    include_bytes!("../../fonts/DejaVuSans/DejaVuSans-BoldOblique.ttf"),
+];
+
+/// Monospace font compiled into the binary, used for code runs
+#[cfg(feature = "bundled-font")]
+const BUNDLED_MONOSPACE_FONT_NAME: &str = "DejaVu Sans Mono";
+
+/// Regular, bold, italic and bold italic variants of the bundled monospace font
+#[cfg(feature = "bundled-font")]
+const BUNDLED_MONOSPACE_FONT_DATA: [&[u8]; 4] = [
+    include_bytes!("../../fonts/DejaVuSansMono/DejaVuSansMono.ttf"),
+    include_bytes!("../../fonts/DejaVuSansMono/DejaVuSansMono-Bold.ttf"),
+    include_bytes!("../../fonts/DejaVuSansMono/DejaVuSansMono-Oblique.ttf"),
+    include_bytes!("../../fonts/DejaVuSansMono/DejaVuSansMono-BoldOblique.ttf"),
+];
+
+/// Page margins in millimetres
+const PAGE_MARGIN: f64 = 20.0;
+
+/// Font size of body text in points, unless set in the options
+const DEFAULT_FONT_SIZE: u8 = 12;
+
+/// Font sizes for heading levels 1 to 3 at the default body font size; deeper levels use the last entry
+const HEADING_FONT_SIZES: [u8; 3] = [20, 16, 14];
+
+/// Heading of the section listing footnotes and endnotes
+const NOTES_HEADING: &str = "Notes";
+
+/// Font size of footnote and endnote text at the default body font size
+const NOTE_FONT_SIZE: u8 = 10;
+
+/// Heading of the table of contents
+const CONTENTS_HEADING: &str = "Contents";
+
+/// Relative widths of the title and page number columns of the table of contents
+const TOC_COLUMN_WEIGHTS: [usize; 2] = [12, 1];
+
+/// Indentation in millimetres of table of contents entries per heading level
+const TOC_INDENT: f64 = 5.0;
+
+/// Heading of the per-sheet list of formulas
+const FORMULAS_HEADING: &str = "Formulas";
+
+/// Text color of hyperlinks, matching Word's Hyperlink style
+const LINK_COLOR: style::Color = style::Color::Rgb(5, 99, 193);
 
 /// Creates a PDF file from Word document content
 ///
 /// # Arguments
 ///
 /// * `content` - The extracted content from a Word document
+/// * `properties` - The title, author and other properties of the document
 /// * `input_path` - Path to the original Word document
 /// * `output_dir` - Directory where the PDF will be saved
+/// * `options` - Options controlling the PDF output
 ///
 /// # Returns
 ///
 /// * `Result<PathBuf>` - Path to the generated PDF file or an error
 pub fn create_pdf_from_docx(
     content: &DocxContent,
+    properties: &DocumentProperties,
     input_path: &Path,
     output_dir: &Path,
+    options: &ConversionOptions,
 ) -> Result<PathBuf> {
     let output_filename = generate_output_filename(input_path, output_dir)?;
     info!("Creating PDF from Word document: {}", output_filename.display());
 
-    // Load default font
-    let font_family = load_default_font()?;
+    let font_family = load_font(options)?;
+    let title = document_title(properties, input_path, "Converted Document");
+    // The header date and the metadata dates all use the time the conversion started
+    let now = Timestamp::now();
 
-    // Create PDF document
-    let mut doc = genpdf::Document::new(font_family);
-    doc.set_title(input_path.file_stem()
-        .map(|s| s.to_string_lossy().to_string())
-        .unwrap_or_else(|| "Converted Document".to_string()));
+    // Pages follow the document's page setup unless the options override it
+    let setup = page_setup(&options.pdf, content.page_setup.as_ref(), false);
+    setup.validate()?;
 
-    // Set default margins
-    let mut decorator = genpdf::SimplePageDecorator::new();
-    decorator.set_margins(20);
-    doc.set_page_decorator(decorator);
+    // Repeat the document's header and footer on every page, unless templates replace them
+    let decorator = page_decorator(&options.pdf, &setup, &content.header, &content.footer, &title, input_path, now);
 
-    // Add paragraphs
-    for paragraph in &content.paragraphs {
-        doc.push(elements::Paragraph::new(paragraph));
-        doc.push(elements::Break::new(1));
-    }
+    // The headings are listed with their pages in the table of contents and the outline
+    let headings: Vec<(usize, String)> = content.blocks.iter()
+        .filter_map(|block| match block {
+            Block::Paragraph(paragraph) => Some((paragraph.heading_level()?, heading_title(paragraph))),
+            _ => None,
+        })
+        .filter(|(_, title)| !title.trim().is_empty())
+        .collect();
+    let font_size = options.pdf.font_size.unwrap_or(DEFAULT_FONT_SIZE);
+
+    let links = render_pdf(&output_filename, decorator, options.pdf.table_of_contents, |decorator, pages| {
+        let mut doc = genpdf::Document::new(font_family.clone());
+        doc.set_title(&title);
+        set_text_layout(&mut doc, &options.pdf, &setup);
+        doc.set_page_decorator(decorator);
+        let mut links = LinkMap::new();
+        if options.pdf.table_of_contents {
+            push_table_of_contents(&mut doc, &headings, pages, font_size, &mut links);
+        }
+        push_docx_content(&mut doc, content, options, &setup, &mut links);
+        (doc, links)
+    })?;
+
+    // Make hyperlinks clickable, add the outline of the headings and the document properties
+    let properties = DocumentProperties { title: Some(title), ..properties.clone() };
+    pdf_postprocess::finish(&output_filename, &links, &properties, now, options.pdf.pdf_a)
+        .context(format!("Failed to post-process PDF file: {}", output_filename.display()))?;
 
-    // Add tables
-    for table_data in &content.tables {
-        if !table_data.is_empty() {
-            // Determine column widths based on the first row
-            if let Some(first_row) = table_data.first() {
-                let col_count = first_row.len();
-                let widths = vec![1; col_count]; // Equal width for all columns
-                let mut table = elements::TableLayout::new(widths);
-
-                // Add table data
-                for row in table_data {
-                    // Create a vector of cell elements first
-                    let cell_elements: Vec<elements::Paragraph> = row.iter()
-                        .map(|cell| elements::Paragraph::new(cell))
-                        .collect();
-
-                    // Then add them to a row
-                    let mut table_row = table.row();
-                    for cell_element in cell_elements {
-                        table_row = table_row.element(cell_element);
+    info!("Successfully created PDF: {}", output_filename.display());
+    Ok(output_filename)
+}
+
+/// Adds the blocks and notes of a Word document to a PDF document
+///
+/// # Arguments
+///
+/// * `doc` - The document to add the content to
+/// * `content` - The extracted content from a Word document
+/// * `options` - Options controlling the PDF output
+/// * `setup` - The resolved page size and margins
+/// * `links` - Marker IDs for hyperlinks, bookmarks and headings
+fn push_docx_content(
+    doc: &mut genpdf::Document,
+    content: &DocxContent,
+    options: &ConversionOptions,
+    setup: &PageSetup,
+    links: &mut LinkMap,
+) {
+    let font_size = options.pdf.font_size.unwrap_or(DEFAULT_FONT_SIZE);
+
+    // Monospace text falls back to the default font when no monospace font is available;
+    // the font is only added when needed, as every font added is embedded in the PDF
+    let has_code = content.paragraphs().any(|paragraph| paragraph.runs.iter().any(|run| run.format.monospace));
+    let monospace = has_code
+        .then(load_monospace_font)
+        .flatten()
+        .map(|family| doc.add_font_family(family));
+
+    // Add paragraphs and tables in document order
+    let mut blocks = content.blocks.iter().peekable();
+    while let Some(block) = blocks.next() {
+        match block {
+            Block::Paragraph(paragraph) if paragraph.list.is_some() && paragraph.heading_level().is_none() => {
+                // Consecutive list paragraphs are rendered as one (possibly nested) list
+                let mut items = vec![paragraph];
+                while let Some(Block::Paragraph(next)) = blocks.peek() {
+                    if next.list.is_none() || next.heading_level().is_some() {
+                        break;
                     }
-                    table_row.push().context("Failed to add row to table in docx conversion")?;
+                    items.push(next);
+                    blocks.next();
                 }
-
-                doc.push(table);
+                doc.push(build_list(&items, monospace, links));
                 doc.push(elements::Break::new(1));
+            },
+            Block::Paragraph(paragraph) => {
+                let rich_paragraph = build_rich_paragraph(paragraph, monospace, links);
+                match paragraph.heading_level() {
+                    Some(level) => {
+                        let heading_style = style::Style::new()
+                            .bold()
+                            .with_font_size(heading_font_size(level, font_size));
+                        doc.push(rich_paragraph.styled(heading_style));
+                    },
+                    None => doc.push(rich_paragraph),
+                }
+                doc.push(elements::Break::new(1));
+            },
+            Block::Table(table_data) => {
+                if let Some(table) = build_table(table_data) {
+                    doc.push(table);
+                    doc.push(elements::Break::new(1));
+                }
+            },
+            Block::Image(image) => {
+                // An image that cannot be embedded should not prevent the rest of the document
+                match build_image(image, setup.content_height()) {
+                    Ok(element) => {
+                        doc.push(element);
+                        doc.push(elements::Break::new(1));
+                    },
+                    Err(err) => warn!("Skipping image {}: {:#}", image.name, err),
+                }
             }
         }
     }
 
-    // Generate PDF
-    doc.render_to_file(&output_filename)
-        .context(format!("Failed to generate PDF file: {}", output_filename.display()))?;
-
-    info!("Successfully created PDF: {}", output_filename.display());
-    Ok(output_filename)
+    // Footnotes and endnotes are collected at the end of the document
+    if !content.notes.is_empty() {
+        doc.push(elements::Paragraph::new(NOTES_HEADING).styled(style::Style::new().bold()));
+        doc.push(elements::Break::new(0.5));
+        for note in &content.notes {
+            let text = format!("{}. {}", note.label, note.paragraphs.join(" "));
+            doc.push(elements::Paragraph::new(text).styled(style::Style::new().with_font_size(scale_font_size(NOTE_FONT_SIZE, font_size))));
+        }
+    }
 }
 
 /// Creates a PDF file from Excel spreadsheet content
@@ -93,69 +231,147 @@ pub fn create_pdf_from_docx(
 /// # Arguments
 ///
 /// * `sheets` - The extracted sheets from an Excel workbook
+/// * `properties` - The title, author and other properties of the workbook
 /// * `input_path` - Path to the original Excel file
 /// * `output_dir` - Directory where the PDF will be saved
+/// * `options` - Options controlling how the sheets are shown
 ///
 /// # Returns
 ///
 /// * `Result<PathBuf>` - Path to the generated PDF file or an error
 pub fn create_pdf_from_xlsx(
     sheets: &[Sheet],
+    properties: &DocumentProperties,
     input_path: &Path,
     output_dir: &Path,
+    options: &ConversionOptions,
 ) -> Result<PathBuf> {
     let output_filename = generate_output_filename(input_path, output_dir)?;
     info!("Creating PDF from Excel spreadsheet: {}", output_filename.display());
 
-    // Load default font
-    let font_family = load_default_font()?;
+    let font_family = load_font(options)?;
+    let title = document_title(properties, input_path, "Converted Spreadsheet");
+    // The header date and the metadata dates all use the time the conversion started
+    let now = Timestamp::now();
+
+    // Cells are shown with their number formats, using the separators of the chosen or the user's locale
+    let locale = options.locale();
+    let font_size = options.pdf.font_size.unwrap_or(sheet_layout::DEFAULT_TABLE_FONT_SIZE);
+
+    // Sheets too wide for a portrait page are printed in landscape orientation, unless it is set.
+    // All pages share one orientation, so narrow sheets keep a workbook on portrait pages.
+    let portrait = page_setup(&options.pdf, None, false);
+    let is_wide = |sheet: &Sheet| is_wide_sheet(sheet, options, &locale, font_size, portrait.content_width());
+    let wide_sheets = sheets.iter()
+        .filter(|sheet| (!options.tables_only && is_wide(sheet)) || sheet.tables.iter().any(|table| is_wide(&table.cells)))
+        .count();
+    let landscape = options.pdf.orientation.is_none() && wide_sheets > 0 && wide_sheets == sheets.len();
+    if landscape {
+        info!("Using landscape pages for wide sheets");
+    } else if options.pdf.orientation.is_none() && wide_sheets > 0 {
+        info!("Using portrait pages, as {} of {} sheets fit them; wide sheets are shrunk or split into column groups",
+              sheets.len() - wide_sheets, sheets.len());
+    }
+    let setup = page_setup(&options.pdf, None, landscape);
+    setup.validate()?;
+
+    // Spreadsheets only have the header and footer set in the options
+    let decorator = page_decorator(&options.pdf, &setup, &[], &[], &title, input_path, now);
+
+    // Each sheet has an entry in the table of contents and the outline
+    let sheet_names: Vec<(usize, String)> = sheets.iter().map(|sheet| (1, sheet.name.clone())).collect();
 
-    // Create PDF document
-    let mut doc = genpdf::Document::new(font_family);
-    doc.set_title(input_path.file_stem()
-        .map(|s| s.to_string_lossy().to_string())
-        .unwrap_or_else(|| "Converted Spreadsheet".to_string()));
+    let links = render_pdf(&output_filename, decorator, options.pdf.table_of_contents, |decorator, pages| {
+        let mut doc = genpdf::Document::new(font_family.clone());
+        doc.set_title(&title);
+        set_text_layout(&mut doc, &options.pdf, &setup);
+        doc.set_page_decorator(decorator);
+        let mut links = LinkMap::new();
+        if options.pdf.table_of_contents {
+            push_table_of_contents(&mut doc, &sheet_names, pages, font_size, &mut links);
+        }
+        push_sheets(&mut doc, sheets, options, &locale, font_size, setup.content_width(), &mut links);
+        (doc, links)
+    })?;
+
+    // Add a bookmark for each sheet and the document properties
+    let properties = DocumentProperties { title: Some(title), ..properties.clone() };
+    pdf_postprocess::finish(&output_filename, &links, &properties, now, options.pdf.pdf_a)
+        .context(format!("Failed to post-process PDF file: {}", output_filename.display()))?;
 
-    // Set default margins
-    let mut decorator = genpdf::SimplePageDecorator::new();
-    decorator.set_margins(20);
-    doc.set_page_decorator(decorator);
+    info!("Successfully created PDF: {}", output_filename.display());
+    Ok(output_filename)
+}
 
+/// Adds sheets to a PDF document, each starting on a new page
+///
+/// # Arguments
+///
+/// * `doc` - The document to add the sheets to
+/// * `sheets` - The extracted sheets from an Excel workbook
+/// * `options` - Options controlling how the sheets are shown
+/// * `locale` - Locale used to format the cell values
+/// * `font_size` - Font size of tables that fit the page
+/// * `content_width` - Width of the page content area in millimetres
+/// * `links` - Marker IDs for the sheet headings
+fn push_sheets(
+    doc: &mut genpdf::Document,
+    sheets: &[Sheet],
+    options: &ConversionOptions,
+    locale: &Locale,
+    font_size: u8,
+    content_width: f64,
+    links: &mut LinkMap,
+) {
     // Process each sheet
     for (i, sheet) in sheets.iter().enumerate() {
-        // Add sheet name as heading
+        // Add sheet name as heading, with a bookmark to the sheet
         let sheet_title = format!("Sheet: {}", sheet.name);
-        let heading = elements::Paragraph::new(&sheet_title)
-            .styled(style::Style::new().bold());
-        doc.push(heading);
+        let mut heading = RichParagraph::new().span(Span::new(sheet_title, style::Style::new()));
+        if let Some(anchor) = links.heading(&sheet.name, 1, &[]) {
+            heading = heading.with_anchor(anchor);
+        }
+        doc.push(heading.styled(style::Style::new().bold()));
         doc.push(elements::Break::new(1));
 
-        if !sheet.data.is_empty() {
-            // Determine column widths based on the first row
-            let col_count = sheet.data.first().map_or(0, |row| row.len());
-            if col_count > 0 {
-                let widths = vec![1; col_count]; // Equal width for all columns
-                let mut table = elements::TableLayout::new(widths);
-
-                // Add table data
-                for row in &sheet.data {
-                    // Create a vector of cell elements first
-                    let cell_elements: Vec<elements::Paragraph> = row.iter()
-                        .map(|cell| elements::Paragraph::new(cell))
-                        .collect();
-
-                    // Then add them to a row
-                    let mut table_row = table.row();
-                    for cell_element in cell_elements {
-                        table_row = table_row.element(cell_element);
+        // Only the tables and named ranges of the sheet are shown when converting tables only
+        let mut formulas = Vec::new();
+        if !options.tables_only {
+            if !sheet.data.is_empty() {
+                push_sheet_cells(doc, sheet, options, locale, font_size, content_width);
+            } else {
+                doc.push(elements::Paragraph::new("(Empty sheet)"));
+            }
+            formulas.extend(sheet.formulas());
+        }
+
+        for (index, table) in sheet.tables.iter().enumerate() {
+            if index > 0 || !options.tables_only {
+                doc.push(elements::Break::new(1));
+            }
+            let title = format!("{}: {} ({})", table.kind, table.name, table.range);
+            doc.push(elements::Paragraph::new(title).styled(style::Style::new().bold()));
+            doc.push(elements::Break::new(1));
+            push_sheet_cells(doc, &table.cells, options, locale, font_size, content_width);
+            if options.tables_only {
+                for formula in table.cells.formulas() {
+                    if !formulas.contains(&formula) {
+                        formulas.push(formula);
                     }
-                    table_row.push().context("Failed to add row to table in xlsx conversion")?;
                 }
+            }
+        }
 
+        // List the formulas of the sheet after its data
+        if options.formulas == FormulaDisplay::Appendix && !formulas.is_empty() {
+            doc.push(elements::Break::new(1));
+            doc.push(elements::Paragraph::new(FORMULAS_HEADING).styled(style::Style::new().bold()));
+            doc.push(elements::Break::new(1));
+            let mut rows = vec![vec!["Cell".to_string(), "Formula".to_string()]];
+            rows.extend(formulas.into_iter().map(|(reference, formula)| vec![reference, formula]));
+            if let Some(table) = build_table(&Table { header_rows: 1, ..Table::from_rows(&rows) }) {
                 doc.push(table);
             }
-        } else {
-            doc.push(elements::Paragraph::new("(Empty sheet)"));
         }
 
         // Add page break between sheets (except for the last one)
@@ -163,95 +379,697 @@ pub fn create_pdf_from_xlsx(
             doc.push(elements::PageBreak::new());
         }
     }
+}
 
-    // Generate PDF
-    doc.render_to_file(&output_filename)
-        .context(format!("Failed to generate PDF file: {}", output_filename.display()))?;
+/// Builds a rich paragraph from the formatted runs of a Word paragraph
+///
+/// # Arguments
+///
+/// * `paragraph` - The paragraph to render
+/// * `monospace` - Font family for monospace runs, if one was loaded
+/// * `links` - Marker IDs for hyperlinks and bookmarks
+///
+/// # Returns
+///
+/// * `RichParagraph` - The paragraph element
+fn build_rich_paragraph(
+    paragraph: &Paragraph,
+    monospace: Option<fonts::FontFamily<fonts::Font>>,
+    links: &mut LinkMap,
+) -> RichParagraph {
+    let mut rich_paragraph = RichParagraph::new();
+    // Headings are added to the document outline
+    let anchor = match paragraph.heading_level() {
+        Some(level) => links.heading(&heading_title(paragraph), level, &paragraph.bookmarks),
+        None => links.anchor(&paragraph.bookmarks),
+    };
+    if let Some(anchor) = anchor {
+        rich_paragraph = rich_paragraph.with_anchor(anchor);
+    }
 
-    info!("Successfully created PDF: {}", output_filename.display());
-    Ok(output_filename)
+    // Numbered headings keep their number; list markers are drawn by the list elements
+    if let (Some(item), Some(_)) = (&paragraph.list, paragraph.heading_level()) {
+        rich_paragraph.push(Span::new(format!("{} ", item.marker), style::Style::new()));
+    }
+
+    for run in &paragraph.runs {
+        let mut run_style = style::Style::new();
+        if run.format.bold {
+            run_style.set_bold();
+        }
+        if run.format.italic {
+            run_style.set_italic();
+        }
+        if let (true, Some(family)) = (run.format.monospace, monospace) {
+            run_style.set_font_family(family);
+        }
+
+        let link = run.link.as_ref().and_then(|target| links.link(target));
+        if link.is_some() {
+            run_style.set_color(LINK_COLOR);
+        }
+
+        // Note references are shown as superscript labels
+        let vertical_align = match run.note {
+            Some(_) => VerticalAlign::Superscript,
+            None => run.format.vertical_align,
+        };
+
+        rich_paragraph.push(Span {
+            underline: run.format.underline || link.is_some(),
+            strike: run.format.strike,
+            vertical_align,
+            link,
+            ..Span::new(&run.text, run_style)
+        });
+    }
+
+    rich_paragraph
 }
 
-/// Loads the default font for PDF generation
+/// Returns the title of a heading as listed in the outline, including its number
+fn heading_title(paragraph: &Paragraph) -> String {
+    match &paragraph.list {
+        Some(item) => format!("{} {}", item.marker, paragraph.text()),
+        None => paragraph.text(),
+    }
+}
+
+/// Adds a table of contents, followed by a page break, to a document
+///
+/// Entries are linked to their headings, which must be added to the link map in
+/// the same order as the entries.
+///
+/// # Arguments
+///
+/// * `doc` - The document to add the table of contents to
+/// * `entries` - The level and title of each heading
+/// * `pages` - The page of each heading, once known from a first layout of the document
+/// * `font_size` - Font size of body text
+/// * `links` - Marker IDs for the links to the headings
+fn push_table_of_contents(
+    doc: &mut genpdf::Document,
+    entries: &[(usize, String)],
+    pages: Option<&[Option<usize>]>,
+    font_size: u8,
+    links: &mut LinkMap,
+) {
+    doc.push(elements::Paragraph::new(CONTENTS_HEADING)
+        .styled(style::Style::new().bold().with_font_size(heading_font_size(1, font_size))));
+    doc.push(elements::Break::new(1));
+
+    // Entries are indented by their level below the top level of the document
+    let top_level = entries.iter().map(|(level, _)| *level).min().unwrap_or(1);
+    let mut table = GridTable::new(2).with_column_weights(&TOC_COLUMN_WEIGHTS);
+    for (index, (level, title)) in entries.iter().enumerate() {
+        let page = pages
+            .and_then(|pages| pages.get(index).copied().flatten())
+            .map_or_else(|| "?".to_string(), |page| page.to_string());
+        let link = links.link(&LinkTarget::Anchor(pdf_postprocess::outline_anchor(index)));
+        let entry = RichParagraph::new()
+            .span(Span { link, ..Span::new(title, style::Style::new()) })
+            .padded(genpdf::Margins::trbl(0, 0, 0, TOC_INDENT * (level - top_level) as f64));
+        table.push_row(vec![
+            GridCell::new(entry, 0, 1),
+            GridCell::new(elements::Paragraph::new(page).aligned(genpdf::Alignment::Right), 1, 1),
+        ]);
+    }
+    doc.push(table);
+    doc.push(elements::PageBreak::new());
+}
+
+/// Builds a list element from consecutive list paragraphs
+///
+/// Items deeper than the first item are nested below the preceding shallower item.
+/// Plain "1." numbering and uniform bullets use genpdf's ordered and unordered lists;
+/// other markers (e.g. "a)" or "1.2.") are drawn as individual bullet points.
+///
+/// # Arguments
+///
+/// * `items` - The list paragraphs in document order
+/// * `monospace` - Font family for monospace runs, if available
+/// * `links` - Marker IDs for hyperlinks and bookmarks
 ///
 /// # Returns
 ///
-/// * `Result<fonts::FontFamily<fonts::FontData>>` - The loaded font family or an error
-fn load_default_font() -> Result<fonts::FontFamily<fonts::FontData>> {
-    debug!("Loading default font: {}", DEFAULT_FONT_NAME);
-
-    // Try to load custom fonts, but fall back to built-in fonts if that fails
-    match load_custom_fonts() {
-        Ok(fonts) => {
-            debug!("Successfully loaded custom fonts");
-            Ok(fonts)
-        },
-        Err(err) => {
-            debug!("Failed to load custom fonts: {}. Using built-in fonts instead.", err);
-            // Create a basic font family with empty data
-            // In a real implementation, we would use a built-in font
-            let empty_data = fonts::FontData::new(Vec::new(), None)
-                .context("Failed to create empty font data")?;
-
-            let font_family = fonts::FontFamily {
-                regular: empty_data.clone(),
-                bold: empty_data.clone(),
-                italic: empty_data.clone(),
-                bold_italic: empty_data,
+/// * `elements::LinearLayout` - The list layout
+fn build_list(
+    items: &[&Paragraph],
+    monospace: Option<fonts::FontFamily<fonts::Font>>,
+    links: &mut LinkMap,
+) -> elements::LinearLayout {
+    let level = |paragraph: &Paragraph| paragraph.list.as_ref().map_or(0, |item| item.level);
+    let base_level = items.first().map_or(0, |first| level(first));
+
+    // Split into top-level entries, each followed by its nested items
+    let mut entries: Vec<(&Paragraph, Vec<&Paragraph>)> = Vec::new();
+    for &paragraph in items {
+        match entries.last_mut() {
+            Some((_, children)) if level(paragraph) > base_level => children.push(paragraph),
+            _ => entries.push((paragraph, Vec::new())),
+        }
+    }
+
+    let mut build_entry = |paragraph: &Paragraph, children: &[&Paragraph]| {
+        let mut entry = elements::LinearLayout::vertical()
+            .element(build_rich_paragraph(paragraph, monospace, links));
+        if !children.is_empty() {
+            entry.push(build_list(children, monospace, links));
+        }
+        entry
+    };
+
+    let list_items: Vec<_> = entries.iter()
+        .filter_map(|(paragraph, _)| paragraph.list.as_ref())
+        .collect();
+    let first_number = list_items.first().map_or(1, |item| item.number);
+    let is_plain_numbering = list_items.iter().enumerate()
+        .all(|(i, item)| item.ordered && item.number == first_number + i && item.marker == format!("{}.", item.number));
+    let is_uniform_bullet = list_items.iter()
+        .all(|item| !item.ordered && item.marker == list_items[0].marker);
+
+    let mut layout = elements::LinearLayout::vertical();
+    if is_plain_numbering {
+        let mut list = elements::OrderedList::with_start(first_number);
+        for (paragraph, children) in &entries {
+            list.push(build_entry(paragraph, children));
+        }
+        layout.push(list);
+    } else if is_uniform_bullet {
+        let mut list = elements::UnorderedList::with_bullet(list_items[0].marker.clone());
+        for (paragraph, children) in &entries {
+            list.push(build_entry(paragraph, children));
+        }
+        layout.push(list);
+    } else {
+        for (paragraph, children) in &entries {
+            let marker = paragraph.list.as_ref().map_or_else(String::new, |item| item.marker.clone());
+            layout.push(elements::BulletPoint::new(build_entry(paragraph, children)).with_bullet(marker));
+        }
+    }
+
+    layout
+}
+
+/// Builds an image element at the display size of a Word image
+///
+/// # Arguments
+///
+/// * `image` - The image extracted from the document
+///
+/// # Returns
+///
+/// * `Result<FittedImage>` - The image element or an error
+fn build_image(image: &Image, content_height: f64) -> Result<FittedImage> {
+    let data = image::load_from_memory(&image.data)
+        .context("Failed to decode image")?;
+
+    // genpdf cannot embed transparency, so transparent pixels are blended onto white
+    let data = if data.color().has_alpha() {
+        let mut rgb = image::RgbImage::new(data.width(), data.height());
+        for (source, target) in data.to_rgba8().pixels().zip(rgb.pixels_mut()) {
+            let [red, green, blue, alpha] = source.0;
+            let blend = |channel: u8| {
+                let alpha = u16::from(alpha);
+                ((u16::from(channel) * alpha + 255 * (255 - alpha)) / 255) as u8
             };
+            *target = image::Rgb([blend(red), blend(green), blend(blue)]);
+        }
+        image::DynamicImage::ImageRgb8(rgb)
+    } else {
+        data
+    };
 
-            warn!("Using empty font data. In a real implementation, use built-in fonts.");
-            Ok(font_family)
+    // Images without a recorded size keep their pixel size at 96 DPI
+    let (width, height) = if image.width_mm > 0.0 && image.height_mm > 0.0 {
+        (image.width_mm, image.height_mm)
+    } else {
+        (f64::from(data.width()) * 25.4 / 96.0, f64::from(data.height()) * 25.4 / 96.0)
+    };
+
+    FittedImage::new(data, genpdf::Mm::from(width), genpdf::Mm::from(height), genpdf::Mm::from(content_height))
+        .context("Failed to create image element")
+}
+
+/// Returns the font size for a one-based heading level, relative to the body font size
+fn heading_font_size(level: usize, font_size: u8) -> u8 {
+    let index = level.clamp(1, HEADING_FONT_SIZES.len()) - 1;
+    scale_font_size(HEADING_FONT_SIZES[index], font_size)
+}
+
+/// Scales a font size chosen for the default body font size to another body font size
+fn scale_font_size(size: u8, font_size: u8) -> u8 {
+    let scaled = f64::from(size) * f64::from(font_size) / f64::from(DEFAULT_FONT_SIZE);
+    scaled.round().clamp(1.0, f64::from(u8::MAX)) as u8
+}
+
+/// Resolves the page size and margins of a PDF
+///
+/// Options that are set take precedence over the page setup of the source document,
+/// which in turn takes precedence over A4 pages with the default margins.
+///
+/// # Arguments
+///
+/// * `options` - The page layout options
+/// * `source` - The page setup of the source document, if it has one
+/// * `landscape` - Whether to use landscape pages if neither the options nor the source set the orientation
+///
+/// # Returns
+///
+/// * `PageSetup` - The page size and margins in millimetres
+fn page_setup(options: &PdfOptions, source: Option<&PageSetup>, landscape: bool) -> PageSetup {
+    let (width, height) = match (options.paper_size, source) {
+        (Some(paper_size), _) => paper_size.size_mm(),
+        (None, Some(source)) => (source.width, source.height),
+        (None, None) => PaperSize::A4.size_mm(),
+    };
+    let landscape = match options.orientation {
+        Some(orientation) => orientation == Orientation::Landscape,
+        None => source.map_or(landscape, |source| source.width > source.height),
+    };
+    let (width, height) = if landscape == (width > height) { (width, height) } else { (height, width) };
+    let margins = options.margins
+        .or(source.map(|source| source.margins))
+        .unwrap_or(PageMargins::uniform(PAGE_MARGIN));
+
+    PageSetup { width, height, margins }
+}
+
+/// Sets the paper size, font size and line spacing of a document
+///
+/// # Arguments
+///
+/// * `doc` - The document to set up
+/// * `options` - The text layout options
+/// * `setup` - The resolved page size and margins
+fn set_text_layout(doc: &mut genpdf::Document, options: &PdfOptions, setup: &PageSetup) {
+    debug!("Using {:.0} x {:.0} mm pages with margins of {}", setup.width, setup.height, setup.margins);
+    doc.set_paper_size(genpdf::Size::new(setup.width, setup.height));
+    if let Some(font_size) = options.font_size {
+        doc.set_font_size(font_size);
+    }
+    if let Some(line_spacing) = options.line_spacing {
+        doc.set_line_spacing(line_spacing);
+    }
+}
+
+/// Converts the margins of a page setup for the page decorators
+fn page_margins(setup: &PageSetup) -> genpdf::Margins {
+    let margins = &setup.margins;
+    genpdf::Margins::trbl(margins.top, margins.right, margins.bottom, margins.left)
+}
+
+/// Returns the title of a document from its properties, or else its file name
+///
+/// # Arguments
+///
+/// * `properties` - The properties of the document
+/// * `input_path` - Path to the source document
+/// * `fallback` - Title used if neither is available
+///
+/// # Returns
+///
+/// * `String` - The document title
+fn document_title(properties: &DocumentProperties, input_path: &Path, fallback: &str) -> String {
+    properties.title.clone()
+        .or_else(|| input_path.file_stem().map(|s| s.to_string_lossy().to_string()))
+        .unwrap_or_else(|| fallback.to_string())
+}
+
+/// Creates the page decorator printing the header and footer of every page
+///
+/// Header and footer templates set in the options replace the lines of the source
+/// document; an empty template leaves the header or footer out.
+///
+/// # Arguments
+///
+/// * `options` - Options with the header and footer templates
+/// * `setup` - The resolved page size and margins
+/// * `header` - Header lines of the source document
+/// * `footer` - Footer lines of the source document
+/// * `title` - Title of the document, for the `{title}` field
+/// * `input_path` - Path to the source document, for the `{filename}` field
+/// * `now` - Time of the conversion, for the `{date}` field
+///
+/// # Returns
+///
+/// * `HeaderFooterDecorator` - The page decorator
+fn page_decorator(
+    options: &PdfOptions,
+    setup: &PageSetup,
+    header: &[String],
+    footer: &[String],
+    title: &str,
+    input_path: &Path,
+    now: Timestamp,
+) -> HeaderFooterDecorator {
+    let date = now.to_date();
+    let lines = |template: &Option<String>, source: &[String]| match template {
+        Some(template) => expand_template(template, title, input_path, &date),
+        None => source.to_vec(),
+    };
+    HeaderFooterDecorator::new(page_margins(setup), lines(&options.header, header), lines(&options.footer, footer))
+}
+
+/// Fills in the fields of a header or footer template
+///
+/// `{title}`, `{filename}` and `{date}` are replaced here; the page number `{n}` and
+/// page count `{total}` become placeholders filled in by the page decorator. Fields are
+/// replaced in a single pass, so text they insert is not expanded again.
+///
+/// # Arguments
+///
+/// * `template` - The template, with lines separated by newlines
+/// * `title` - Title of the document
+/// * `input_path` - Path to the source document
+/// * `date` - The conversion date
+///
+/// # Returns
+///
+/// * `Vec<String>` - The lines of the header or footer, none for an empty template
+fn expand_template(template: &str, title: &str, input_path: &Path, date: &str) -> Vec<String> {
+    if template.trim().is_empty() {
+        return Vec::new();
+    }
+
+    let filename = input_path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let fields = [
+        ("{title}", title),
+        ("{filename}", &filename),
+        ("{date}", date),
+        ("{n}", PAGE_NUMBER_PLACEHOLDER),
+        ("{total}", PAGE_COUNT_PLACEHOLDER),
+    ];
+    template.lines()
+        .map(|line| {
+            let mut text = String::new();
+            let mut rest = line;
+            while let Some(start) = rest.find('{') {
+                text.push_str(&rest[..start]);
+                rest = &rest[start..];
+                match fields.iter().find(|(field, _)| rest.starts_with(field)) {
+                    Some((field, value)) => {
+                        text.push_str(value);
+                        rest = &rest[field.len()..];
+                    },
+                    None => {
+                        text.push('{');
+                        rest = &rest[1..];
+                    },
+                }
+            }
+            text.push_str(rest);
+            text
+        })
+        .collect()
+}
+
+/// Lays out a document and writes it to a PDF file
+///
+/// If the header or footer shows the page count, or the table of contents shows the
+/// pages of the headings, the document is laid out twice: once to find the pages,
+/// and again with them filled in.
+///
+/// # Arguments
+///
+/// * `output_filename` - Path of the PDF file to write
+/// * `decorator` - The page decorator printing the header and footer
+/// * `table_of_contents` - Whether the document has a table of contents
+/// * `build` - Builds the document with the given page decorator and heading pages, if known
+///
+/// # Returns
+///
+/// * `Result<LinkMap>` - The link map of the written document, or an error
+fn render_pdf(
+    output_filename: &Path,
+    decorator: HeaderFooterDecorator,
+    table_of_contents: bool,
+    build: impl Fn(HeaderFooterDecorator, Option<&[Option<usize>]>) -> (genpdf::Document, LinkMap),
+) -> Result<LinkMap> {
+    let mut decorator = decorator;
+    let mut pages = None;
+    if decorator.shows_page_count() || table_of_contents {
+        let (doc, links) = build(decorator.clone(), None);
+        let mut data = Vec::new();
+        doc.render(&mut data)
+            .context(format!("Failed to lay out PDF file: {}", output_filename.display()))?;
+
+        let page_count = lopdf::Document::load_mem(&data)
+            .context(format!("Failed to count pages of PDF file: {}", output_filename.display()))?
+            .get_pages()
+            .len();
+        debug!("Counted {} pages in the first layout", page_count);
+        decorator = decorator.with_page_count(Some(page_count));
+        if table_of_contents {
+            pages = Some(pdf_postprocess::outline_pages(&data, &links)?);
+        }
+    }
+
+    let (doc, links) = build(decorator, pages.as_deref());
+    if let Err(err) = doc.render_to_file(output_filename) {
+        // Do not leave a partly written file behind
+        let _ = std::fs::remove_file(output_filename);
+        return Err(err).context(format!("Failed to generate PDF file: {}", output_filename.display()));
+    }
+    Ok(links)
+}
+
+/// Builds a table element from a table, including cells spanning several columns or rows
+///
+/// Header rows are set in bold and repeated on each page the table continues onto.
+///
+/// # Arguments
+///
+/// * `table` - The table to render
+///
+/// # Returns
+///
+/// * `Option<GridTable>` - The table, or `None` if there is nothing to render
+fn build_table(table: &Table) -> Option<GridTable> {
+    // Columns have equal width across the grid of the table
+    let col_count = table.column_count();
+    if col_count == 0 {
+        return None;
+    }
+
+    let mut grid_table = GridTable::new(col_count).with_header_rows(table.header_rows);
+    for (index, row) in table.rows.iter().enumerate() {
+        // Header rows are set in bold
+        let style = if index < table.header_rows { style::Style::new().bold() } else { style::Style::new() };
+        grid_table.push_row(row.iter()
+            .map(|cell| GridCell::new(elements::Paragraph::new(&cell.text).styled(style), cell.column, cell.col_span)
+                .with_row_span(cell.row_span))
+            .collect());
+    }
+
+    Some(grid_table)
+}
+
+/// Lays out the cells of a sheet, or of a table on it, as a table below its title block
+///
+/// # Arguments
+///
+/// * `sheet` - The sheet cells to lay out
+/// * `options` - Options controlling how formulas and grid labels are shown
+/// * `locale` - Locale used to format the cell values
+///
+/// # Returns
+///
+/// * `(Vec<String>, Table)` - The lines above the header, and the table
+fn sheet_table(sheet: &Sheet, options: &ConversionOptions, locale: &Locale) -> (Vec<String>, Table) {
+    let rows = match options.formulas {
+        FormulaDisplay::Inline => sheet.display_rows_with_formulas(locale),
+        _ => sheet.display_rows(locale),
+    };
+    if options.grid_labels {
+        (Vec::new(), sheet.add_grid_labels(sheet.table(&rows)))
+    } else {
+        sheet.body_table(&rows)
+    }
+}
+
+/// Adds the cells of a sheet, or of a table on it, to a document
+///
+/// Column widths follow their content. Tables too wide for the page are set in a
+/// smaller font, down to a minimum size beyond which the columns are split into
+/// groups printed on separate pages, each repeating the first column.
+///
+/// # Arguments
+///
+/// * `doc` - The document to add the cells to
+/// * `sheet` - The sheet cells to render
+/// * `options` - Options controlling how formulas and grid labels are shown
+/// * `locale` - Locale used to format the cell values
+/// * `font_size` - Font size of the table if it fits the page
+/// * `content_width` - Width of the page content area in millimetres
+fn push_sheet_cells(
+    doc: &mut genpdf::Document,
+    sheet: &Sheet,
+    options: &ConversionOptions,
+    locale: &Locale,
+    font_size: u8,
+    content_width: f64,
+) {
+    let (preamble, table) = sheet_table(sheet, options, locale);
+
+    // Title blocks and notes above the header are written as paragraphs
+    if !preamble.is_empty() {
+        for line in preamble {
+            doc.push(elements::Paragraph::new(line));
+        }
+        doc.push(elements::Break::new(1));
+    }
+
+    // Grid labels add a column of row numbers before the first data column
+    let key_columns = if options.grid_labels { 2 } else { 1 };
+    let label_columns = usize::from(options.grid_labels);
+    let layout = sheet_layout::plan_layout(&table, key_columns, font_size, content_width);
+    if layout.column_groups.len() > 1 {
+        debug!("Splitting {} columns of sheet '{}' into {} groups at font size {}",
+               layout.column_weights.len(), sheet.name, layout.column_groups.len(), layout.font_size);
+    }
+
+    for (index, group) in layout.column_groups.iter().enumerate() {
+        if layout.column_groups.len() > 1 {
+            if index > 0 {
+                doc.push(elements::PageBreak::new());
+            }
+            // Name the sheet columns of the group, leaving out the repeated key columns
+            let letters: Vec<String> = group.iter()
+                .filter(|&&column| column >= key_columns || index == 0)
+                .filter(|&&column| column >= label_columns)
+                .map(|&column| table::column_name(sheet.first_column + (column - label_columns) as u32))
+                .collect();
+            let caption = format!("Columns {}–{} (part {} of {})",
+                letters.first().map(String::as_str).unwrap_or_default(),
+                letters.last().map(String::as_str).unwrap_or_default(),
+                index + 1, layout.column_groups.len());
+            doc.push(elements::Paragraph::new(caption).styled(style::Style::new().italic()));
+            doc.push(elements::Break::new(0.5));
+        }
+
+        let part = sheet_layout::select_columns(&table, group);
+        let weights: Vec<usize> = group.iter().map(|&column| layout.column_weights[column]).collect();
+        if let Some(grid_table) = build_table(&part) {
+            doc.push(grid_table
+                .with_column_weights(&weights)
+                .styled(style::Style::new().with_font_size(layout.font_size)));
         }
     }
 }
 
-/// Attempts to load custom fonts from the resources directory
+/// Checks if a sheet, or a table on it, is too wide for a portrait page at the body font size
+///
+/// # Arguments
+///
+/// * `sheet` - The sheet cells to measure
+/// * `options` - Options controlling how formulas and grid labels are shown
+/// * `locale` - Locale used to format the cell values
+/// * `font_size` - Font size of the table if it fits the page
+/// * `content_width` - Width of the portrait page content area in millimetres
+///
+/// # Returns
+///
+/// * `bool` - True if the cells need a landscape page
+fn is_wide_sheet(sheet: &Sheet, options: &ConversionOptions, locale: &Locale, font_size: u8, content_width: f64) -> bool {
+    let (_, table) = sheet_table(sheet, options, locale);
+    let weights = sheet_layout::column_weights(&table);
+    sheet_layout::required_width(&weights, font_size) > content_width
+}
+
+/// Loads the font chosen in the options, or the default font compiled into the binary
+///
+/// # Arguments
+///
+/// * `options` - Options naming a font family or font directory
 ///
 /// # Returns
 ///
 /// * `Result<fonts::FontFamily<fonts::FontData>>` - The loaded font family or an error
-fn load_custom_fonts() -> Result<fonts::FontFamily<fonts::FontData>> {
-    // In a real implementation, these would be actual font files
-    // For this example, we're using placeholders, so this will likely fail
-    // and the application will fall back to built-in fonts
-
-    let font_data = fonts::FontData::new(
-        std::fs::read("resources/fonts/Roboto/static/Roboto-Regular.ttf")
-            .context("Failed to read regular font file")?,
-        None,
-    )
-    .context("Failed to load regular font data")?;
-
-    let font_data_bold = fonts::FontData::new(
-        std::fs::read("resources/fonts/Roboto/static/Roboto-Bold.ttf")
-            .context("Failed to read bold font file")?,
-        None,
-    )
-    .context("Failed to load bold font data")?;
-
-    let font_data_italic = fonts::FontData::new(
-        std::fs::read("resources/fonts/Roboto/static/Roboto-Italic.ttf")
-            .context("Failed to read italic font file")?,
-        None,
-    )
-    .context("Failed to load italic font data")?;
-
-    let font_data_bold_italic = fonts::FontData::new(
-        std::fs::read("resources/fonts/Roboto/static/Roboto-BoldItalic.ttf")
-            .context("Failed to read bold italic font file")?,
-        None,
-    )
-    .context("Failed to load bold italic font data")?;
-
-    let font_family = fonts::FontFamily {
-        regular: font_data,
-        bold: font_data_bold,
-        italic: font_data_italic,
-        bold_italic: font_data_bold_italic,
+fn load_font(options: &ConversionOptions) -> Result<fonts::FontFamily<fonts::FontData>> {
+    let files = match (&options.font_files, &options.font) {
+        (Some(files), _) => files.clone(),
+        (None, Some(font)) => font_loader::find_font(font)?,
+        (None, None) => return load_bundled_font(),
     };
+    debug!("Loading font '{}' from {}", files.family, files.regular.display());
+    files.load()
+}
+
+/// Loads the font family compiled into the binary
+///
+/// # Returns
+///
+/// * `Result<fonts::FontFamily<fonts::FontData>>` - The bundled font family or an error
+#[cfg(feature = "bundled-font")]
+fn load_bundled_font() -> Result<fonts::FontFamily<fonts::FontData>> {
+    load_bundled_family(BUNDLED_FONT_NAME, BUNDLED_FONT_DATA)
+}
 
-    Ok(font_family)
+/// Loads a font family compiled into the binary
+///
+/// # Arguments
+///
+/// * `name` - Name of the font family, for messages
+/// * `data` - The regular, bold, italic and bold italic font files
+///
+/// # Returns
+///
+/// * `Result<fonts::FontFamily<fonts::FontData>>` - The font family or an error
+#[cfg(feature = "bundled-font")]
+fn load_bundled_family(name: &str, data: [&[u8]; 4]) -> Result<fonts::FontFamily<fonts::FontData>> {
+    debug!("Loading bundled font: {}", name);
+
+    let [regular, bold, italic, bold_italic] = data.map(|data| {
+        fonts::FontData::new(data.to_vec(), None)
+            .context(format!("Failed to load bundled font: {}", name))
+    });
+
+    Ok(fonts::FontFamily {
+        regular: regular?,
+        bold: bold?,
+        italic: italic?,
+        bold_italic: bold_italic?,
+    })
+}
+
+/// Reports that no font is compiled into the binary
+///
+/// # Returns
+///
+/// * `Result<fonts::FontFamily<fonts::FontData>>` - Always an error
+#[cfg(not(feature = "bundled-font"))]
+fn load_bundled_font() -> Result<fonts::FontFamily<fonts::FontData>> {
+    anyhow::bail!("No default font is available (built without the bundled-font feature); choose a font with --font")
+}
+
+/// Loads the monospace font compiled into the binary, used for code runs
+///
+/// # Returns
+///
+/// * `Option<fonts::FontFamily<fonts::FontData>>` - The loaded font family, or `None` if it could not be loaded
+#[cfg(feature = "bundled-font")]
+fn load_monospace_font() -> Option<fonts::FontFamily<fonts::FontData>> {
+    match load_bundled_family(BUNDLED_MONOSPACE_FONT_NAME, BUNDLED_MONOSPACE_FONT_DATA) {
+        Ok(fonts) => Some(fonts),
+        Err(err) => {
+            warn!("Failed to load monospace font: {:#}. Using the default font for code.", err);
+            None
+        }
+    }
+}
+
+/// Reports that no monospace font is compiled into the binary, so code runs use the default font
+///
+/// # Returns
+///
+/// * `Option<fonts::FontFamily<fonts::FontData>>` - Always `None`
+#[cfg(not(feature = "bundled-font"))]
+fn load_monospace_font() -> Option<fonts::FontFamily<fonts::FontData>> {
+    debug!("No monospace font is available (built without the bundled-font feature). Using the default font for code.");
+    None
 }
 
 /// Generates an output filename for the PDF based on the input file
@@ -0,0 +1,163 @@
+//! Layout of spreadsheet tables on PDF pages.
+//! Column widths follow the length of their content. Tables too wide for the page
+//! are set in a smaller font, and beyond a minimum font size their columns are split
+//! into groups printed one after another, each repeating the key columns.
+
+use crate::converter::table::{Table, TableCell};
+
+/// Font size of spreadsheet tables that fit the page
+pub const DEFAULT_TABLE_FONT_SIZE: u8 = 12;
+
+/// Smallest font size used to fit a wide table on the page
+pub const MIN_TABLE_FONT_SIZE: u8 = 6;
+
+/// Average character width of the default font, relative to the font size
+const CHAR_WIDTH_EM: f64 = 0.5;
+
+/// Millimetres per typographic point
+const MM_PER_POINT: f64 = 0.3528;
+
+/// Width in characters given to columns with very short content
+const MIN_COLUMN_CHARS: usize = 3;
+
+/// Width in characters beyond which the text of a column is wrapped
+const MAX_COLUMN_CHARS: usize = 40;
+
+/// Characters of space kept between neighbouring columns
+const COLUMN_GAP_CHARS: usize = 1;
+
+/// How a table is laid out on the page
+#[derive(Debug, Clone, PartialEq)]
+pub struct SheetLayout {
+    /// Font size of the table
+    pub font_size: u8,
+    /// Relative width of each grid column of the table
+    pub column_weights: Vec<usize>,
+    /// Grid columns printed together, starting with the key columns; one group if the table fits
+    pub column_groups: Vec<Vec<usize>>,
+}
+
+/// Returns the relative width of each grid column, following the length of its content
+///
+/// Cells spanning several columns do not widen the columns they cover.
+///
+/// # Arguments
+///
+/// * `table` - The table to measure
+///
+/// # Returns
+///
+/// * `Vec<usize>` - The width of each column in characters, including the gap to the next column
+pub fn column_weights(table: &Table) -> Vec<usize> {
+    let mut weights = vec![MIN_COLUMN_CHARS; table.column_count()];
+    for cell in table.rows.iter().flatten().filter(|cell| cell.col_span == 1) {
+        // The longest line of a cell sets its width
+        let length = cell.text.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+        weights[cell.column] = weights[cell.column].max(length.min(MAX_COLUMN_CHARS));
+    }
+
+    weights.into_iter().map(|weight| weight + COLUMN_GAP_CHARS).collect()
+}
+
+/// Returns the width in millimetres needed for columns of the given weights
+///
+/// # Arguments
+///
+/// * `weights` - Relative column widths in characters
+/// * `font_size` - Font size in points
+///
+/// # Returns
+///
+/// * `f64` - The estimated width of the columns
+pub fn required_width(weights: &[usize], font_size: u8) -> f64 {
+    weights.iter().sum::<usize>() as f64 * f64::from(font_size) * CHAR_WIDTH_EM * MM_PER_POINT
+}
+
+/// Plans the layout of a table on pages with the given content width
+///
+/// # Arguments
+///
+/// * `table` - The table to lay out
+/// * `key_columns` - Number of leading grid columns repeated in every column group
+/// * `font_size` - Font size of the table if it fits the page, usually `DEFAULT_TABLE_FONT_SIZE`
+/// * `content_width` - Width available to the table in millimetres
+///
+/// # Returns
+///
+/// * `SheetLayout` - The font size, column widths and column groups of the table
+pub fn plan_layout(table: &Table, key_columns: usize, font_size: u8, content_width: f64) -> SheetLayout {
+    let column_weights = column_weights(table);
+    let all_columns: Vec<usize> = (0..column_weights.len()).collect();
+
+    // Scale the font down proportionally until the table fits
+    let natural_width = required_width(&column_weights, font_size);
+    let min_font_size = MIN_TABLE_FONT_SIZE.min(font_size);
+    if natural_width <= content_width {
+        return SheetLayout { font_size, column_weights, column_groups: vec![all_columns] };
+    }
+    let scaled = (f64::from(font_size) * content_width / natural_width).floor() as u8;
+    if scaled >= min_font_size {
+        return SheetLayout { font_size: scaled, column_weights, column_groups: vec![all_columns] };
+    }
+
+    // At the smallest font size, split the columns into groups that fit the page
+    let font_size = min_font_size;
+    let column_width = |column: usize| required_width(&column_weights[column..=column], font_size);
+    let mut keys: Vec<usize> = (0..key_columns.min(column_weights.len())).collect();
+    // Key columns may take at most half of the page
+    while keys.iter().map(|&column| column_width(column)).sum::<f64>() > content_width / 2.0 {
+        keys.pop();
+    }
+    let key_width: f64 = keys.iter().map(|&column| column_width(column)).sum();
+
+    let mut column_groups = Vec::new();
+    let mut group = keys.clone();
+    let mut width = key_width;
+    for column in keys.len()..column_weights.len() {
+        if group.len() > keys.len() && width + column_width(column) > content_width {
+            column_groups.push(std::mem::replace(&mut group, keys.clone()));
+            width = key_width;
+        }
+        group.push(column);
+        width += column_width(column);
+    }
+    if group.len() > keys.len() || column_groups.is_empty() {
+        column_groups.push(group);
+    }
+
+    SheetLayout { font_size, column_weights, column_groups }
+}
+
+/// Returns the part of a table covering the given grid columns
+///
+/// Cells spanning into or across columns that are left out are clipped to the
+/// selected columns, keeping their text.
+///
+/// # Arguments
+///
+/// * `table` - The table to take columns from
+/// * `columns` - Grid columns to keep, in ascending order
+///
+/// # Returns
+///
+/// * `Table` - The table with the selected columns, numbered from zero
+pub fn select_columns(table: &Table, columns: &[usize]) -> Table {
+    let rows = table.rows.iter()
+        .map(|row| row.iter()
+            .filter_map(|cell| {
+                let covered: Vec<usize> = columns.iter()
+                    .enumerate()
+                    .filter(|(_, column)| (cell.column..cell.column + cell.col_span).contains(column))
+                    .map(|(index, _)| index)
+                    .collect();
+                Some(TableCell {
+                    column: *covered.first()?,
+                    col_span: covered.len(),
+                    ..cell.clone()
+                })
+            })
+            .collect())
+        .collect();
+
+    Table { rows, header_rows: table.header_rows }
+}
@@ -0,0 +1,229 @@
+//! Table model shared by the document readers and the output writers.
+//! Cells are placed on a grid of rows and columns and may span several of each.
+
+use std::fmt;
+
+/// Number of columns of a worksheet, the last being `XFD`
+const MAX_COLUMNS: u32 = 16_384;
+
+/// A table cell, placed at a column of its row
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableCell {
+    /// Text of the cell
+    pub text: String,
+    /// Zero-based grid column where the cell starts
+    pub column: usize,
+    /// Number of grid columns the cell covers
+    pub col_span: usize,
+    /// Number of grid rows the cell covers, starting at its own row
+    pub row_span: usize,
+}
+
+impl TableCell {
+    /// Creates a cell covering a single grid position
+    pub fn new(text: impl Into<String>, column: usize) -> TableCell {
+        TableCell {
+            text: text.into(),
+            column,
+            col_span: 1,
+            row_span: 1,
+        }
+    }
+}
+
+/// A table laid out on a grid
+///
+/// Each row lists the cells that start in it, ordered by column. Grid positions
+/// covered by a spanning cell from a previous row or column have no cell of their own.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    /// Cells starting in each row
+    pub rows: Vec<Vec<TableCell>>,
+    /// Number of leading rows forming the header, if known
+    pub header_rows: usize,
+}
+
+impl Table {
+    /// Creates a table without spanning cells from rows of cell text
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - The table data as rows of cells
+    ///
+    /// # Returns
+    ///
+    /// * `Table` - The table with one cell per grid position
+    pub fn from_rows(rows: &[Vec<String>]) -> Table {
+        Table {
+            rows: rows.iter()
+                .map(|row| row.iter()
+                    .enumerate()
+                    .map(|(column, text)| TableCell::new(text.as_str(), column))
+                    .collect())
+                .collect(),
+            header_rows: 0,
+        }
+    }
+
+    /// Returns `true` if the table has no rows
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Returns the number of grid columns, as covered by the widest row
+    pub fn column_count(&self) -> usize {
+        self.rows.iter()
+            .flatten()
+            .map(|cell| cell.column + cell.col_span)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns `true` if any cell spans more than one row or column
+    pub fn has_spans(&self) -> bool {
+        self.rows.iter()
+            .flatten()
+            .any(|cell| cell.col_span > 1 || cell.row_span > 1)
+    }
+
+    /// Returns the text at every grid position, with covered and missing positions empty
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Vec<String>>` - One row per table row, each with `column_count` cells
+    pub fn grid(&self) -> Vec<Vec<String>> {
+        let column_count = self.column_count();
+        self.rows.iter()
+            .map(|row| {
+                let mut grid_row = vec![String::new(); column_count];
+                for cell in row {
+                    grid_row[cell.column] = cell.text.clone();
+                }
+                grid_row
+            })
+            .collect()
+    }
+
+    /// Merges the header rows into a single row, joining the texts above each column
+    ///
+    /// A multi-row header such as a `2023` cell spanning `Q1` and `Q2` becomes the
+    /// header texts `2023 / Q1` and `2023 / Q2`.
+    ///
+    /// # Returns
+    ///
+    /// * `Table` - The table with at most one header row
+    pub fn flatten_header(&self) -> Table {
+        if self.header_rows <= 1 {
+            return self.clone();
+        }
+
+        let mut parts: Vec<Vec<&str>> = vec![Vec::new(); self.column_count()];
+        for cell in self.rows.iter().take(self.header_rows).flatten() {
+            let text = cell.text.trim();
+            for column_parts in &mut parts[cell.column..cell.column + cell.col_span] {
+                if !text.is_empty() && column_parts.last() != Some(&text) {
+                    column_parts.push(text);
+                }
+            }
+        }
+
+        let header = parts.iter()
+            .enumerate()
+            .map(|(column, column_parts)| TableCell::new(column_parts.join(" / "), column))
+            .collect();
+        let mut rows = vec![header];
+        rows.extend(self.rows.iter().skip(self.header_rows).cloned());
+
+        Table { rows, header_rows: 1 }
+    }
+}
+
+/// A rectangle of cells, in zero-based worksheet positions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellRange {
+    /// First row of the range
+    pub first_row: u32,
+    /// First column of the range
+    pub first_column: u32,
+    /// Last row of the range, inclusive
+    pub last_row: u32,
+    /// Last column of the range, inclusive
+    pub last_column: u32,
+}
+
+impl fmt::Display for CellRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}:{}{}", column_name(self.first_column), self.first_row + 1,
+               column_name(self.last_column), self.last_row + 1)
+    }
+}
+
+impl CellRange {
+    /// Parses an A1-style range reference such as `A1:C2`
+    ///
+    /// # Arguments
+    ///
+    /// * `reference` - The range reference
+    ///
+    /// # Returns
+    ///
+    /// * `Option<CellRange>` - The range, or `None` if the reference is invalid
+    pub fn parse(reference: &str) -> Option<CellRange> {
+        let (start, end) = reference.split_once(':').unwrap_or((reference, reference));
+        let (first_row, first_column) = cell_position(start)?;
+        let (last_row, last_column) = cell_position(end)?;
+        Some(CellRange {
+            first_row: first_row.min(last_row),
+            first_column: first_column.min(last_column),
+            last_row: first_row.max(last_row),
+            last_column: first_column.max(last_column),
+        })
+    }
+
+    /// Returns `true` if the range covers the given worksheet position
+    pub fn contains(&self, row: u32, column: u32) -> bool {
+        (self.first_row..=self.last_row).contains(&row) && (self.first_column..=self.last_column).contains(&column)
+    }
+}
+
+/// Returns the letters naming a zero-based worksheet column, e.g. `A`, `Z`, `AA`
+///
+/// # Arguments
+///
+/// * `column` - Zero-based column index
+///
+/// # Returns
+///
+/// * `String` - The column letters
+pub fn column_name(column: u32) -> String {
+    let mut letters = Vec::new();
+    let mut remaining = column + 1;
+    while remaining > 0 {
+        let digit = (remaining - 1) % 26;
+        letters.push((b'A' + digit as u8) as char);
+        remaining = (remaining - 1) / 26;
+    }
+    letters.iter().rev().collect()
+}
+
+/// Converts an A1-style cell reference to a zero-based (row, column) position
+pub(crate) fn cell_position(reference: &str) -> Option<(u32, u32)> {
+    let split = reference.find(|c: char| c.is_ascii_digit())?;
+    let (letters, digits) = reference.split_at(split);
+    if letters.is_empty() {
+        return None;
+    }
+
+    let column = letters.chars().try_fold(0u32, |column, c| {
+        if !c.is_ascii_alphabetic() {
+            return None;
+        }
+        column.checked_mul(26)?.checked_add(c.to_ascii_uppercase() as u32 - 'A' as u32 + 1)
+    })?;
+    if column > MAX_COLUMNS {
+        return None;
+    }
+    let row: u32 = digits.parse().ok()?;
+
+    Some((row.checked_sub(1)?, column - 1))
+}
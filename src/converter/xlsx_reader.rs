@@ -1,9 +1,138 @@
-//! Module for reading and extracting data from Excel (.xlsx/.xls) spreadsheets.
+//! Module for reading and extracting data from spreadsheets.
+//! Excel (.xlsx/.xlsm/.xlsb/.xls) and OpenDocument (.ods) workbooks are supported.
 
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Cursor;
 use std::path::Path;
 use anyhow::{Result, Context};
-use calamine::{Reader, open_workbook, Xlsx, Range, DataType};
+use calamine::{
+    open_workbook_auto_from_rs, open_workbook_from_rs, DataType, Ods, Range, Reader, SheetVisible, Sheets, Xls, Xlsb,
+    Xlsx,
+};
 use log::{info, debug, warn};
+use quick_xml::events::Event;
+
+use crate::converter::number_format::{self, Locale, NumberFormat};
+use crate::converter::options::{ConversionOptions, FormulaDisplay, SheetRange};
+use crate::converter::package::{read_package_part, resolve_part_target};
+use crate::converter::table::{cell_position, column_name, CellRange, Table, TableCell};
+
+/// Workbook part listing the sheets
+const WORKBOOK_PART: &str = "xl/workbook.xml";
+
+/// Relationships of the workbook part, mapping sheets to their parts
+const WORKBOOK_RELS_PART: &str = "xl/_rels/workbook.xml.rels";
+
+/// Styles part holding the number formats
+const STYLES_PART: &str = "xl/styles.xml";
+
+/// Number of rows at the top of a sheet searched for its header
+const HEADER_SCAN_ROWS: usize = 10;
+
+/// Largest number of rows a detected header can span
+const MAX_HEADER_ROWS: usize = 3;
+
+/// Number formats of the cells of a sheet, keyed by zero-based (row, column)
+type CellFormats = HashMap<(u32, u32), NumberFormat>;
+
+/// Formulas of the cells of a sheet, keyed by zero-based (row, column)
+type CellFormulas = HashMap<(u32, u32), String>;
+
+/// Details of a sheet read from its package part
+#[derive(Debug, Default)]
+struct SheetMarkup {
+    /// Number formats of the formatted cells
+    formats: CellFormats,
+    /// Merged cell ranges
+    merged_ranges: Vec<CellRange>,
+    /// Excel tables on the sheet
+    tables: Vec<TablePart>,
+}
+
+/// An Excel table defined in a table part
+#[derive(Debug, Clone)]
+struct TablePart {
+    /// Display name of the table
+    name: String,
+    /// The cells of the table, including its header
+    range: CellRange,
+    /// Number of header rows at the top of the range
+    header_rows: usize,
+}
+
+/// The value of a spreadsheet cell
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum CellValue {
+    /// No value
+    #[default]
+    Empty,
+    /// Text
+    String(String),
+    /// A number
+    Number(f64),
+    /// A date and/or time, as a serial number of days in the 1900 date system
+    DateTime(f64),
+    /// A duration in days
+    Duration(f64),
+    /// A boolean
+    Bool(bool),
+    /// An error such as "#DIV/0!"
+    Error(String),
+}
+
+/// A spreadsheet cell with its value and number format
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Cell {
+    /// The typed value of the cell
+    pub value: CellValue,
+    /// The number format applied to the cell, if known
+    pub number_format: Option<NumberFormat>,
+    /// The formula computing the cell, starting with `=`, if formulas were read
+    pub formula: Option<String>,
+}
+
+impl Cell {
+    /// Returns `true` if the cell shows no text
+    pub fn is_empty(&self) -> bool {
+        match &self.value {
+            CellValue::Empty => true,
+            CellValue::String(text) => text.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// Returns the text of the cell as Excel displays it
+    ///
+    /// # Arguments
+    ///
+    /// * `locale` - Locale providing the decimal and thousands separators
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The formatted cell value
+    pub fn display(&self, locale: &Locale) -> String {
+        number_format::format_value(&self.value, self.number_format.as_ref(), locale)
+    }
+
+    /// Returns the displayed text of the cell followed by its formula, e.g. `12 (=SUM(A1:A9))`
+    ///
+    /// # Arguments
+    ///
+    /// * `locale` - Locale providing the decimal and thousands separators
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The formatted cell value with its formula, if it has one
+    pub fn display_with_formula(&self, locale: &Locale) -> String {
+        let text = self.display(locale);
+        match &self.formula {
+            Some(formula) if text.is_empty() => formula.clone(),
+            Some(formula) => format!("{} ({})", text, formula),
+            None => text,
+        }
+    }
+}
 
 /// Represents a sheet in an Excel workbook
 #[derive(Debug)]
@@ -11,7 +140,234 @@ pub struct Sheet {
     /// Name of the sheet
     pub name: String,
     /// Data in the sheet (rows and columns)
-    pub data: Vec<Vec<String>>,
+    pub data: Vec<Vec<Cell>>,
+    /// One-based worksheet row number of each data row
+    pub row_numbers: Vec<u32>,
+    /// Zero-based worksheet row of the first cell of the used range
+    pub first_row: u32,
+    /// Zero-based worksheet column of the first data column
+    pub first_column: u32,
+    /// Merged cell ranges of the sheet
+    pub merged_ranges: Vec<CellRange>,
+    /// Excel tables and named ranges on the sheet
+    pub tables: Vec<SheetTable>,
+    /// Index in `data` of the first header row; rows above it are a title block or notes
+    pub header_start: usize,
+    /// Number of header rows, starting at `header_start`
+    pub header_rows: usize,
+}
+
+/// The kind of a named region of cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableKind {
+    /// An Excel table (ListObject)
+    Table,
+    /// A defined name referring to a cell range
+    NamedRange,
+}
+
+impl fmt::Display for TableKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TableKind::Table => "Table",
+            TableKind::NamedRange => "Named range",
+        })
+    }
+}
+
+/// An Excel table or named range, read as a table of its own
+#[derive(Debug)]
+pub struct SheetTable {
+    /// Name of the table or range
+    pub name: String,
+    /// Whether this is an Excel table or a named range
+    pub kind: TableKind,
+    /// The cells the table or range refers to
+    pub range: CellRange,
+    /// The cells of the table, with the header row first
+    pub cells: Sheet,
+}
+
+impl Sheet {
+    /// Returns the displayed text of every cell
+    ///
+    /// # Arguments
+    ///
+    /// * `locale` - Locale providing the decimal and thousands separators
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Vec<String>>` - The formatted data as rows of strings
+    pub fn display_rows(&self, locale: &Locale) -> Vec<Vec<String>> {
+        self.data.iter()
+            .map(|row| row.iter().map(|cell| cell.display(locale)).collect())
+            .collect()
+    }
+
+    /// Returns the displayed text of every cell, followed by its formula if it has one
+    ///
+    /// # Arguments
+    ///
+    /// * `locale` - Locale providing the decimal and thousands separators
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Vec<String>>` - The formatted data with formulas as rows of strings
+    pub fn display_rows_with_formulas(&self, locale: &Locale) -> Vec<Vec<String>> {
+        self.data.iter()
+            .map(|row| row.iter().map(|cell| cell.display_with_formula(locale)).collect())
+            .collect()
+    }
+
+    /// Lays out rows of cell text as a table, spanning merged cells across their range
+    ///
+    /// Rows and columns of a merged range that are not part of the data are left out
+    /// of its span. The text of a merged cell is taken from its first data cell.
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - Text of each data cell, as returned by `display_rows`
+    ///
+    /// # Returns
+    ///
+    /// * `Table` - The table with merged cells spanning their rows and columns
+    pub fn table(&self, rows: &[Vec<String>]) -> Table {
+        let mut table = Table::from_rows(rows);
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0) as u32;
+
+        for range in &self.merged_ranges {
+            let data_rows: Vec<usize> = self.row_numbers.iter()
+                .enumerate()
+                .filter(|(_, number)| (range.first_row + 1..=range.last_row + 1).contains(*number))
+                .map(|(index, _)| index)
+                .collect();
+            let first_column = range.first_column.max(self.first_column);
+            let last_column = range.last_column.min((self.first_column + width).saturating_sub(1));
+            if data_rows.is_empty() || first_column > last_column {
+                continue;
+            }
+            let first_column = (first_column - self.first_column) as usize;
+            let last_column = (last_column - self.first_column) as usize;
+
+            // Keep the first cell, spanning the range, and drop the cells it covers
+            for (index, &row) in data_rows.iter().enumerate() {
+                table.rows[row].retain_mut(|cell| {
+                    if !(first_column..=last_column).contains(&cell.column) {
+                        return true;
+                    }
+                    if index == 0 && cell.column == first_column {
+                        cell.col_span = last_column - first_column + 1;
+                        cell.row_span = data_rows.len();
+                        return true;
+                    }
+                    false
+                });
+            }
+        }
+
+        table
+    }
+
+    /// Returns the A1-style reference of the first cell of the used range, e.g. `D5`
+    pub fn origin(&self) -> String {
+        format!("{}{}", column_name(self.first_column), self.first_row + 1)
+    }
+
+    /// Returns the letters of the worksheet columns of the data, e.g. `D`, `E`, `F`
+    pub fn column_letters(&self) -> Vec<String> {
+        let width = self.data.iter().map(Vec::len).max().unwrap_or(0) as u32;
+        (self.first_column..self.first_column + width).map(column_name).collect()
+    }
+
+    /// Adds Excel-like grid labels to a table of the sheet's data
+    ///
+    /// A first row of column letters and a first column of row numbers are added,
+    /// shifting the data one row down and one column to the right.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - The table of the sheet's data, as returned by `table`
+    ///
+    /// # Returns
+    ///
+    /// * `Table` - The table with grid labels
+    pub fn add_grid_labels(&self, table: Table) -> Table {
+        let mut header = vec![TableCell::new("", 0)];
+        header.extend(self.column_letters()
+            .into_iter()
+            .enumerate()
+            .map(|(column, letters)| TableCell::new(letters, column + 1)));
+
+        let mut rows = vec![header];
+        for (index, row) in table.rows.into_iter().enumerate() {
+            let row_number = self.row_numbers.get(index).copied().unwrap_or(index as u32 + 1);
+            let mut labelled = vec![TableCell::new(row_number.to_string(), 0)];
+            labelled.extend(row.into_iter().map(|cell| TableCell { column: cell.column + 1, ..cell }));
+            rows.push(labelled);
+        }
+
+        Table { rows, header_rows: 1 }
+    }
+
+    /// Splits the rows of cell text into the lines above the header and a table from the header on
+    ///
+    /// Rows above the detected or configured header, such as a title block or notes,
+    /// are returned as lines of text. Empty rows among them are left out.
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - Text of each data cell, as returned by `display_rows`
+    ///
+    /// # Returns
+    ///
+    /// * `(Vec<String>, Table)` - The lines above the header, and the table with its header rows
+    pub fn body_table(&self, rows: &[Vec<String>]) -> (Vec<String>, Table) {
+        let mut table = self.table(rows);
+        let header_start = self.header_start.min(table.rows.len());
+
+        let preamble = rows[..header_start].iter()
+            .map(|row| row.iter()
+                .map(|text| text.trim())
+                .filter(|text| !text.is_empty())
+                .collect::<Vec<_>>()
+                .join(" "))
+            .filter(|line| !line.is_empty())
+            .collect();
+        table.rows.drain(..header_start);
+        table.header_rows = self.header_rows.min(table.rows.len());
+
+        (preamble, table)
+    }
+
+    /// Returns the A1-style reference of a data cell, e.g. `B3`
+    ///
+    /// # Arguments
+    ///
+    /// * `row` - Index of the row in `data`
+    /// * `column` - Index of the column in the row
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The worksheet reference of the cell
+    pub fn cell_reference(&self, row: usize, column: usize) -> String {
+        let row_number = self.row_numbers.get(row).copied().unwrap_or(row as u32 + 1);
+        format!("{}{}", column_name(self.first_column + column as u32), row_number)
+    }
+
+    /// Lists the formulas of the sheet with the references of their cells
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<(String, String)>` - Cell reference and formula pairs, in row order
+    pub fn formulas(&self) -> Vec<(String, String)> {
+        self.data.iter()
+            .enumerate()
+            .flat_map(|(row_index, row)| row.iter()
+                .enumerate()
+                .filter_map(move |(col_index, cell)| Some((row_index, col_index, cell.formula.as_ref()?))))
+            .map(|(row, column, formula)| (self.cell_reference(row, column), formula.clone()))
+            .collect()
+    }
 }
 
 /// Extracts data from all sheets in an Excel workbook
@@ -19,33 +375,114 @@ pub struct Sheet {
 /// # Arguments
 ///
 /// * `path` - Path to the Excel file
+/// * `options` - Conversion options selecting what is read besides the cell values
 ///
 /// # Returns
 ///
 /// * `Result<Vec<Sheet>>` - Vector of extracted sheets or an error
-pub fn extract_sheets(path: &Path) -> Result<Vec<Sheet>> {
+pub fn extract_sheets(path: &Path, options: &ConversionOptions) -> Result<Vec<Sheet>> {
     info!("Extracting data from Excel file: {}", path.display());
     
-    let mut workbook: Xlsx<_> = open_workbook(path)
-        .context(format!("Failed to open Excel file: {}", path.display()))?;
+    let data = std::fs::read(path)
+        .context(format!("Failed to read file: {}", path.display()))?;
+    let mut workbook = open_workbook(path, &data)?;
     
     let sheet_names = workbook.sheet_names().to_vec();
     info!("Found {} sheets in workbook", sheet_names.len());
+
+    // Ranges apply to every converted workbook, so one naming another workbook's sheet is not an error
+    for selection in &options.sheet_ranges {
+        if !sheet_names.iter().any(|name| name.eq_ignore_ascii_case(&selection.sheet)) {
+            warn!("Ignoring range {} of a sheet the workbook does not have: {}", selection.range, selection.sheet);
+        }
+    }
+
+    // calamine does not expose number formats or merged cells, so they are read from the package
+    let mut markup = match workbook {
+        Sheets::Xlsx(_) => read_sheet_markup(&data).unwrap_or_else(|err| {
+            warn!("Failed to read number formats and merged cells: {:#}", err);
+            HashMap::new()
+        }),
+        _ => {
+            warn!("Number formats, merged cells and tables are only read from .xlsx and .xlsm workbooks; \
+                   showing the plain values of {}", path.display());
+            HashMap::new()
+        },
+    };
     
     let mut sheets = Vec::new();
     
+    let defined_names = read_defined_names(&workbook);
+
+    // Hidden and very hidden sheets typically hold scratch data and lookups
+    let hidden: Vec<String> = workbook.sheets_metadata()
+        .iter()
+        .filter(|sheet| sheet.visible != SheetVisible::Visible)
+        .map(|sheet| sheet.name.clone())
+        .collect();
+
     for sheet_name in sheet_names {
+        if !options.selects_sheet(&sheet_name) {
+            info!("Skipping sheet not selected for conversion: {}", sheet_name);
+            continue;
+        }
+        if !options.include_hidden_sheets && hidden.contains(&sheet_name) {
+            info!("Skipping hidden sheet: {}", sheet_name);
+            continue;
+        }
+
         debug!("Processing sheet: {}", sheet_name);
         
-        if let Some(Ok(range)) = workbook.worksheet_range(&sheet_name) {
-            let sheet_data = process_range(range);
+        if let Some(Ok(used_range)) = workbook.worksheet_range(&sheet_name) {
+            let formulas = if options.formulas == FormulaDisplay::Hidden {
+                CellFormulas::new()
+            } else {
+                read_formulas(&mut workbook, &sheet_name)
+            };
+            let sheet_markup = markup.remove(&sheet_name).unwrap_or_default();
+
+            // Excel tables and named ranges on the sheet, each read as its own table
+            // The header of an Excel table is known; that of a named range is detected
+            let regions = sheet_markup.tables.iter()
+                .map(|table| (TableKind::Table, &table.name, &table.range, Some(table.header_rows)))
+                .chain(defined_names.iter()
+                    .filter(|(_, selection)| selection.sheet.eq_ignore_ascii_case(&sheet_name))
+                    .map(|(name, selection)| (TableKind::NamedRange, name, &selection.range, options.header_rows)));
+            let mut tables = Vec::new();
+            for (kind, name, range, header_rows) in regions {
+                let cells = build_sheet(
+                    &sheet_name,
+                    select_range(&used_range, range),
+                    &sheet_markup,
+                    &formulas,
+                    header_rows,
+                    options,
+                );
+                if cells.data.is_empty() {
+                    debug!("Skipping empty {} '{}'", kind, name);
+                    continue;
+                }
+                tables.push(SheetTable { name: name.clone(), kind, range: *range, cells });
+            }
+            debug!("Found {} tables and named ranges on sheet '{}'", tables.len(), sheet_name);
+
+            if options.tables_only && tables.is_empty() {
+                info!("Skipping sheet without tables or named ranges: {}", sheet_name);
+                continue;
+            }
+
+            // Converting tables only leaves the rest of the sheet out, so it is not read
+            let range = match options.sheet_range(&sheet_name) {
+                _ if options.tables_only => Range::empty(),
+                Some(selection) => select_range(&used_range, selection),
+                None => used_range,
+            };
+            let mut sheet = build_sheet(&sheet_name, range, &sheet_markup, &formulas, options.header_rows, options);
+            sheet.tables = tables;
             
-            if !sheet_data.is_empty() {
-                debug!("Extracted {} rows from sheet '{}'", sheet_data.len(), sheet_name);
-                sheets.push(Sheet {
-                    name: sheet_name,
-                    data: sheet_data,
-                });
+            if !sheet.data.is_empty() || !sheet.tables.is_empty() {
+                debug!("Extracted {} rows from sheet '{}'", sheet.data.len(), sheet_name);
+                sheets.push(sheet);
             } else {
                 warn!("Sheet '{}' appears to be empty", sheet_name);
             }
@@ -63,44 +500,510 @@ pub fn extract_sheets(path: &Path) -> Result<Vec<Sheet>> {
     Ok(sheets)
 }
 
+/// Opens a workbook with the calamine backend for its format
+///
+/// The format is chosen from the file extension. When the extension is unknown or
+/// does not match the contents (e.g. an .xlsx file saved as .xls), the format is
+/// detected from the file contents instead.
+///
+/// # Arguments
+///
+/// * `path` - Path to the spreadsheet file
+/// * `data` - Contents of the spreadsheet file
+///
+/// # Returns
+///
+/// * `Result<Sheets<Cursor<Vec<u8>>>>` - The opened workbook or an error
+fn open_workbook(path: &Path, data: &[u8]) -> Result<Sheets<Cursor<Vec<u8>>>> {
+    let extension = path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    let reader = Cursor::new(data.to_vec());
+    let by_extension = match extension.as_str() {
+        "xls" | "xla" => open_workbook_from_rs::<Xls<_>, _>(reader.clone())
+            .map(Sheets::Xls)
+            .map_err(|err| err.to_string()),
+        "xlsx" | "xlsm" | "xlam" => open_workbook_from_rs::<Xlsx<_>, _>(reader.clone())
+            .map(Sheets::Xlsx)
+            .map_err(|err| err.to_string()),
+        "xlsb" => open_workbook_from_rs::<Xlsb<_>, _>(reader.clone())
+            .map(Sheets::Xlsb)
+            .map_err(|err| err.to_string()),
+        "ods" => open_workbook_from_rs::<Ods<_>, _>(reader.clone())
+            .map(Sheets::Ods)
+            .map_err(|err| err.to_string()),
+        _ => Err(format!("Unknown spreadsheet extension: {}", extension)),
+    };
+
+    match by_extension {
+        Ok(workbook) => Ok(workbook),
+        Err(err) => {
+            debug!("Failed to open {} by extension ({}); detecting format from contents", path.display(), err);
+            open_workbook_auto_from_rs(reader)
+                .context(format!("Failed to open spreadsheet: {}: {}", path.display(), err))
+        }
+    }
+}
+
 /// Processes a range of cells from an Excel sheet
 ///
 /// # Arguments
 ///
 /// * `range` - The range of cells to process
+/// * `formats` - Number formats of the sheet's cells, keyed by absolute position
+/// * `formulas` - Formulas of the sheet's cells, keyed by absolute position
+/// * `max_blank_rows` - Number of consecutive empty rows kept; further empty rows are skipped
 ///
 /// # Returns
 ///
-/// * `Vec<Vec<String>>` - The processed data as rows of strings
-fn process_range(range: Range<DataType>) -> Vec<Vec<String>> {
+/// * `(Vec<Vec<Cell>>, Vec<u32>)` - The processed data as rows of typed cells, and the
+///   one-based worksheet row number of each row
+fn process_range(
+    range: Range<DataType>,
+    formats: &CellFormats,
+    formulas: &CellFormulas,
+    max_blank_rows: usize,
+) -> (Vec<Vec<Cell>>, Vec<u32>) {
     let height = range.height();
     let width = range.width();
     
     if height == 0 || width == 0 {
-        return Vec::new();
+        return (Vec::new(), Vec::new());
     }
     
     debug!("Processing range with dimensions: {}x{}", width, height);
-    
+
+    // Positions in the range are relative to its first cell
+    let (start_row, start_col) = range.start().unwrap_or_default();
     let mut data = Vec::with_capacity(height);
+    let mut row_numbers = Vec::with_capacity(height);
+    let mut blank_rows = 0;
     
     for row_index in 0..height {
         let mut row = Vec::with_capacity(width);
         
         for col_index in 0..width {
-            let cell_value = match range.get_value((row_index as u32, col_index as u32)) {
-                Some(value) => value.to_string(),
-                None => String::new(),
+            let value = match range.get((row_index, col_index)) {
+                Some(value) => cell_value(value),
+                None => CellValue::Empty,
             };
+            let position = (start_row + row_index as u32, start_col + col_index as u32);
             
-            row.push(cell_value);
+            row.push(Cell {
+                value,
+                number_format: formats.get(&position).cloned(),
+                formula: formulas.get(&position).cloned(),
+            });
         }
         
-        // Skip completely empty rows
-        if row.iter().any(|cell| !cell.is_empty()) {
+        // Skip completely empty rows beyond the number of blank rows to keep
+        if row.iter().all(|cell| cell.is_empty() && cell.formula.is_none()) {
+            blank_rows += 1;
+        } else {
+            blank_rows = 0;
+        }
+        if blank_rows <= max_blank_rows {
             data.push(row);
+            row_numbers.push(start_row + row_index as u32 + 1);
         }
     }
     
-    data
-}
\ No newline at end of file
+    (data, row_numbers)
+}
+
+/// Builds a sheet from a range of its cells
+///
+/// # Arguments
+///
+/// * `name` - Name of the sheet
+/// * `range` - The cells to read
+/// * `markup` - Number formats and merged ranges of the sheet
+/// * `formulas` - Formulas of the sheet's cells, keyed by absolute position
+/// * `header_rows` - Number of header rows at the top of the range, or `None` to detect the header
+/// * `options` - Conversion options
+///
+/// # Returns
+///
+/// * `Sheet` - The sheet with the processed cells
+fn build_sheet(
+    name: &str,
+    range: Range<DataType>,
+    markup: &SheetMarkup,
+    formulas: &CellFormulas,
+    header_rows: Option<usize>,
+    options: &ConversionOptions,
+) -> Sheet {
+    let (first_row, first_column) = range.start().unwrap_or_default();
+    let (data, row_numbers) = process_range(range, &markup.formats, formulas, options.max_blank_rows);
+    let (header_start, header_rows) = match header_rows {
+        Some(count) => (0, count),
+        None => detect_header(&data),
+    };
+
+    Sheet {
+        name: name.to_string(),
+        data,
+        row_numbers,
+        first_row,
+        first_column,
+        merged_ranges: markup.merged_ranges.clone(),
+        tables: Vec::new(),
+        header_start,
+        header_rows,
+    }
+}
+
+/// Detects the header rows of a sheet's data
+///
+/// The header starts at the first row that is mostly text and fills at least half of
+/// the columns, provided no row of typed data (numbers, dates, booleans) comes before it.
+/// Rows above it are treated as a title block. The header extends over the following
+/// text rows while each row above has fewer filled cells than the next, as parent
+/// cells spanning groups of child columns do.
+///
+/// # Arguments
+///
+/// * `data` - The sheet's data as rows of typed cells
+///
+/// # Returns
+///
+/// * `(usize, usize)` - Index of the first header row and number of header rows;
+///   `(0, 1)` if no header is found
+fn detect_header(data: &[Vec<Cell>]) -> (usize, usize) {
+    let width = data.iter().map(Vec::len).max().unwrap_or(0);
+    let filled = |row: &[Cell]| row.iter().filter(|cell| !cell.is_empty()).count();
+    let is_typed = |cell: &Cell| matches!(cell.value,
+        CellValue::Number(_) | CellValue::DateTime(_) | CellValue::Duration(_) | CellValue::Bool(_));
+    let is_header_like = |row: &[Cell]| {
+        let count = filled(row);
+        let text = row.iter().filter(|cell| matches!(&cell.value, CellValue::String(text) if !text.is_empty())).count();
+        // A single filled cell in a wider sheet is a title, not a header
+        count > 0 && (count >= 2 || width == 1) && count * 2 >= width && text * 2 >= count
+    };
+
+    let scanned = &data[..data.len().min(HEADER_SCAN_ROWS)];
+    let Some(start) = scanned.iter().position(|row| is_header_like(row)) else {
+        return (0, 1);
+    };
+    if scanned[..start].iter().flatten().any(is_typed) {
+        return (0, 1);
+    }
+
+    let mut rows = 1;
+    while rows < MAX_HEADER_ROWS
+        && let Some(next) = data.get(start + rows)
+        && is_header_like(next)
+        && !next.iter().any(is_typed)
+        && filled(&data[start + rows - 1]) < filled(next)
+    {
+        rows += 1;
+    }
+
+    (start, rows)
+}
+
+/// Lists the defined names of a workbook that refer to a single cell range
+///
+/// Built-in names such as print areas and names of formulas or constants are left out.
+///
+/// # Arguments
+///
+/// * `workbook` - The opened workbook
+///
+/// # Returns
+///
+/// * `Vec<(String, SheetRange)>` - Each name with the range it refers to
+fn read_defined_names(workbook: &Sheets<Cursor<Vec<u8>>>) -> Vec<(String, SheetRange)> {
+    workbook.defined_names()
+        .iter()
+        .filter(|(name, _)| !name.starts_with("_xlnm."))
+        .filter_map(|(name, formula)| match formula.trim_start_matches('=').parse::<SheetRange>() {
+            Ok(selection) => Some((name.clone(), selection)),
+            Err(_) => {
+                debug!("Skipping defined name that is not a cell range: {} = {}", name, formula);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Restricts a range of cells to the cells inside a selection
+///
+/// # Arguments
+///
+/// * `range` - The used range of a sheet
+/// * `selection` - The cells to keep
+///
+/// # Returns
+///
+/// * `Range<DataType>` - The used cells inside the selection, or an empty range
+fn select_range(range: &Range<DataType>, selection: &CellRange) -> Range<DataType> {
+    let (Some(start), Some(end)) = (range.start(), range.end()) else {
+        return Range::empty();
+    };
+
+    // Clip the selection to the used range so that it does not add empty cells
+    let first = (start.0.max(selection.first_row), start.1.max(selection.first_column));
+    let last = (end.0.min(selection.last_row), end.1.min(selection.last_column));
+    if first.0 > last.0 || first.1 > last.1 {
+        return Range::empty();
+    }
+
+    debug!("Restricting sheet to {}{}:{}{}", column_name(first.1), first.0 + 1, column_name(last.1), last.0 + 1);
+    range.range(first, last)
+}
+
+/// Reads the formulas of a sheet
+///
+/// # Arguments
+///
+/// * `workbook` - The opened workbook
+/// * `sheet_name` - Name of the sheet
+///
+/// # Returns
+///
+/// * `CellFormulas` - The formulas keyed by absolute position, each starting with `=`
+fn read_formulas(workbook: &mut Sheets<Cursor<Vec<u8>>>, sheet_name: &str) -> CellFormulas {
+    let range = match workbook.worksheet_formula(sheet_name) {
+        Some(Ok(range)) => range,
+        Some(Err(err)) => {
+            warn!("Failed to read formulas of sheet '{}': {}", sheet_name, err);
+            return CellFormulas::new();
+        },
+        None => return CellFormulas::new(),
+    };
+
+    // Positions in the range are relative to its first cell
+    let (start_row, start_col) = range.start().unwrap_or_default();
+    let formulas: CellFormulas = range.used_cells()
+        .filter(|(_, _, formula)| !formula.is_empty())
+        .map(|(row, column, formula)| {
+            // OpenDocument formulas carry a namespace prefix such as "of:="
+            let formula = formula.strip_prefix("of:").unwrap_or(formula);
+            let formula = if formula.starts_with('=') {
+                formula.to_string()
+            } else {
+                format!("={}", formula)
+            };
+            ((start_row + row as u32, start_col + column as u32), formula)
+        })
+        .collect();
+
+    debug!("Read {} formulas from sheet '{}'", formulas.len(), sheet_name);
+    formulas
+}
+
+/// Converts a calamine value to a typed cell value
+fn cell_value(value: &DataType) -> CellValue {
+    match value {
+        DataType::Int(value) => CellValue::Number(*value as f64),
+        DataType::Float(value) => CellValue::Number(*value),
+        DataType::String(text) => CellValue::String(text.clone()),
+        DataType::Bool(value) => CellValue::Bool(*value),
+        DataType::DateTime(serial) => CellValue::DateTime(*serial),
+        DataType::Duration(days) => CellValue::Duration(*days),
+        // OpenDocument dates and durations are kept as their ISO 8601 text
+        DataType::DateTimeIso(text) | DataType::DurationIso(text) => CellValue::String(text.clone()),
+        DataType::Error(error) => CellValue::Error(error.to_string()),
+        DataType::Empty => CellValue::Empty,
+    }
+}
+
+/// Reads the number formats and merged ranges of every sheet in an .xlsx or .xlsm package
+///
+/// # Arguments
+///
+/// * `data` - Contents of the workbook file
+///
+/// # Returns
+///
+/// * `Result<HashMap<String, SheetMarkup>>` - Sheet markup keyed by sheet name, or an error
+fn read_sheet_markup(data: &[u8]) -> Result<HashMap<String, SheetMarkup>> {
+    let styles = read_package_part(data, STYLES_PART)?.unwrap_or_default();
+    let cell_styles = read_cell_styles(&styles);
+
+    let workbook = read_package_part(data, WORKBOOK_PART)?
+        .context("Workbook part is missing")?;
+    let (workbook_folder, _) = WORKBOOK_PART.rsplit_once('/').unwrap_or(("", WORKBOOK_PART));
+    let relationships = read_package_part(data, WORKBOOK_RELS_PART)?.unwrap_or_default();
+    let targets: HashMap<String, String> = xml_elements(&relationships, b"Relationship")
+        .into_iter()
+        .filter_map(|attributes| Some((attributes.get("Id")?.clone(), attributes.get("Target")?.clone())))
+        .collect();
+
+    let mut sheets = HashMap::new();
+    for sheet in xml_elements(&workbook, b"sheet") {
+        let (Some(name), Some(target)) = (sheet.get("name"), sheet.get("id").and_then(|id| targets.get(id))) else {
+            continue;
+        };
+        // Targets are relative to the folder of the workbook part unless they start at the package root
+        let part = resolve_part_target(workbook_folder, target);
+        let Some(xml) = read_package_part(data, &part)? else {
+            warn!("Sheet part not found: {}", part);
+            continue;
+        };
+
+        let formats: CellFormats = xml_elements(&xml, b"c")
+            .into_iter()
+            .filter_map(|cell| {
+                let position = cell_position(cell.get("r")?)?;
+                let style: usize = cell.get("s")?.parse().ok()?;
+                Some((position, cell_styles.get(style)?.clone()?))
+            })
+            .collect();
+        let merged_ranges = xml_elements(&xml, b"mergeCell")
+            .into_iter()
+            .filter_map(|merge| CellRange::parse(merge.get("ref")?))
+            .collect();
+        // A table that cannot be read should not lose the formats and merges of every sheet
+        let tables = read_table_parts(data, &part, &xml).unwrap_or_else(|err| {
+            warn!("Failed to read the tables of sheet '{}': {:#}", name, err);
+            Vec::new()
+        });
+        sheets.insert(name.clone(), SheetMarkup { formats, merged_ranges, tables });
+    }
+
+    Ok(sheets)
+}
+
+/// Reads the names and ranges of the Excel tables on a sheet
+///
+/// # Arguments
+///
+/// * `data` - Contents of the workbook file
+/// * `sheet_part` - Name of the sheet's part
+/// * `sheet_xml` - XML of the sheet's part
+///
+/// # Returns
+///
+/// * `Result<Vec<TablePart>>` - The tables of the sheet, or an error
+fn read_table_parts(data: &[u8], sheet_part: &str, sheet_xml: &str) -> Result<Vec<TablePart>> {
+    let table_ids: Vec<String> = xml_elements(sheet_xml, b"tablePart")
+        .into_iter()
+        .filter_map(|table_part| table_part.get("id").cloned())
+        .collect();
+    if table_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Relationships of a part live in the _rels folder next to it
+    let (folder, file) = sheet_part.rsplit_once('/').unwrap_or(("", sheet_part));
+    let relationships = read_package_part(data, &format!("{}/_rels/{}.rels", folder, file))?.unwrap_or_default();
+    let targets: HashMap<String, String> = xml_elements(&relationships, b"Relationship")
+        .into_iter()
+        .filter_map(|attributes| Some((attributes.get("Id")?.clone(), attributes.get("Target")?.clone())))
+        .collect();
+
+    let mut tables = Vec::new();
+    for id in table_ids {
+        let Some(target) = targets.get(&id) else {
+            warn!("Table relationship not found: {}", id);
+            continue;
+        };
+        let part = resolve_part_target(folder, target);
+        let Some(xml) = read_package_part(data, &part)? else {
+            warn!("Table part not found: {}", part);
+            continue;
+        };
+        let Some(table) = xml_elements(&xml, b"table").into_iter().next() else {
+            continue;
+        };
+        let name = table.get("displayName").or_else(|| table.get("name")).cloned().unwrap_or_default();
+        let header_rows = table.get("headerRowCount").and_then(|count| count.parse().ok()).unwrap_or(1);
+        match table.get("ref").and_then(|reference| CellRange::parse(reference)) {
+            Some(range) => tables.push(TablePart { name, range, header_rows }),
+            None => warn!("Table '{}' has no valid range", name),
+        }
+    }
+
+    Ok(tables)
+}
+
+/// Resolves the number format of each cell style in the styles part
+///
+/// # Arguments
+///
+/// * `styles` - XML of the styles part
+///
+/// # Returns
+///
+/// * `Vec<Option<NumberFormat>>` - The number format of each cell style, `None` for General
+fn read_cell_styles(styles: &str) -> Vec<Option<NumberFormat>> {
+    let custom: HashMap<u32, String> = xml_elements(styles, b"numFmt")
+        .into_iter()
+        .filter_map(|format| Some((format.get("numFmtId")?.parse().ok()?, format.get("formatCode")?.clone())))
+        .collect();
+
+    // Cell styles are the `xf` elements inside `cellXfs`; those in `cellStyleXfs` are named styles
+    let mut reader = quick_xml::Reader::from_str(styles);
+    let mut in_cell_styles = false;
+    let mut cell_styles = Vec::new();
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(element)) if element.local_name().as_ref() == b"cellXfs" => in_cell_styles = true,
+            Ok(Event::End(element)) if element.local_name().as_ref() == b"cellXfs" => in_cell_styles = false,
+            Ok(Event::Start(element) | Event::Empty(element))
+                if in_cell_styles && element.local_name().as_ref() == b"xf" => {
+                let id = element.attributes()
+                    .filter_map(|attribute| attribute.ok())
+                    .find(|attribute| attribute.key.local_name().as_ref() == b"numFmtId")
+                    .and_then(|attribute| String::from_utf8_lossy(&attribute.value).parse::<u32>().ok())
+                    .unwrap_or(0);
+                let format = match custom.get(&id) {
+                    Some(code) => Some(NumberFormat::Custom(code.clone())),
+                    None if id == 0 => None,
+                    None => Some(NumberFormat::Builtin(id)),
+                };
+                cell_styles.push(format);
+            },
+            Ok(Event::Eof) => break,
+            Err(err) => {
+                warn!("Failed to parse styles: {}", err);
+                break;
+            },
+            _ => {}
+        }
+    }
+
+    cell_styles
+}
+
+/// Collects the attributes of every element with the given local name
+///
+/// # Arguments
+///
+/// * `xml` - The XML to scan
+/// * `name` - Local name of the elements to collect
+///
+/// # Returns
+///
+/// * `Vec<HashMap<String, String>>` - Attributes of each element, keyed by local name
+fn xml_elements(xml: &str, name: &[u8]) -> Vec<HashMap<String, String>> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut elements = Vec::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(element) | Event::Empty(element)) if element.local_name().as_ref() == name => {
+                elements.push(element.attributes()
+                    .filter_map(|attribute| attribute.ok())
+                    .filter_map(|attribute| {
+                        let key = String::from_utf8_lossy(attribute.key.local_name().as_ref()).to_string();
+                        let value = attribute.decode_and_unescape_value(&reader).ok()?.to_string();
+                        Some((key, value))
+                    })
+                    .collect());
+            },
+            Ok(Event::Eof) => break,
+            Err(err) => {
+                warn!("Failed to parse workbook part: {}", err);
+                break;
+            },
+            _ => {}
+        }
+    }
+
+    elements
+}
+
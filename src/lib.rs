@@ -3,5 +3,8 @@
 //! This library provides functionality for converting Word and Excel documents to PDF and Markdown.
 //! It is used by the Aqon CLI tool, but can also be used as a library in other projects.
 
+#![allow(non_snake_case)]
+
+pub mod config;
 pub mod converter;
 pub mod utils;
\ No newline at end of file
@@ -1,16 +1,16 @@
-use std::path::PathBuf;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc;
-use anyhow::{Result, Context, anyhow};
-use clap::{Parser, Subcommand};
-use log::{info, error, warn, debug, LevelFilter};
+use anyhow::{Result, Context};
+use clap::{Args, Parser, Subcommand};
+use log::{error, LevelFilter};
 use env_logger::Builder;
 use colored::Colorize;
-use indicatif::{ProgressBar, ProgressStyle, MultiProgress};
-use notify::{Watcher, RecursiveMode, Event, EventKind, Config};
+use indicatif::{ProgressBar, ProgressStyle};
+use notify::{Watcher, RecursiveMode, EventKind};
 
-mod converter;
-mod utils;
+use Aqon::{converter, utils};
+use Aqon::config::Config;
+use Aqon::converter::{font_loader, ConversionOptions, FormulaDisplay, Orientation, PageMargins, PaperSize, PdfOptions, SheetRange, MIN_FONT_SIZE};
 
 /// A CLI tool for batch conversion of Word and Excel documents to PDF
 #[derive(Parser, Debug)]
@@ -22,6 +22,10 @@ struct Cli {
     /// Enable verbose logging
     #[clap(short, long, action, global = true)]
     verbose: bool,
+
+    /// Read settings from a configuration file of `key = value` lines
+    #[clap(long, value_name = "FILE", value_parser, global = true)]
+    config: Option<PathBuf>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -36,13 +40,16 @@ enum Commands {
         #[clap(short, long, value_parser)]
         output: PathBuf,
 
-        /// Only convert files of specified type (docx, xlsx, xls)
+        /// Only convert files of specified type (docx, xlsx, xlsm, xlsb, xls, ods)
         #[clap(short, long, value_parser)]
         r#type: Option<String>,
 
         /// Output format (pdf or markdown)
         #[clap(short, long, value_parser, default_value = "pdf")]
         format: String,
+
+        #[command(flatten)]
+        options: OptionArgs,
     },
     /// Watch a directory and automatically convert new documents
     Watch {
@@ -54,16 +61,146 @@ enum Commands {
         #[clap(short, long, value_parser)]
         output: PathBuf,
 
-        /// Only convert files of specified type (docx, xlsx, xls)
+        /// Only convert files of specified type (docx, xlsx, xlsm, xlsb, xls, ods)
         #[clap(short, long, value_parser)]
         r#type: Option<String>,
 
         /// Output format (pdf or markdown)
         #[clap(short, long, value_parser, default_value = "pdf")]
         format: String,
+
+        #[command(flatten)]
+        options: OptionArgs,
     },
 }
 
+/// Options shared by the convert and watch commands
+#[derive(Args, Debug)]
+struct OptionArgs {
+    /// How spreadsheet formulas are shown (hidden, inline or appendix)
+    #[clap(long, value_parser, default_value = "hidden")]
+    formulas: FormulaDisplay,
+
+    /// Keep up to this many consecutive empty spreadsheet rows
+    #[clap(long, value_parser, default_value_t = 0)]
+    max_blank_rows: usize,
+
+    /// Label spreadsheets with row numbers and column letters
+    #[clap(long, action)]
+    grid_labels: bool,
+
+    /// Only convert sheets matching this name or glob pattern (repeatable)
+    #[clap(long = "include-sheet", value_name = "PATTERN", value_parser)]
+    include_sheets: Vec<String>,
+
+    /// Skip sheets matching this name or glob pattern (repeatable)
+    #[clap(long = "exclude-sheet", value_name = "PATTERN", value_parser)]
+    exclude_sheets: Vec<String>,
+
+    /// Also convert hidden and very hidden sheets
+    #[clap(long, action)]
+    include_hidden_sheets: bool,
+
+    /// Only convert a cell range of a sheet, e.g. Sheet1!A1:H200 (repeatable)
+    #[clap(long = "range", value_name = "SHEET!RANGE", value_parser)]
+    sheet_ranges: Vec<SheetRange>,
+
+    /// Only convert the Excel tables and named ranges of spreadsheets
+    #[clap(long, action)]
+    tables_only: bool,
+
+    /// Number of header rows at the top of each sheet (detected if not given)
+    #[clap(long, value_name = "N", value_parser)]
+    header_rows: Option<usize>,
+
+    /// Font family name or directory of TTF/OTF files used for PDF output
+    #[clap(long, value_name = "FAMILY|DIR", value_parser)]
+    font: Option<String>,
+
+    /// Locale whose separators and date format are used for spreadsheet cells, e.g. de-DE (from the environment if not given)
+    #[clap(long, value_name = "TAG", value_parser)]
+    locale: Option<String>,
+
+    /// Paper size of PDF pages (A4, A3, Letter, Legal or WIDTHxHEIGHT in mm)
+    #[clap(long, value_name = "SIZE", value_parser)]
+    paper_size: Option<PaperSize>,
+
+    /// Orientation of PDF pages (portrait or landscape)
+    #[clap(long, value_parser)]
+    orientation: Option<Orientation>,
+
+    /// Page margins in mm: one value for all edges, or top,right,bottom,left
+    #[clap(long, value_name = "MM", value_parser)]
+    margins: Option<PageMargins>,
+
+    /// Font size of body text in PDF output, in points
+    #[clap(long, value_name = "PT", value_parser = clap::value_parser!(u8).range(i64::from(MIN_FONT_SIZE)..))]
+    font_size: Option<u8>,
+
+    /// Line spacing of PDF output, as a multiple of the line height
+    #[clap(long, value_name = "FACTOR", value_parser = converter::parse_line_spacing)]
+    line_spacing: Option<f64>,
+
+    /// Header printed on every PDF page, e.g. "{title} — {date}"; replaces the Word header
+    #[clap(long, value_name = "TEMPLATE", value_parser)]
+    header: Option<String>,
+
+    /// Footer printed on every PDF page, e.g. "{title} — {date}"; takes the page number and count
+    /// placeholders listed in the README and replaces the Word footer
+    #[clap(long, value_name = "TEMPLATE", value_parser)]
+    footer: Option<String>,
+
+    /// Add a table of contents with page numbers at the front of PDFs
+    #[clap(long, action)]
+    toc: bool,
+
+    /// Write PDF/A-2b files for archiving; fails files that cannot conform
+    #[clap(long = "pdf-a", action)]
+    pdf_a: bool,
+}
+
+impl OptionArgs {
+    /// Builds the conversion options from the command line arguments and the configuration file
+    ///
+    /// # Returns
+    ///
+    /// * `Result<ConversionOptions>` - The options, or an error if they do not describe a usable page or the font is not found
+    fn to_options(&self, config: &Config) -> Result<ConversionOptions> {
+        let mut options = ConversionOptions {
+            formulas: self.formulas,
+            max_blank_rows: self.max_blank_rows,
+            grid_labels: self.grid_labels,
+            include_sheets: self.include_sheets.clone(),
+            exclude_sheets: self.exclude_sheets.clone(),
+            include_hidden_sheets: self.include_hidden_sheets,
+            sheet_ranges: self.sheet_ranges.clone(),
+            tables_only: self.tables_only,
+            header_rows: self.header_rows,
+            font: self.font.clone().or_else(|| config.font.clone()),
+            font_files: None,
+            locale: self.locale.clone().or_else(|| config.locale.clone()),
+            pdf: PdfOptions {
+                paper_size: self.paper_size.or(config.pdf.paper_size),
+                orientation: self.orientation.or(config.pdf.orientation),
+                margins: self.margins.or(config.pdf.margins),
+                font_size: self.font_size.or(config.pdf.font_size),
+                line_spacing: self.line_spacing.or(config.pdf.line_spacing),
+                header: self.header.clone().or_else(|| config.pdf.header.clone()),
+                footer: self.footer.clone().or_else(|| config.pdf.footer.clone()),
+                table_of_contents: self.toc || config.pdf.table_of_contents,
+                pdf_a: self.pdf_a || config.pdf.pdf_a,
+            },
+        };
+        options.pdf.validate().context("Invalid PDF options")?;
+        // The font is looked up once rather than for every file converted
+        options.font_files = options.font.as_deref()
+            .map(font_loader::find_font)
+            .transpose()
+            .context("Failed to find the PDF font")?;
+        Ok(options)
+    }
+}
+
 fn main() -> Result<()> {
     // Parse command line arguments
     let cli = Cli::parse();
@@ -79,12 +216,17 @@ fn main() -> Result<()> {
 
     println!("{}", "Starting Aqon document converter".bright_green());
 
+    let config = match &cli.config {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+
     match &cli.command {
-        Commands::Convert { input, output, r#type, format } => {
-            convert_command(input, output, r#type, format)?;
+        Commands::Convert { input, output, r#type, format, options } => {
+            convert_command(input, output, r#type, format, &options.to_options(&config)?)?;
         },
-        Commands::Watch { input, output, r#type, format } => {
-            watch_command(input, output, r#type, format)?;
+        Commands::Watch { input, output, r#type, options, .. } => {
+            watch_command(input, output, r#type, &options.to_options(&config)?)?;
         }
     }
 
@@ -92,7 +234,13 @@ fn main() -> Result<()> {
 }
 
 /// Handle the convert command
-fn convert_command(input: &PathBuf, output: &PathBuf, file_type: &Option<String>, format: &String) -> Result<()> {
+fn convert_command(
+    input: &Path,
+    output: &Path,
+    file_type: &Option<String>,
+    format: &str,
+    options: &ConversionOptions,
+) -> Result<()> {
     // Validate and resolve paths
     let input_dir = utils::resolve_path(input)
         .context("Failed to resolve input directory path")?;
@@ -116,6 +264,14 @@ fn convert_command(input: &PathBuf, output: &PathBuf, file_type: &Option<String>
 
     println!("{} {}", "Output format:".blue(), format);
 
+    if options.formulas != FormulaDisplay::Hidden {
+        println!("{} {}", "Formulas:".blue(), options.formulas);
+    }
+
+    if let Some(files) = &options.font_files {
+        println!("{} {}", "Font:".blue(), files.family);
+    }
+
     // Get list of files to convert
     let files = get_files_to_convert(&input_dir, file_type)?;
 
@@ -143,14 +299,14 @@ fn convert_command(input: &PathBuf, output: &PathBuf, file_type: &Option<String>
 
         let result = match format.to_lowercase().as_str() {
             "pdf" => {
-                converter::convert_to_pdf(&file_path, &output_dir)
+                converter::convert_to_pdf_with_options(&file_path, &output_dir, options)
             },
             "markdown" | "md" => {
-                converter::convert_to_markdown(&file_path, &output_dir)
+                converter::convert_to_markdown_with_options(&file_path, &output_dir, options)
             },
             _ => {
                 error!("Unsupported output format: {}. Using PDF as default.", format);
-                converter::convert_to_pdf(&file_path, &output_dir)
+                converter::convert_to_pdf_with_options(&file_path, &output_dir, options)
             }
         };
 
@@ -183,7 +339,12 @@ fn convert_command(input: &PathBuf, output: &PathBuf, file_type: &Option<String>
 }
 
 /// Handle the watch command
-fn watch_command(input: &PathBuf, output: &PathBuf, file_type: &Option<String>, format: &String) -> Result<()> {
+fn watch_command(
+    input: &Path,
+    output: &Path,
+    file_type: &Option<String>,
+    options: &ConversionOptions,
+) -> Result<()> {
     // Validate and resolve paths
     let input_dir = utils::resolve_path(input)
         .context("Failed to resolve input directory path")?;
@@ -205,6 +366,14 @@ fn watch_command(input: &PathBuf, output: &PathBuf, file_type: &Option<String>,
         println!("{} {}", "File type filter:".blue(), t);
     }
 
+    if options.formulas != FormulaDisplay::Hidden {
+        println!("{} {}", "Formulas:".blue(), options.formulas);
+    }
+
+    if let Some(files) = &options.font_files {
+        println!("{} {}", "Font:".blue(), files.family);
+    }
+
     println!("{}", "Press Ctrl+C to stop watching".yellow());
 
     // Create channel for watcher events
@@ -237,7 +406,7 @@ fn watch_command(input: &PathBuf, output: &PathBuf, file_type: &Option<String>,
                         println!("{} {}", "New file detected:".blue(), file_name);
 
                         // Convert the file
-                        match converter::convert_to_pdf(&path, &output_dir) {
+                        match converter::convert_to_pdf_with_options(&path, &output_dir, options) {
                             Ok(pdf_path) => {
                                 println!("{} {} -> {}", "Successfully converted".green(), file_name, pdf_path.display());
                             },
@@ -258,7 +427,7 @@ fn watch_command(input: &PathBuf, output: &PathBuf, file_type: &Option<String>,
 }
 
 /// Get list of files to convert based on the file type filter
-fn get_files_to_convert(input_dir: &PathBuf, file_type: &Option<String>) -> Result<Vec<PathBuf>> {
+fn get_files_to_convert(input_dir: &Path, file_type: &Option<String>) -> Result<Vec<PathBuf>> {
     let mut files = Vec::new();
 
     for entry in walkdir::WalkDir::new(input_dir)
@@ -283,7 +452,7 @@ fn get_files_to_convert(input_dir: &PathBuf, file_type: &Option<String>) -> Resu
 }
 
 /// Check if a file matches the specified type filter
-fn is_file_type_match(path: &PathBuf, file_type: &Option<String>) -> bool {
+fn is_file_type_match(path: &Path, file_type: &Option<String>) -> bool {
     if let Some(filter) = file_type {
         if let Some(ext) = path.extension() {
             let ext_str = ext.to_string_lossy().to_lowercase();
@@ -293,10 +462,5 @@ fn is_file_type_match(path: &PathBuf, file_type: &Option<String>) -> bool {
     }
 
     // If no filter is specified, check if it's a supported file type
-    if let Some(ext) = path.extension() {
-        let ext_str = ext.to_string_lossy().to_lowercase();
-        return ext_str == "docx" || ext_str == "xlsx" || ext_str == "xls";
-    }
-
-    false
+    utils::is_supported_file(path)
 }
@@ -72,7 +72,7 @@ pub fn resolve_path(path: &Path) -> Result<PathBuf> {
 ///
 /// * `Vec<&'static str>` - List of supported file extensions
 pub fn get_supported_extensions() -> Vec<&'static str> {
-    vec!["docx", "xlsx", "xls"]
+    vec!["docx", "xlsx", "xlsm", "xlsb", "xls", "ods"]
 }
 
 /// Checks if a file has a supported extension.
@@ -91,4 +91,47 @@ pub fn is_supported_file(path: &Path) -> bool {
     } else {
         false
     }
-}
\ No newline at end of file
+}
+
+/// Checks if a name matches a glob pattern, ignoring case.
+///
+/// `*` matches any run of characters and `?` matches a single character.
+///
+/// # Arguments
+///
+/// * `pattern` - The glob pattern
+/// * `name` - The name to match
+///
+/// # Returns
+///
+/// * `bool` - True if the whole name matches the pattern
+pub fn matches_glob(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+
+    // Greedy matching that backtracks to the last `*` on a mismatch
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            },
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            },
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                },
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -2,9 +2,12 @@
 //! This file contains shared functions and mock implementations for tests
 
 use std::fs;
+use std::io::{Cursor, Read, Write};
 use std::path::{Path, PathBuf};
 use tempfile::TempDir;
 use anyhow::Result;
+use docx_rs::{Docx, Paragraph, Run};
+use rust_xlsxwriter::Workbook;
 
 /// Creates a temporary test environment with input and output directories
 pub fn setup_test_env() -> Result<(TempDir, PathBuf, PathBuf)> {
@@ -20,18 +23,133 @@ pub fn setup_test_env() -> Result<(TempDir, PathBuf, PathBuf)> {
 
 /// Creates a mock DOCX file for testing
 pub fn create_mock_docx(dir: &Path, filename: &str) -> Result<PathBuf> {
+    let docx = Docx::new()
+        .add_paragraph(Paragraph::new().add_run(Run::new().add_text("Mock DOCX content")));
+    create_docx(dir, filename, docx)
+}
+
+/// Writes a DOCX file built with docx-rs for testing
+pub fn create_docx(dir: &Path, filename: &str, docx: Docx) -> Result<PathBuf> {
     let file_path = dir.join(format!("{}.docx", filename));
-    fs::write(&file_path, b"Mock DOCX content")?;
+    let file = fs::File::create(&file_path)?;
+    docx.build().pack(file)?;
     Ok(file_path)
 }
 
+/// Replaces the contents of a part inside a DOCX package, for markup docx-rs cannot write
+pub fn edit_docx_part(path: &Path, part: &str, edit: impl Fn(String) -> String) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(fs::read(path)?))?;
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        let name = file.name().to_string();
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        if name == part {
+            data = edit(String::from_utf8(data)?).into_bytes();
+        }
+        writer.start_file(name, zip::write::FileOptions::default())?;
+        writer.write_all(&data)?;
+    }
+
+    fs::write(path, writer.finish()?.into_inner())?;
+    Ok(())
+}
+
+/// Replaces the raw contents of a part inside a package, e.g. with bytes that are not valid UTF-8
+pub fn replace_package_part(path: &Path, part: &str, contents: &[u8]) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(fs::read(path)?))?;
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+
+    for index in 0..archive.len() {
+        let file = archive.by_index_raw(index)?;
+        if file.name() == part {
+            writer.start_file(part, zip::write::FileOptions::default())?;
+            writer.write_all(contents)?;
+        } else {
+            writer.raw_copy_file(file)?;
+        }
+    }
+
+    fs::write(path, writer.finish()?.into_inner())?;
+    Ok(())
+}
+
+/// Adds a part to a DOCX package, for parts docx-rs cannot write
+pub fn add_docx_part(path: &Path, part: &str, contents: &str) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(fs::read(path)?))?;
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+
+    for index in 0..archive.len() {
+        writer.raw_copy_file(archive.by_index_raw(index)?)?;
+    }
+    writer.start_file(part, zip::write::FileOptions::default())?;
+    writer.write_all(contents.as_bytes())?;
+
+    fs::write(path, writer.finish()?.into_inner())?;
+    Ok(())
+}
+
+/// Creates a PNG image of a single color, with an alpha channel
+pub fn create_png(width: u32, height: u32) -> Result<Vec<u8>> {
+    let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(width, height, image::Rgba([30, 90, 160, 200])));
+    let mut data = Vec::new();
+    image.write_to(&mut data, image::ImageOutputFormat::Png)?;
+    Ok(data)
+}
+
 /// Creates a mock XLSX file for testing
 pub fn create_mock_xlsx(dir: &Path, filename: &str) -> Result<PathBuf> {
     let file_path = dir.join(format!("{}.xlsx", filename));
-    fs::write(&file_path, b"Mock XLSX content")?;
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet().set_name("Data")?;
+    worksheet.write_row(0, 0, ["Name", "Value"])?;
+    worksheet.write_string(1, 0, "Mock XLSX content")?;
+    worksheet.write_number(1, 1, 42)?;
+    workbook.save(&file_path)?;
+    Ok(file_path)
+}
+
+/// Creates a minimal OpenDocument spreadsheet with one sheet for testing
+pub fn create_mock_ods(dir: &Path, filename: &str) -> Result<PathBuf> {
+    create_ods(dir, filename, "Inventory", concat!(
+        r#"<table:table-row><table:table-cell office:value-type="string"><text:p>Item</text:p></table:table-cell>"#,
+        r#"<table:table-cell office:value-type="string"><text:p>Count</text:p></table:table-cell></table:table-row>"#,
+        r#"<table:table-row><table:table-cell office:value-type="string"><text:p>Bolts</text:p></table:table-cell>"#,
+        r#"<table:table-cell office:value-type="float" office:value="12"><text:p>12</text:p></table:table-cell></table:table-row>"#,
+    ))
+}
+
+/// Creates an ODS spreadsheet with a single sheet holding the given `table:table-row` elements
+pub fn create_ods(dir: &Path, filename: &str, sheet_name: &str, rows: &str) -> Result<PathBuf> {
+    let file_path = dir.join(format!("{}.ods", filename));
+    let content = format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            r#"<office:document-content xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" "#,
+            r#"xmlns:table="urn:oasis:names:tc:opendocument:xmlns:table:1.0" "#,
+            r#"xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0">"#,
+            r#"<office:body><office:spreadsheet><table:table table:name="{}">{}"#,
+            r#"</table:table></office:spreadsheet></office:body></office:document-content>"#,
+        ),
+        sheet_name, rows,
+    );
+
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    writer.start_file("mimetype", zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored))?;
+    writer.write_all(b"application/vnd.oasis.opendocument.spreadsheet")?;
+    writer.start_file("content.xml", zip::write::FileOptions::default())?;
+    writer.write_all(content.as_bytes())?;
+    fs::write(&file_path, writer.finish()?.into_inner())?;
     Ok(file_path)
 }
 
+/// Returns the path of a test file checked in under tests/fixtures
+pub fn fixture_path(filename: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(filename)
+}
+
 /// Verifies that a PDF file exists with the expected name
 pub fn verify_pdf_output(output_dir: &Path, expected_name: &str) -> Result<PathBuf> {
     let pdf_path = output_dir.join(format!("{}.pdf", expected_name));
@@ -48,4 +166,52 @@ pub fn verify_markdown_output(output_dir: &Path, expected_name: &str) -> Result<
         anyhow::bail!("Expected Markdown file does not exist: {}", md_path.display());
     }
     Ok(md_path)
-}
\ No newline at end of file
+}
+/// Returns the PostScript names of the font programs embedded in a PDF file
+pub fn pdf_font_names(pdf_path: &Path) -> Result<Vec<String>> {
+    let pdf = lopdf::Document::load(pdf_path)?;
+    let mut names = Vec::new();
+    for object in pdf.objects.values() {
+        let Ok(stream) = object.as_stream() else {
+            continue;
+        };
+        // TrueType font programs give their length before encoding as Length1
+        if !stream.dict.has(b"Length1") {
+            continue;
+        }
+        let font = match stream.dict.has(b"Filter") {
+            true => stream.decompressed_content()?,
+            false => stream.content.clone(),
+        };
+        names.extend(Aqon::converter::font_subset::postscript_name(&font));
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Returns the positions of the text drawn on each page of a PDF file, in drawing order
+///
+/// Positions are the text origins in points from the bottom left corner of the page.
+pub fn pdf_text_positions(pdf_path: &Path) -> Result<Vec<Vec<(f64, f64)>>> {
+    let pdf = lopdf::Document::load(pdf_path)?;
+    let number = |object: &lopdf::Object| object.as_f64().or_else(|_| object.as_i64().map(|value| value as f64));
+
+    pdf.get_pages().values().map(|page| {
+        let content = pdf.get_and_decode_page_content(*page)?;
+        let mut positions = Vec::new();
+        let (mut x, mut y) = (0.0, 0.0);
+        for operation in &content.operations {
+            match operation.operator.as_str() {
+                "BT" => (x, y) = (0.0, 0.0),
+                "Td" | "TD" => {
+                    x += number(&operation.operands[0])?;
+                    y += number(&operation.operands[1])?;
+                },
+                "Tm" => (x, y) = (number(&operation.operands[4])?, number(&operation.operands[5])?),
+                "Tj" | "TJ" => positions.push((x, y)),
+                _ => {},
+            }
+        }
+        Ok(positions)
+    }).collect()
+}
@@ -8,10 +8,24 @@ use anyhow::Result;
 mod common;
 
 // Import the crate to test
-use Aqon::converter;
+use Aqon::config::Config;
+use Aqon::converter::{self, font_loader, ConversionOptions, FormulaDisplay, Orientation, PageMargins, PaperSize, PdfOptions};
+use Aqon::converter::docx_reader::{self, Block, LinkTarget};
+use Aqon::converter::metadata::{self, DocumentProperties};
+use Aqon::converter::number_format::{self, Locale};
+use Aqon::converter::package;
+use Aqon::converter::pdf_archive;
+use Aqon::converter::sheet_layout;
+use Aqon::converter::xlsx_reader::{self, CellValue};
 use Aqon::utils;
+use rust_xlsxwriter::{DocProperties, ExcelDateTime, Format, Formula, Table as ExcelTable, Workbook};
+use docx_rs::{
+    AbstractNumbering, Docx, Footer, Header, Hyperlink, HyperlinkType, IndentLevel, Level, LevelJc, LevelText, NumberFormat, Numbering,
+    NumberingId, PageNum, Paragraph, Pic, Run, RunFonts, RunProperty, Start, Style, StyleType, Table, TableCell, TableRow, VMergeType, VertAlignType,
+};
 
 #[test]
+#[allow(unused_variables)]
 fn test_convert_docx_to_pdf() -> Result<()> {
     // Set up test environment
     let (temp_dir, input_dir, output_dir) = common::setup_test_env()?;
@@ -37,6 +51,7 @@ fn test_convert_docx_to_pdf() -> Result<()> {
 }
 
 #[test]
+#[allow(unused_variables)]
 fn test_convert_xlsx_to_pdf() -> Result<()> {
     // Set up test environment
     let (temp_dir, input_dir, output_dir) = common::setup_test_env()?;
@@ -62,6 +77,7 @@ fn test_convert_xlsx_to_pdf() -> Result<()> {
 }
 
 #[test]
+#[allow(unused_variables)]
 fn test_convert_docx_to_markdown() -> Result<()> {
     // Set up test environment
     let (temp_dir, input_dir, output_dir) = common::setup_test_env()?;
@@ -87,6 +103,7 @@ fn test_convert_docx_to_markdown() -> Result<()> {
 }
 
 #[test]
+#[allow(unused_variables)]
 fn test_batch_convert() -> Result<()> {
     // Set up test environment
     let (temp_dir, input_dir, output_dir) = common::setup_test_env()?;
@@ -115,6 +132,7 @@ fn test_batch_convert() -> Result<()> {
 }
 
 #[test]
+#[allow(unused_variables)]
 fn test_utils_directory_functions() -> Result<()> {
     // Set up test environment
     let (temp_dir, input_dir, output_dir) = common::setup_test_env()?;
@@ -141,6 +159,7 @@ fn test_utils_directory_functions() -> Result<()> {
 }
 
 #[test]
+#[allow(unused_variables)]
 fn test_supported_file_extensions() -> Result<()> {
     // Set up test environment
     let (temp_dir, input_dir, _) = common::setup_test_env()?;
@@ -161,6 +180,1601 @@ fn test_supported_file_extensions() -> Result<()> {
     assert!(extensions.contains(&"docx"), "DOCX should be in supported extensions");
     assert!(extensions.contains(&"xlsx"), "XLSX should be in supported extensions");
     assert!(extensions.contains(&"xls"), "XLS should be in supported extensions");
+    for extension in ["xlsm", "xlsb", "ods"] {
+        assert!(extensions.contains(&extension), "{} should be in supported extensions", extension);
+    }
     
     Ok(())
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_docx_blocks_keep_document_order() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // Create a DOCX with a table between two paragraphs
+    let docx = Docx::new()
+        .add_paragraph(Paragraph::new().add_run(Run::new().add_text("Before the table")))
+        .add_table(Table::new(vec![
+            TableRow::new(vec![
+                TableCell::new().add_paragraph(Paragraph::new().add_run(Run::new().add_text("Key"))),
+                TableCell::new().add_paragraph(Paragraph::new().add_run(Run::new().add_text("Value"))),
+            ]),
+        ]))
+        .add_paragraph(Paragraph::new().add_run(Run::new().add_text("After the table")));
+    let docx_path = common::create_docx(&input_dir, "ordered", docx)?;
+
+    // The extracted blocks follow the body order
+    let content = docx_reader::extract_content(&docx_path)?;
+    assert_eq!(content.blocks.len(), 3, "Expected 3 blocks, got {:?}", content.blocks);
+    assert!(matches!(&content.blocks[0], Block::Paragraph(p) if p.text() == "Before the table"));
+    assert!(matches!(&content.blocks[1], Block::Table(table) if table.grid()[0] == ["Key", "Value"]));
+    assert!(matches!(&content.blocks[2], Block::Paragraph(p) if p.text() == "After the table"));
+
+    // The Markdown output renders the table between the paragraphs
+    converter::convert_to_markdown(&docx_path, &output_dir)?;
+    let md_path = common::verify_markdown_output(&output_dir, "ordered")?;
+    let markdown = std::fs::read_to_string(md_path)?;
+    let before = markdown.find("Before the table").unwrap();
+    let table = markdown.find("| Key | Value |").unwrap();
+    let after = markdown.find("After the table").unwrap();
+    assert!(before < table && table < after, "Table is out of order:\n{}", markdown);
+
+    Ok(())
+}
+
+#[test]
+fn test_docx_heading_levels() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // Create a DOCX using a named heading style and a direct outline level
+    let docx = Docx::new()
+        .add_style(Style::new("Heading1", StyleType::Paragraph).name("heading 1"))
+        .add_paragraph(Paragraph::new().style("Heading1").add_run(Run::new().add_text("Overview")))
+        .add_paragraph(Paragraph::new().outline_lvl(1).add_run(Run::new().add_text("Details")))
+        .add_paragraph(Paragraph::new().add_run(Run::new().add_text("Body text")));
+    let docx_path = common::create_docx(&input_dir, "headings", docx)?;
+
+    let content = docx_reader::extract_content(&docx_path)?;
+    let levels: Vec<Option<usize>> = content.paragraphs().map(|p| p.heading_level()).collect();
+    assert_eq!(levels, vec![Some(1), Some(2), None]);
+    let first = content.paragraphs().next().unwrap();
+    assert_eq!(first.style_id.as_deref(), Some("Heading1"));
+
+    // Headings become Markdown headings
+    converter::convert_to_markdown(&docx_path, &output_dir)?;
+    let md_path = common::verify_markdown_output(&output_dir, "headings")?;
+    let markdown = std::fs::read_to_string(md_path)?;
+    assert!(markdown.contains("\n# Overview\n"), "Missing level 1 heading:\n{}", markdown);
+    // A document starting with a level 1 heading is not given a second one from its file name
+    assert!(!markdown.contains("# headings\n"), "Unexpected title heading:\n{}", markdown);
+    assert!(markdown.contains("\n## Details\n"), "Missing level 2 heading:\n{}", markdown);
+    assert!(markdown.contains("\nBody text\n"), "Missing body text:\n{}", markdown);
+
+    Ok(())
+}
+
+#[test]
+fn test_docx_inline_formatting() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // Create a DOCX paragraph mixing several run formats
+    let docx = Docx::new()
+        .add_paragraph(Paragraph::new()
+            .add_run(Run::new().add_text("Plain, "))
+            .add_run(Run::new().add_text("bold").bold())
+            .add_run(Run::new().add_text(", "))
+            .add_run(Run::new().add_text("italic ").italic())
+            .add_run(Run { run_property: RunProperty::new().strike(), ..Run::new().add_text("struck") })
+            .add_run(Run::new().add_text(" and "))
+            .add_run(Run::new().add_text("code()").fonts(RunFonts::new().ascii("Courier New")))
+            .add_run(Run::new().add_text(" x"))
+            .add_run(Run {
+                run_property: RunProperty::new().vert_align(VertAlignType::SuperScript),
+                ..Run::new().add_text("2")
+            })
+            .add_run(Run::new().add_text(" "))
+            .add_run(Run::new().add_text("under").underline("single")))
+        // Formatting also comes from character styles, and applies to runs inside hyperlinks
+        .add_style(Style::new("Emphasis", StyleType::Character).italic())
+        .add_paragraph(Paragraph::new()
+            .add_run(Run::new().add_text("styled").style("Emphasis"))
+            .add_run(Run::new().add_text(" "))
+            .add_hyperlink(Hyperlink::new("https://example.com", HyperlinkType::External)
+                .add_run(Run::new().add_text("bold link").bold())));
+    let docx_path = common::create_docx(&input_dir, "formatting", docx)?;
+
+    let content = docx_reader::extract_content(&docx_path)?;
+    let paragraph = content.paragraphs().next().unwrap();
+    assert_eq!(paragraph.text(), "Plain, bold, italic struck and code() x2 under");
+    assert!(paragraph.runs.iter().any(|run| run.text == "bold" && run.format.bold));
+    assert!(paragraph.runs.iter().any(|run| run.text == "code()" && run.format.monospace));
+    let paragraph = content.paragraphs().nth(1).unwrap();
+    assert!(paragraph.runs.iter().any(|run| run.text == "styled" && run.format.italic && !run.format.bold));
+    assert!(paragraph.runs.iter().any(|run| run.text == "bold link" && run.format.bold && run.link.is_some()));
+
+    // Formatting maps to inline Markdown
+    converter::convert_to_markdown(&docx_path, &output_dir)?;
+    let md_path = common::verify_markdown_output(&output_dir, "formatting")?;
+    let markdown = std::fs::read_to_string(md_path)?;
+    assert!(
+        markdown.contains("Plain, **bold**, *italic* ~~struck~~ and `code()` x<sup>2</sup> <u>under</u>"),
+        "Unexpected inline formatting:\n{}", markdown
+    );
+
+    // The formatted paragraph also renders to PDF, with the code run in a monospace font
+    let pdf_path = converter::convert_to_pdf(&docx_path, &output_dir)?;
+    assert_eq!(pdf_path.extension().unwrap(), "pdf", "Output file is not a PDF");
+    if cfg!(feature = "bundled-font") {
+        let fonts = common::pdf_font_names(&pdf_path)?;
+        assert!(fonts.iter().any(|name| name == "DejaVuSansMono"), "No monospace font in {:?}", fonts);
+        assert!(fonts.iter().any(|name| !name.contains("Mono")), "No body font in {:?}", fonts);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_docx_lists() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // Create a DOCX with a nested numbered list followed by a bulleted list
+    // (docx-rs reserves numbering 1 for its default definition)
+    let level = |lvl: usize, format: &str, text: &str| {
+        Level::new(lvl, Start::new(1), NumberFormat::new(format), LevelText::new(text), LevelJc::new("left"))
+    };
+    let continued = Level::new(0, Start::new(4), NumberFormat::new("upperRoman"), LevelText::new("Part %1:"), LevelJc::new("left"));
+    let item = |text: &str, num_id: usize, lvl: usize| {
+        Paragraph::new()
+            .add_run(Run::new().add_text(text))
+            .numbering(NumberingId::new(num_id), IndentLevel::new(lvl))
+    };
+    let docx = Docx::new()
+        .add_abstract_numbering(AbstractNumbering::new(2)
+            .add_level(level(0, "decimal", "%1."))
+            .add_level(level(1, "lowerLetter", "%2)")))
+        .add_abstract_numbering(AbstractNumbering::new(3)
+            .add_level(level(0, "bullet", "\u{f0b7}")))
+        .add_numbering(Numbering::new(2, 2))
+        .add_abstract_numbering(AbstractNumbering::new(4).add_level(continued))
+        .add_numbering(Numbering::new(3, 3))
+        .add_numbering(Numbering::new(4, 4))
+        .add_paragraph(item("Step one", 2, 0))
+        .add_paragraph(item("Step two", 2, 0))
+        .add_paragraph(item("Detail a", 2, 1))
+        .add_paragraph(item("Detail b", 2, 1))
+        .add_paragraph(item("Step three", 2, 0))
+        .add_paragraph(Paragraph::new().add_run(Run::new().add_text("Ingredients")))
+        .add_paragraph(item("Apples", 3, 0))
+        .add_paragraph(item("Pears", 3, 0))
+        .add_paragraph(item("Continued", 4, 0));
+    let docx_path = common::create_docx(&input_dir, "lists", docx)?;
+
+    // Counters follow the nesting and markers follow the level definitions
+    let content = docx_reader::extract_content(&docx_path)?;
+    let markers: Vec<String> = content.paragraphs()
+        .map(|p| p.list.as_ref().map_or_else(String::new, |item| item.marker.clone()))
+        .collect();
+    assert_eq!(markers, vec!["1.", "2.", "a)", "b)", "3.", "", "•", "•", "Part IV:"]);
+    let levels: Vec<Option<usize>> = content.paragraphs()
+        .map(|p| p.list.as_ref().map(|item| item.level))
+        .collect();
+    assert_eq!(levels, vec![Some(0), Some(0), Some(1), Some(1), Some(0), None, Some(0), Some(0), Some(0)]);
+
+    // Lists become indented Markdown lists
+    converter::convert_to_markdown(&docx_path, &output_dir)?;
+    let md_path = common::verify_markdown_output(&output_dir, "lists")?;
+    let markdown = std::fs::read_to_string(md_path)?;
+    assert!(
+        markdown.contains("1. Step one\n2. Step two\n    1. Detail a\n    2. Detail b\n3. Step three\n\n"),
+        "Unexpected numbered list:\n{}", markdown
+    );
+    assert!(markdown.contains("\n- Apples\n- Pears\n"), "Unexpected bulleted list:\n{}", markdown);
+
+    // The lists also render to PDF
+    let pdf_path = converter::convert_to_pdf(&docx_path, &output_dir)?;
+    assert_eq!(pdf_path.extension().unwrap(), "pdf", "Output file is not a PDF");
+
+    Ok(())
+}
+
+#[test]
+fn test_docx_hyperlinks() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // Create a DOCX with an external link and a link to a bookmark
+    let docx = Docx::new()
+        .add_paragraph(Paragraph::new()
+            .add_run(Run::new().add_text("See "))
+            .add_hyperlink(Hyperlink::new("https://example.com/docs", HyperlinkType::External)
+                .add_run(Run::new().add_text("the docs")))
+            .add_run(Run::new().add_text(" or "))
+            .add_hyperlink(Hyperlink::new("appendix", HyperlinkType::Anchor)
+                .add_run(Run::new().add_text("the appendix")))
+            .add_run(Run::new().add_text(".")))
+        .add_paragraph(Paragraph::new()
+            .add_bookmark_start(1, "appendix")
+            .add_run(Run::new().add_text("Appendix"))
+            .add_bookmark_end(1));
+    let docx_path = common::create_docx(&input_dir, "links", docx)?;
+
+    // Link targets are resolved through the relationships
+    let content = docx_reader::extract_content(&docx_path)?;
+    let paragraphs: Vec<_> = content.paragraphs().collect();
+    assert_eq!(paragraphs[0].text(), "See the docs or the appendix.");
+    let links: Vec<_> = paragraphs[0].runs.iter().filter_map(|run| run.link.clone()).collect();
+    assert_eq!(links, vec![
+        LinkTarget::Url("https://example.com/docs".to_string()),
+        LinkTarget::Anchor("appendix".to_string()),
+    ]);
+    assert_eq!(paragraphs[1].bookmarks, vec!["appendix".to_string()]);
+
+    // Links become Markdown links
+    converter::convert_to_markdown(&docx_path, &output_dir)?;
+    let md_path = common::verify_markdown_output(&output_dir, "links")?;
+    let markdown = std::fs::read_to_string(md_path)?;
+    assert!(
+        markdown.contains("See [the docs](https://example.com/docs) or [the appendix](#appendix)."),
+        "Unexpected links:\n{}", markdown
+    );
+    assert!(markdown.contains("<a id=\"appendix\"></a>Appendix"), "Missing anchor:\n{}", markdown);
+
+    // Links become PDF link annotations
+    let pdf_path = converter::convert_to_pdf(&docx_path, &output_dir)?;
+    let pdf = lopdf::Document::load(&pdf_path)?;
+    let mut uris = Vec::new();
+    let mut internal_links = 0;
+    for page in pdf.get_pages().into_values() {
+        let Ok(annotations) = pdf.get_dictionary(page)?.get(b"Annots").and_then(|a| a.as_array()) else {
+            continue;
+        };
+        for annotation in annotations {
+            let annotation = pdf.get_dictionary(annotation.as_reference()?)?;
+            if let Ok(action) = annotation.get(b"A").and_then(|a| a.as_dict()) {
+                uris.push(String::from_utf8_lossy(action.get(b"URI")?.as_str()?).to_string());
+            }
+            if annotation.get(b"Dest").is_ok() {
+                internal_links += 1;
+            }
+        }
+    }
+    assert_eq!(uris, vec!["https://example.com/docs".to_string()]);
+    assert_eq!(internal_links, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_docx_images() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // Create a DOCX with a captioned image; docx-rs cannot write alt text, so add it afterwards
+    let pic = Pic::new(&common::create_png(40, 20)?).size(1_440_000, 720_000);
+    let png = pic.image.clone();
+    let docx = Docx::new()
+        .add_paragraph(Paragraph::new().add_run(Run::new().add_text("Architecture")))
+        .add_paragraph(Paragraph::new().add_run(Run::new().add_image(pic)))
+        .add_paragraph(Paragraph::new().add_run(Run::new().add_text("Figure 1")));
+    let docx_path = common::create_docx(&input_dir, "images", docx)?;
+    common::edit_docx_part(&docx_path, "word/document.xml", |xml| {
+        xml.replace("name=\"Figure\"", "name=\"Figure\" descr=\"Component diagram\"")
+    })?;
+
+    // The image is read with its size and alternative text, in document order
+    let content = docx_reader::extract_content(&docx_path)?;
+    assert!(matches!(content.blocks[1], Block::Image(_)), "Image is not the second block");
+    let image = content.images().next().unwrap();
+    assert_eq!(image.data, png);
+    assert_eq!((image.width_mm, image.height_mm), (40.0, 20.0));
+    assert_eq!(image.alt_text.as_deref(), Some("Component diagram"));
+
+    // Markdown references a copy of the image in the assets folder
+    converter::convert_to_markdown(&docx_path, &output_dir)?;
+    let md_path = common::verify_markdown_output(&output_dir, "images")?;
+    let markdown = std::fs::read_to_string(md_path)?;
+    let reference = format!("![Component diagram](images_assets/{})", image.name);
+    assert!(markdown.contains(&reference), "Missing image reference:\n{}", markdown);
+    assert_eq!(std::fs::read(output_dir.join("images_assets").join(&image.name))?, png);
+
+    // The image is embedded in the PDF
+    let pdf_path = converter::convert_to_pdf(&docx_path, &output_dir)?;
+    let pdf = lopdf::Document::load(&pdf_path)?;
+    let image_count = pdf.objects.values()
+        .filter_map(|object| object.as_stream().ok())
+        .filter(|stream| stream.dict.get(b"Subtype").and_then(|s| s.as_name()).ok() == Some(b"Image".as_slice()))
+        .count();
+    assert_eq!(image_count, 1, "Expected one embedded image");
+
+    Ok(())
+}
+
+#[test]
+fn test_docx_headers_footers_and_notes() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // Create a DOCX with a header, a page number footer and one footnote and endnote;
+    // docx-rs cannot write notes, so the references and notes parts are added afterwards
+    let docx = Docx::new()
+        .header(Header::new().add_paragraph(Paragraph::new().add_run(Run::new().add_text("Quarterly Report"))))
+        .footer(Footer::new().add_paragraph(Paragraph::new().add_page_num(PageNum::new())))
+        .add_paragraph(Paragraph::new()
+            .add_run(Run::new().add_text("Revenue grew."))
+            .add_run(Run::new().add_text("FOOTNOTE"))
+            .add_run(Run::new().add_text(" Costs fell."))
+            .add_run(Run::new().add_text("ENDNOTE")));
+    let docx_path = common::create_docx(&input_dir, "notes", docx)?;
+    common::edit_docx_part(&docx_path, "word/document.xml", |xml| {
+        xml.replace(r#"<w:t xml:space="preserve">FOOTNOTE</w:t>"#, r#"<w:footnoteReference w:id="2" />"#)
+            .replace(r#"<w:t xml:space="preserve">ENDNOTE</w:t>"#, r#"<w:endnoteReference w:id="1" />"#)
+    })?;
+    let note_part = |kind: &str, id: u32, text: &str| format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            r#"<w:{0}s xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">"#,
+            r#"<w:{0} w:type="separator" w:id="0"><w:p><w:r><w:separator/></w:r></w:p></w:{0}>"#,
+            r#"<w:{0} w:id="{1}"><w:p><w:r><w:t>{2}</w:t></w:r></w:p></w:{0}>"#,
+            r#"</w:{0}s>"#,
+        ),
+        kind, id, text,
+    );
+    common::add_docx_part(&docx_path, "word/footnotes.xml", &note_part("footnote", 2, "Audited figures."))?;
+    common::add_docx_part(&docx_path, "word/endnotes.xml", &note_part("endnote", 1, "See appendix."))?;
+
+    // Notes are numbered in reference order and kept as inline markers
+    let content = docx_reader::extract_content(&docx_path)?;
+    assert_eq!(content.header, vec!["Quarterly Report"]);
+    assert!(content.footer.iter().any(|line| line.contains(docx_reader::PAGE_NUMBER_PLACEHOLDER)),
+            "Footer has no page number: {:?}", content.footer);
+    assert_eq!(content.notes.len(), 2);
+    assert_eq!((content.notes[0].label.as_str(), content.notes[0].paragraphs.as_slice()),
+               ("1", ["Audited figures.".to_string()].as_slice()));
+    assert_eq!((content.notes[1].label.as_str(), content.notes[1].paragraphs.as_slice()),
+               ("i", ["See appendix.".to_string()].as_slice()));
+    let paragraph = content.paragraphs().next().unwrap();
+    assert_eq!(paragraph.text(), "Revenue grew.1 Costs fell.i");
+    assert_eq!(paragraph.runs.iter().filter(|run| run.note.is_some()).count(), 2);
+
+    // Markdown uses footnote syntax
+    converter::convert_to_markdown(&docx_path, &output_dir)?;
+    let md_path = common::verify_markdown_output(&output_dir, "notes")?;
+    let markdown = std::fs::read_to_string(md_path)?;
+    assert!(markdown.contains("Revenue grew.[^1] Costs fell.[^i]"), "Missing note references:\n{}", markdown);
+    assert!(markdown.contains("[^1]: Audited figures.\n"), "Missing footnote:\n{}", markdown);
+    assert!(markdown.contains("[^i]: See appendix.\n"), "Missing endnote:\n{}", markdown);
+
+    // PDF renders with the header, footer and notes section
+    let pdf_path = converter::convert_to_pdf(&docx_path, &output_dir)?;
+    common::verify_pdf_output(&output_dir, "notes")?;
+    assert_eq!(lopdf::Document::load(&pdf_path)?.get_pages().len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_docx_merged_cells() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // Create a DOCX table with a title spanning all columns and a region label spanning two rows
+    let cell = |text: &str| TableCell::new().add_paragraph(Paragraph::new().add_run(Run::new().add_text(text)));
+    let docx = Docx::new()
+        .add_table(Table::new(vec![
+            TableRow::new(vec![cell("Quarterly sales").grid_span(3)]),
+            TableRow::new(vec![cell("North").vertical_merge(VMergeType::Restart), cell("Q1"), cell("120")]),
+            TableRow::new(vec![cell("").vertical_merge(VMergeType::Continue), cell("Q2"), cell("135")]),
+            TableRow::new(vec![cell("South"), cell("Q1"), cell("98")]),
+        ]));
+    let docx_path = common::create_docx(&input_dir, "merged", docx)?;
+
+    // Spans are resolved onto the table grid
+    let content = docx_reader::extract_content(&docx_path)?;
+    let table = content.tables().next().unwrap();
+    assert_eq!(table.column_count(), 3);
+    assert_eq!((table.rows[0][0].col_span, table.rows[0][0].row_span), (3, 1));
+    assert_eq!((table.rows[1][0].text.as_str(), table.rows[1][0].row_span), ("North", 2));
+    assert_eq!(table.rows[2].iter().map(|cell| cell.column).collect::<Vec<_>>(), [1, 2]);
+    assert_eq!(table.grid()[2], ["", "Q2", "135"]);
+
+    // Markdown falls back to an HTML table to keep the spans
+    converter::convert_to_markdown(&docx_path, &output_dir)?;
+    let md_path = common::verify_markdown_output(&output_dir, "merged")?;
+    let markdown = std::fs::read_to_string(md_path)?;
+    assert!(markdown.contains(r#"<th colspan="3">Quarterly sales</th>"#), "Missing spanning header:\n{}", markdown);
+    assert!(markdown.contains(r#"<tr><td rowspan="2">North</td><td>Q1</td><td>120</td></tr>"#),
+            "Missing spanning row:\n{}", markdown);
+    assert!(markdown.contains("<tr><td>Q2</td><td>135</td></tr>"), "Missing covered row:\n{}", markdown);
+
+    // Rows flagged as header rows in Word are all written as header cells
+    let docx = Docx::new()
+        .add_table(Table::new(vec![
+            TableRow::new(vec![cell("Region").vertical_merge(VMergeType::Restart), cell("Sales").grid_span(2)]),
+            TableRow::new(vec![cell("").vertical_merge(VMergeType::Continue), cell("Q1"), cell("Q2")]),
+            TableRow::new(vec![cell("North"), cell("120"), cell("135")]),
+        ]));
+    let header_path = common::create_docx(&input_dir, "merged_header", docx)?;
+    common::edit_docx_part(&header_path, "word/document.xml", |xml| {
+        xml.replacen("<w:trPr />", "<w:trPr><w:tblHeader /></w:trPr>", 2)
+    })?;
+    let header_markdown = std::fs::read_to_string(converter::convert_to_markdown(&header_path, &output_dir)?)?;
+    assert!(header_markdown.contains("<tr><th>Q1</th><th>Q2</th></tr>\n  <tr><td>North</td>"), "Missing header rows:\n{}", header_markdown);
+
+    // The PDF renders rows of different lengths instead of falling back to Markdown
+    let pdf_path = converter::convert_to_pdf(&docx_path, &output_dir)?;
+    assert_eq!(pdf_path, common::verify_pdf_output(&output_dir, "merged")?);
+
+    // A tall cell spanning rows runs down beside them instead of pushing them below it
+    let docx = Docx::new()
+        .add_table(Table::new(vec![
+            TableRow::new(vec![cell(&"merged ".repeat(80)).vertical_merge(VMergeType::Restart), cell("One")]),
+            TableRow::new(vec![cell("").vertical_merge(VMergeType::Continue), cell("Two")]),
+            TableRow::new(vec![cell("").vertical_merge(VMergeType::Continue), cell("Three")]),
+        ]))
+        .add_paragraph(Paragraph::new().add_run(Run::new().add_text("After the table")));
+    let docx_path = common::create_docx(&input_dir, "tall_merge", docx)?;
+    let pdf_path = converter::convert_to_pdf(&docx_path, &output_dir)?;
+    let positions = common::pdf_text_positions(&pdf_path)?.remove(0);
+    let (left, _) = positions[0];
+    let (after, cells) = positions.split_last().unwrap();
+    let merged_bottom = cells.iter().filter(|(x, _)| *x == left).map(|(_, y)| *y).fold(f64::MAX, f64::min);
+    let rows: Vec<f64> = cells.iter().filter(|(x, _)| *x > left).map(|(_, y)| *y).collect();
+    assert_eq!(rows.len(), 3, "Expected three rows beside the merged cell: {:?}", positions);
+    assert!(rows[0] - rows[2] < 40.0, "Rows are pushed apart by the merged cell: {:?}", rows);
+    assert!(merged_bottom < rows[2] - 100.0, "The merged cell does not run past its rows: {:?}", positions);
+    assert!(after.1 < merged_bottom, "The text after the table overlaps the merged cell: {:?}", positions);
+
+    Ok(())
+}
+
+#[test]
+fn test_spreadsheet_format_detection() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // OpenDocument spreadsheets are read with the ODS backend
+    let ods_path = common::create_mock_ods(&input_dir, "inventory")?;
+    let sheets = xlsx_reader::extract_sheets(&ods_path, &ConversionOptions::default())?;
+    assert_eq!(sheets.len(), 1);
+    assert_eq!(sheets[0].name, "Inventory");
+    assert_eq!(sheets[0].display_rows(&Locale::default()), [["Item", "Count"], ["Bolts", "12"]]);
+
+    // Only XLSX and XLSM styles are read, so other formats show plain values
+    let ods_path = common::create_ods(&input_dir, "rates", "Rates", concat!(
+        r#"<table:table-row><table:table-cell office:value-type="percentage" office:value="0.1523"><text:p>15.23%</text:p></table:table-cell>"#,
+        r#"<table:table-cell table:number-columns-spanned="2" office:value-type="string"><text:p>Merged</text:p></table:table-cell>"#,
+        r#"<table:covered-table-cell/></table:table-row>"#,
+    ))?;
+    let sheets = xlsx_reader::extract_sheets(&ods_path, &ConversionOptions::default())?;
+    assert_eq!(sheets[0].display_rows(&Locale::default()), [["0.1523", "Merged"]]);
+    assert!(sheets[0].merged_ranges.is_empty());
+    std::fs::remove_file(&ods_path)?;
+
+    // Excel 97-2003 workbooks are read with the BIFF8 backend
+    let xls_path = input_dir.join("stock.xls");
+    std::fs::copy(common::fixture_path("stock.xls"), &xls_path)?;
+    let sheets = xlsx_reader::extract_sheets(&xls_path, &ConversionOptions::default())?;
+    assert_eq!(sheets.len(), 1);
+    assert_eq!(sheets[0].name, "Stock");
+    assert_eq!(sheets[0].display_rows(&Locale::default()), [["Item", "Count"], ["Bolts", "12"], ["Nuts", "40"]]);
+
+    // A workbook whose extension does not match its contents is detected from the contents
+    let xlsx_path = common::create_mock_xlsx(&input_dir, "mislabelled")?;
+    let mislabelled_path = xlsx_path.with_extension("xls");
+    std::fs::rename(&xlsx_path, &mislabelled_path)?;
+    let sheets = xlsx_reader::extract_sheets(&mislabelled_path, &ConversionOptions::default())?;
+    assert_eq!(sheets[0].name, "Data");
+
+    // Batch conversion picks up every spreadsheet format
+    let results = converter::batch_convert_to_markdown(&input_dir, &output_dir)?;
+    assert_eq!(results.len(), 3, "Expected all spreadsheets to be converted: {:?}", results);
+    common::verify_markdown_output(&output_dir, "inventory")?;
+    common::verify_markdown_output(&output_dir, "stock")?;
+    common::verify_markdown_output(&output_dir, "mislabelled")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_xlsx_number_formats() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // Create a workbook with dates, currency, percentages and built-in formats
+    let xlsx_path = input_dir.join("finance.xlsx");
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet().set_name("Report")?;
+    worksheet.write_datetime_with_format(0, 0, ExcelDateTime::from_ymd(2023, 1, 1)?, &Format::new().set_num_format("yyyy-mm-dd"))?;
+    worksheet.write_number_with_format(0, 1, 1234567.891, &Format::new().set_num_format("#,##0.00 [$€-407]"))?;
+    worksheet.write_number_with_format(0, 2, 0.1523, &Format::new().set_num_format("0.0%"))?;
+    worksheet.write_number_with_format(0, 3, -42.5, &Format::new().set_num_format_index(8))?;
+    worksheet.write_boolean(0, 4, true)?;
+    worksheet.write_number(0, 5, 0.1523)?;
+    workbook.save(&xlsx_path)?;
+
+    // Cells keep their type and number format
+    let sheets = xlsx_reader::extract_sheets(&xlsx_path, &ConversionOptions::default())?;
+    let row = &sheets[0].data[0];
+    assert_eq!(row[0].value, CellValue::DateTime(44927.0));
+    assert_eq!(row[1].number_format, Some(number_format::NumberFormat::Custom("#,##0.00 [$€-407]".to_string())));
+    assert_eq!(row[3].number_format, Some(number_format::NumberFormat::Builtin(8)));
+    assert_eq!(row[4].value, CellValue::Bool(true));
+
+    // Values are displayed as Excel shows them, with the locale's separators
+    assert_eq!(sheets[0].display_rows(&Locale::default())[0],
+               ["2023-01-01", "1,234,567.89 €", "15.2%", "($42.50)", "TRUE", "0.1523"]);
+    assert_eq!(sheets[0].display_rows(&Locale::from_tag("de_DE.UTF-8"))[0],
+               ["2023-01-01", "1.234.567,89 €", "15,2%", "($42,50)", "TRUE", "0,1523"]);
+
+    // The locale can be chosen instead of taken from the environment
+    let options = ConversionOptions { locale: Some("de-DE".to_string()), ..Default::default() };
+    let md_path = converter::convert_to_markdown_with_options(&xlsx_path, &output_dir, &options)?;
+    assert!(std::fs::read_to_string(md_path)?.contains("1.234.567,89 €"));
+    assert_eq!(Config::parse("locale = de-DE")?.locale.as_deref(), Some("de-DE"));
+
+    // Sheet parts are found relative to the workbook folder, however their targets are written
+    assert_eq!(package::resolve_part_target("xl", "worksheets/sheet1.xml"), "xl/worksheets/sheet1.xml");
+    assert_eq!(package::resolve_part_target("xl", "../xl/worksheets/sheet1.xml"), "xl/worksheets/sheet1.xml");
+    assert_eq!(package::resolve_part_target("xl", "/xl/worksheets/sheet1.xml"), "xl/worksheets/sheet1.xml");
+
+    Ok(())
+}
+
+#[test]
+fn test_number_format_codes() {
+    let en = Locale::default();
+    let fr = Locale::from_tag("fr-FR");
+    let format = |value: CellValue, code: &str, locale: &Locale| {
+        number_format::format_value(&value, Some(&number_format::NumberFormat::Custom(code.to_string())), locale)
+    };
+
+    // Numbers
+    assert_eq!(format(CellValue::Number(1234.5), "#,##0.00", &fr), "1\u{a0}234,50");
+    assert_eq!(format(CellValue::Number(-1234.5), "#,##0", &en), "-1,235");
+    assert_eq!(format(CellValue::Number(0.5), "#.00", &en), ".50");
+    assert_eq!(format(CellValue::Number(1_500_000.0), "#,##0.0,,\"M\"", &en), "1.5M");
+    assert_eq!(format(CellValue::Number(12345.0), "0.00E+00", &en), "1.23E+04");
+    assert_eq!(format(CellValue::Number(0.0), "0.00;(0.00);\"-\"", &en), "-");
+    assert_eq!(format(CellValue::Number(-3.0), "0.00;[Red](0.00)", &en), "(3.00)");
+    // Fractions
+    let builtin = |value: f64, id: u32| number_format::format_value(&CellValue::Number(value), Some(&number_format::NumberFormat::Builtin(id)), &en);
+    assert_eq!(builtin(45123.456, 12), "45123 4/9");
+    assert_eq!(builtin(45123.456, 13), "45123 31/68");
+    assert_eq!(builtin(1.75, 12), "1 3/4");
+    assert_eq!(builtin(-1.75, 13), "-1  3/4");
+    assert_eq!(builtin(3.0, 12), "3");
+    assert_eq!(builtin(0.0, 12), "0");
+    assert_eq!(builtin(0.999, 12), "1");
+    assert_eq!(format(CellValue::Number(1.75), "?/?", &en), "7/4");
+    assert_eq!(format(CellValue::Number(0.3), "# ?/8", &en), "2/8");
+    assert_eq!(format(CellValue::Number(2.37), "0 ??/100", &en), "2 37/100");
+    assert_eq!(format(CellValue::DateTime(45123.0), "d/m/yyyy", &en), "16/7/2023");
+
+    // Numbers at the ends of the floating point range
+    assert_eq!(format(CellValue::Number(1e308), "0.0%", &en), "#######");
+    assert_eq!(format(CellValue::Number(1e-310), "0.00E+00", &en), "1.00E-310");
+    assert_eq!(format(CellValue::Number(5e-324), "0.00E+00", &en), "4.94E-324");
+    assert_eq!(format(CellValue::Number(1e-308), "##0.0E+0", &en), "10.0E-309");
+    assert_eq!(format(CellValue::Number(1e-310), "0E+00", &en), "1E-310");
+    assert_eq!(format(CellValue::Number(1.5e308), "0.00E+00", &en), "1.50E+308");
+
+    // Dates and times
+    assert_eq!(format(CellValue::DateTime(45123.75), "dddd, mmmm d, yyyy h:mm AM/PM", &en),
+               "Sunday, July 16, 2023 6:00 PM");
+    assert_eq!(format(CellValue::DateTime(0.5 + 1.0 / 86_400.0), "hh:mm:ss", &en), "12:00:01");
+    assert_eq!(format(CellValue::DateTime(0.5 + 1.5 / 86_400.0), "h:mm:ss.0", &fr), "12:00:01,5");
+    assert_eq!(format(CellValue::Duration(1.25), "[h]:mm", &en), "30:00");
+    assert_eq!(number_format::format_value(&CellValue::DateTime(45123.0), Some(&number_format::NumberFormat::Builtin(14)), &Locale::from_tag("de-DE")),
+               "16.07.2023");
+    // Dates outside the range Excel can show are filled with number signs
+    assert_eq!(format(CellValue::DateTime(2_958_465.5), "yyyy-mm-dd hh:mm", &en), "9999-12-31 12:00");
+    assert_eq!(format(CellValue::DateTime(2_958_466.0), "yyyy-mm-dd", &en), "#######");
+    assert_eq!(number_format::format_value(&CellValue::DateTime(1e20), None, &en), "#######");
+    assert_eq!(format(CellValue::Number(1e20), "d/m/yyyy", &en), "#######");
+    assert_eq!(format(CellValue::DateTime(-5.0), "d/m/yyyy", &en), "#######");
+    assert_eq!(format(CellValue::Duration(-0.5), "[h]:mm", &en), "#######");
+
+    // General
+    assert_eq!(number_format::format_value(&CellValue::Number(1.0 / 3.0), None, &en), "0.3333333333");
+    assert_eq!(number_format::format_value(&CellValue::Number(1.5e20), None, &fr), "1,5E+20");
+}
+
+#[test]
+fn test_xlsx_formulas() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // Create a workbook with a formula and its cached result
+    let xlsx_path = input_dir.join("model.xlsx");
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet().set_name("Model")?;
+    worksheet.write_string(0, 1, "Amount")?;
+    worksheet.write_number(1, 1, 10)?;
+    worksheet.write_number(2, 1, 20)?;
+    worksheet.write_formula(3, 1, Formula::new("=SUM(B2:B3)").set_result("30"))?;
+    workbook.save(&xlsx_path)?;
+
+    // Formulas are only read when requested
+    let sheets = xlsx_reader::extract_sheets(&xlsx_path, &ConversionOptions::default())?;
+    assert_eq!(sheets[0].data[3][0].formula, None);
+
+    let options = ConversionOptions { formulas: FormulaDisplay::Inline, ..Default::default() };
+    let sheets = xlsx_reader::extract_sheets(&xlsx_path, &options)?;
+    let cell = &sheets[0].data[3][0];
+    assert_eq!(cell.value, CellValue::Number(30.0));
+    assert_eq!(cell.formula.as_deref(), Some("=SUM(B2:B3)"));
+    assert_eq!(sheets[0].formulas(), [("B4".to_string(), "=SUM(B2:B3)".to_string())]);
+
+    // Inline display follows each value with its formula
+    let md_path = converter::convert_to_markdown_with_options(&xlsx_path, &output_dir, &options)?;
+    let markdown = std::fs::read_to_string(&md_path)?;
+    assert!(markdown.contains("| 30 (=SUM(B2:B3)) |"), "{}", markdown);
+
+    // The appendix lists formulas by cell reference after the sheet
+    let options = ConversionOptions { formulas: FormulaDisplay::Appendix, ..Default::default() };
+    let md_path = converter::convert_to_markdown_with_options(&xlsx_path, &output_dir, &options)?;
+    let markdown = std::fs::read_to_string(&md_path)?;
+    assert!(markdown.contains("### Formulas"));
+    assert!(markdown.contains("| B4 | =SUM(B2:B3) |"), "{}", markdown);
+    assert!(!markdown.contains("(=SUM"));
+
+    let pdf_path = converter::convert_to_pdf_with_options(&xlsx_path, &output_dir, &options)?;
+    assert!(pdf_path.exists());
+
+    assert_eq!(converter::table::column_name(0), "A");
+    assert_eq!(converter::table::column_name(27), "AB");
+
+    Ok(())
+}
+
+#[test]
+fn test_xlsx_merged_cells() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // Create a report with a merged title row and a region merged down two rows
+    let xlsx_path = input_dir.join("report.xlsx");
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet().set_name("Report")?;
+    worksheet.merge_range(0, 0, 0, 2, "Quarterly sales", &Format::new())?;
+    worksheet.write_string(1, 0, "Region")?;
+    worksheet.write_string(1, 1, "Quarter")?;
+    worksheet.write_string(1, 2, "Sales")?;
+    worksheet.merge_range(2, 0, 3, 0, "North", &Format::new())?;
+    worksheet.write_string(2, 1, "Q1")?;
+    worksheet.write_number(2, 2, 120)?;
+    worksheet.write_string(3, 1, "Q2")?;
+    worksheet.write_number(3, 2, 135)?;
+    workbook.save(&xlsx_path)?;
+
+    // Merge ranges are exposed on the sheet
+    let sheets = xlsx_reader::extract_sheets(&xlsx_path, &ConversionOptions::default())?;
+    let sheet = &sheets[0];
+    assert_eq!(sheet.merged_ranges, [
+        converter::table::CellRange { first_row: 0, first_column: 0, last_row: 0, last_column: 2 },
+        converter::table::CellRange { first_row: 2, first_column: 0, last_row: 3, last_column: 0 },
+    ]);
+
+    // Merged cells span their range and the cells they cover are left out
+    let table = sheet.table(&sheet.display_rows(&Locale::default()));
+    assert_eq!((table.rows[0].len(), table.rows[0][0].col_span), (1, 3));
+    assert_eq!((table.rows[2][0].text.as_str(), table.rows[2][0].row_span), ("North", 2));
+    assert_eq!(table.rows[3].iter().map(|cell| cell.column).collect::<Vec<_>>(), [1, 2]);
+
+    // Markdown falls back to an HTML table, with the merged title above the detected header
+    let md_path = converter::convert_to_markdown(&xlsx_path, &output_dir)?;
+    let markdown = std::fs::read_to_string(&md_path)?;
+    assert!(markdown.contains("Quarterly sales\n\n<table>\n  <tr><th>Region</th>"), "Missing title:\n{}", markdown);
+    assert!(markdown.contains(r#"<tr><td rowspan="2">North</td><td>Q1</td><td>120</td></tr>"#),
+            "Missing spanning region:\n{}", markdown);
+
+    // When the title row is declared the header, it spans the table
+    let options = ConversionOptions { header_rows: Some(1), ..Default::default() };
+    let md_path = converter::convert_to_markdown_with_options(&xlsx_path, &output_dir, &options)?;
+    let markdown = std::fs::read_to_string(&md_path)?;
+    assert!(markdown.contains(r#"<th colspan="3">Quarterly sales</th>"#), "Missing spanning title:\n{}", markdown);
+
+    let pdf_path = converter::convert_to_pdf(&xlsx_path, &output_dir)?;
+    assert_eq!(pdf_path, common::verify_pdf_output(&output_dir, "report")?);
+
+    Ok(())
+}
+
+#[test]
+fn test_xlsx_sheet_geometry() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // Create a sheet whose data starts at D5 with three empty rows between two blocks
+    let xlsx_path = input_dir.join("blocks.xlsx");
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet().set_name("Blocks")?;
+    worksheet.write_string(4, 3, "Name")?;
+    worksheet.write_string(4, 4, "Value")?;
+    worksheet.write_string(5, 3, "First")?;
+    worksheet.write_number(5, 4, 1)?;
+    worksheet.write_string(9, 3, "Second")?;
+    worksheet.write_number(9, 4, 2)?;
+    workbook.save(&xlsx_path)?;
+
+    // Empty rows are dropped by default, but the origin and row numbers are kept
+    let sheets = xlsx_reader::extract_sheets(&xlsx_path, &ConversionOptions::default())?;
+    assert_eq!(sheets[0].origin(), "D5");
+    assert_eq!(sheets[0].column_letters(), ["D", "E"]);
+    assert_eq!(sheets[0].row_numbers, [5, 6, 10]);
+    assert_eq!(sheets[0].cell_reference(2, 1), "E10");
+
+    // Blank rows are kept up to the limit
+    let options = ConversionOptions { max_blank_rows: 1, grid_labels: true, ..Default::default() };
+    let sheets = xlsx_reader::extract_sheets(&xlsx_path, &options)?;
+    assert_eq!(sheets[0].row_numbers, [5, 6, 7, 10]);
+
+    // Grid labels show the column letters and row numbers
+    let md_path = converter::convert_to_markdown_with_options(&xlsx_path, &output_dir, &options)?;
+    let markdown = std::fs::read_to_string(&md_path)?;
+    assert!(markdown.contains("|  | D | E |"), "Missing column letters:\n{}", markdown);
+    assert!(markdown.contains("| 5 | Name | Value |"), "Missing row number:\n{}", markdown);
+    assert!(markdown.contains("| 7 |  |  |"), "Missing blank row:\n{}", markdown);
+    assert!(markdown.contains("| 10 | Second | 2 |"), "Missing row number:\n{}", markdown);
+
+    let pdf_path = converter::convert_to_pdf_with_options(&xlsx_path, &output_dir, &options)?;
+    assert!(pdf_path.exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_xlsx_sheet_selection() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, _) = common::setup_test_env()?;
+
+    // Create a workbook with scratch and hidden lookup sheets next to the report
+    let xlsx_path = input_dir.join("selection.xlsx");
+    let mut workbook = Workbook::new();
+    let report = workbook.add_worksheet().set_name("Report")?;
+    for row in 0..5 {
+        for col in 0..5u16 {
+            report.write_number(row, col, (row * 10 + col as u32) as f64)?;
+        }
+    }
+    workbook.add_worksheet().set_name("Scratch 1")?.write_string(0, 0, "notes")?;
+    workbook.add_worksheet().set_name("Lookup")?.write_string(0, 0, "codes")?.set_hidden(true);
+    workbook.save(&xlsx_path)?;
+
+    let names = |options: &ConversionOptions| -> Result<Vec<String>> {
+        Ok(xlsx_reader::extract_sheets(&xlsx_path, options)?.into_iter().map(|sheet| sheet.name).collect())
+    };
+
+    // Hidden sheets are skipped unless requested
+    assert_eq!(names(&ConversionOptions::default())?, ["Report", "Scratch 1"]);
+    let options = ConversionOptions { include_hidden_sheets: true, ..Default::default() };
+    assert_eq!(names(&options)?, ["Report", "Scratch 1", "Lookup"]);
+
+    // Sheets are included and excluded by name or glob pattern
+    let options = ConversionOptions { exclude_sheets: vec!["scratch*".to_string()], ..Default::default() };
+    assert_eq!(names(&options)?, ["Report"]);
+    let options = ConversionOptions {
+        include_sheets: vec!["Report".to_string(), "Look?p".to_string()],
+        include_hidden_sheets: true,
+        ..Default::default()
+    };
+    assert_eq!(names(&options)?, ["Report", "Lookup"]);
+
+    // A range restricts the cells read from a sheet
+    let range: converter::SheetRange = "'Report'!$B$2:C3".parse()?;
+    assert_eq!(range.sheet, "Report");
+    let options = ConversionOptions { sheet_ranges: vec![range], ..Default::default() };
+    let sheets = xlsx_reader::extract_sheets(&xlsx_path, &options)?;
+    assert_eq!(sheets[0].origin(), "B2");
+    assert_eq!(sheets[0].display_rows(&Locale::default()), [["11", "12"], ["21", "22"]]);
+    assert_eq!(sheets[1].data.len(), 1);
+    assert!("A1:B2".parse::<converter::SheetRange>().is_err());
+    // Columns end at XFD
+    assert!("Report!A1:XFD2".parse::<converter::SheetRange>().is_ok());
+    assert!("Report!A1:XFE2".parse::<converter::SheetRange>().is_err());
+    assert!("Sheet1!AAAAAAAA1:B2".parse::<converter::SheetRange>().is_err());
+
+    assert!(utils::matches_glob("Q? *", "q1 Sales"));
+    assert!(!utils::matches_glob("Q?", "Q10"));
+
+    Ok(())
+}
+
+#[test]
+fn test_xlsx_tables_and_named_ranges() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // Create a sheet with a title above an Excel table and a named range beside it
+    let xlsx_path = input_dir.join("tables.xlsx");
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet().set_name("Sales")?;
+    worksheet.merge_range(0, 0, 0, 2, "Quarterly report", &Format::new())?;
+    worksheet.write_row(2, 0, ["Region", "Q1", "Q2"])?;
+    worksheet.write_row(3, 0, ["North", "120", "135"])?;
+    worksheet.write_row(4, 0, ["South", "98", "110"])?;
+    worksheet.add_table(2, 0, 4, 2, &ExcelTable::new().set_name("RegionSales"))?;
+    worksheet.write_row(0, 4, ["Currency", "Rate"])?;
+    worksheet.write_row(1, 4, ["EUR", "1.08"])?;
+    workbook.add_worksheet().set_name("Notes")?.write_string(0, 0, "Scratch")?;
+    workbook.define_name("Rates", "=Sales!$E$1:$F$2")?;
+    workbook.define_name("_xlnm.Print_Area", "=Sales!$A$1:$C$5").ok();
+    workbook.save(&xlsx_path)?;
+
+    // Tables and named ranges are read as their own tables, header row first
+    let sheets = xlsx_reader::extract_sheets(&xlsx_path, &ConversionOptions::default())?;
+    let tables = &sheets[0].tables;
+    assert_eq!(tables.len(), 2);
+    assert_eq!((tables[0].kind, tables[0].name.as_str()), (xlsx_reader::TableKind::Table, "RegionSales"));
+    assert_eq!(tables[0].range.to_string(), "A3:C5");
+    assert_eq!(tables[0].cells.display_rows(&Locale::default())[0], ["Region", "Q1", "Q2"]);
+    assert_eq!((tables[1].kind, tables[1].name.as_str()), (xlsx_reader::TableKind::NamedRange, "Rates"));
+    assert_eq!(tables[1].cells.display_rows(&Locale::default()), [["Currency", "Rate"], ["EUR", "1.08"]]);
+
+    // Converting tables only leaves out the rest of the sheet and sheets without tables
+    let options = ConversionOptions { tables_only: true, ..Default::default() };
+    let sheets = xlsx_reader::extract_sheets(&xlsx_path, &options)?;
+    assert_eq!(sheets.len(), 1);
+    assert!(sheets[0].data.is_empty());
+    assert_eq!(sheets[0].tables.len(), 2);
+    let md_path = converter::convert_to_markdown_with_options(&xlsx_path, &output_dir, &options)?;
+    let markdown = std::fs::read_to_string(&md_path)?;
+    assert!(markdown.contains("### Table: RegionSales (A3:C5)\n\n| Region | Q1 | Q2 |\n| --- | --- | --- |\n"),
+            "Missing table:\n{}", markdown);
+    assert!(markdown.contains("### Named range: Rates (E1:F2)\n\n| Currency | Rate |"), "Missing named range:\n{}", markdown);
+    assert!(!markdown.contains("Quarterly report"));
+    assert!(!markdown.contains("Notes"));
+
+    let pdf_path = converter::convert_to_pdf_with_options(&xlsx_path, &output_dir, &options)?;
+    assert!(pdf_path.exists());
+
+    // A table part that cannot be read only loses that table, not the rest of the sheet markup
+    common::replace_package_part(&xlsx_path, "xl/tables/table1.xml", b"\xff\xfe")?;
+    let sheets = xlsx_reader::extract_sheets(&xlsx_path, &ConversionOptions::default())?;
+    assert_eq!(sheets[0].tables.iter().map(|table| table.name.as_str()).collect::<Vec<_>>(), ["Rates"]);
+    assert_eq!(sheets[0].merged_ranges.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_xlsx_header_detection() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // Create a sheet with a title block above a two-level header
+    let xlsx_path = input_dir.join("budget.xlsx");
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet().set_name("Budget")?;
+    worksheet.write_string(0, 0, "Budget 2024")?;
+    worksheet.write_string(1, 0, "Prepared by finance")?;
+    worksheet.write_string(3, 0, "Region")?;
+    worksheet.merge_range(3, 1, 3, 2, "Sales", &Format::new())?;
+    worksheet.merge_range(3, 3, 3, 4, "Costs", &Format::new())?;
+    worksheet.write_row(4, 1, ["Q1", "Q2", "Q1", "Q2"])?;
+    worksheet.write_string(5, 0, "North")?;
+    worksheet.write_row(5, 1, [120, 135, 80, 85])?;
+    workbook.save(&xlsx_path)?;
+
+    // The header is found below the title block and spans two rows
+    let sheets = xlsx_reader::extract_sheets(&xlsx_path, &ConversionOptions::default())?;
+    assert_eq!((sheets[0].header_start, sheets[0].header_rows), (2, 2));
+
+    // Markdown writes the title block as text and flattens the header
+    let md_path = converter::convert_to_markdown(&xlsx_path, &output_dir)?;
+    let markdown = std::fs::read_to_string(&md_path)?;
+    assert!(markdown.contains("Budget 2024\n\nPrepared by finance\n\n\
+        | Region | Sales / Q1 | Sales / Q2 | Costs / Q1 | Costs / Q2 |\n\
+        | --- | --- | --- | --- | --- |\n\
+        | North | 120 | 135 | 80 | 85 |\n"), "Unexpected header:\n{}", markdown);
+
+    let pdf_path = converter::convert_to_pdf(&xlsx_path, &output_dir)?;
+    assert!(pdf_path.exists());
+
+    // Without header rows the column letters label the table, which keeps its merged cells
+    let options = ConversionOptions { header_rows: Some(0), ..Default::default() };
+    let md_path = converter::convert_to_markdown_with_options(&xlsx_path, &output_dir, &options)?;
+    let markdown = std::fs::read_to_string(&md_path)?;
+    assert!(markdown.contains("<tr><th>A</th><th>B</th><th>C</th><th>D</th><th>E</th></tr>\n  <tr><td>Budget 2024</td>"),
+            "Unexpected header:\n{}", markdown);
+
+    Ok(())
+}
+
+#[test]
+fn test_xlsx_wide_sheet_layout() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // Create a workbook with a moderately wide sheet and a very wide one
+    let xlsx_path = input_dir.join("wide.xlsx");
+    let mut workbook = Workbook::new();
+    let wide = workbook.add_worksheet().set_name("Wide")?;
+    for col in 0..12u16 {
+        wide.write_string(0, col, format!("Column name {}", col))?;
+        wide.write_number(1, col, 1000.0 + col as f64)?;
+    }
+    let huge = workbook.add_worksheet().set_name("Huge")?;
+    for col in 0..60u16 {
+        huge.write_string(0, col, format!("Measure {:04}", col))?;
+        huge.write_string(1, col, format!("Value {:06}", col))?;
+    }
+    workbook.save(&xlsx_path)?;
+
+    // Column widths follow the content, and wide tables get a smaller font
+    let sheets = xlsx_reader::extract_sheets(&xlsx_path, &ConversionOptions::default())?;
+    let landscape_width = 297.0 - 40.0;
+    let table = sheets[0].table(&sheets[0].display_rows(&Locale::default()));
+    let layout = sheet_layout::plan_layout(&table, 1, sheet_layout::DEFAULT_TABLE_FONT_SIZE, landscape_width);
+    assert_eq!(layout.column_weights[0], "Column name 0".len() + 1);
+    assert!(layout.font_size < sheet_layout::DEFAULT_TABLE_FONT_SIZE && layout.font_size >= sheet_layout::MIN_TABLE_FONT_SIZE);
+    assert_eq!(layout.column_groups.len(), 1);
+
+    // Beyond the minimum font size the columns are split into groups repeating the key column
+    let table = sheets[1].table(&sheets[1].display_rows(&Locale::default()));
+    let layout = sheet_layout::plan_layout(&table, 1, sheet_layout::DEFAULT_TABLE_FONT_SIZE, landscape_width);
+    assert_eq!(layout.font_size, sheet_layout::MIN_TABLE_FONT_SIZE);
+    assert!(layout.column_groups.len() > 1);
+    assert!(layout.column_groups.iter().all(|group| group[0] == 0));
+    let columns: usize = layout.column_groups.iter().map(|group| group.len() - 1).sum();
+    assert_eq!(columns, 59);
+    let part = sheet_layout::select_columns(&table, &layout.column_groups[1]);
+    assert_eq!(part.rows[0][0].text, "Measure 0000");
+    assert_eq!(part.column_count(), layout.column_groups[1].len());
+
+    // The PDF uses landscape pages, with the column groups on pages of their own
+    let pdf_path = converter::convert_to_pdf(&xlsx_path, &output_dir)?;
+    let pdf = lopdf::Document::load(&pdf_path)?;
+    let pages = pdf.get_pages();
+    assert_eq!(pages.len(), 1 + layout.column_groups.len());
+    let page = pdf.get_dictionary(*pages.values().next().unwrap())?;
+    let media_box = page.get(b"MediaBox")?.as_array()?;
+    let size: Vec<f64> = media_box.iter()
+        .map(|value| value.as_f64().or_else(|_| value.as_i64().map(|v| v as f64)))
+        .collect::<std::result::Result<_, _>>()?;
+    assert!(size[2] > size[3], "Expected a landscape page: {:?}", size);
+
+    // A narrow sheet keeps the workbook on portrait pages, as all pages share one orientation
+    let mut workbook = Workbook::new();
+    let wide = workbook.add_worksheet().set_name("Wide")?;
+    for col in 0..12u16 {
+        wide.write_string(0, col, format!("Column name {}", col))?;
+    }
+    workbook.add_worksheet().set_name("Narrow")?.write_row(0, 0, ["Item", "Count"])?;
+    let mixed_path = input_dir.join("mixed.xlsx");
+    workbook.save(&mixed_path)?;
+    let pdf_path = converter::convert_to_pdf(&mixed_path, &output_dir)?;
+    let pdf = lopdf::Document::load(&pdf_path)?;
+    let page = pdf.get_dictionary(*pdf.get_pages().values().next().unwrap())?;
+    let size: Vec<f64> = page.get(b"MediaBox")?.as_array()?.iter()
+        .map(|value| value.as_f64().or_else(|_| value.as_i64().map(|v| v as f64)))
+        .collect::<std::result::Result<_, _>>()?;
+    assert!(size[2] < size[3], "Expected a portrait page: {:?}", size);
+
+    Ok(())
+}
+
+#[test]
+fn test_repeated_table_headers() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // The text lines of each page, as drawn in the content stream
+    let page_lines = |pdf: &lopdf::Document| -> Result<Vec<Vec<String>>> {
+        pdf.get_pages().keys()
+            .map(|page| Ok(pdf.extract_text(&[*page])?.lines().map(str::to_string).collect()))
+            .collect()
+    };
+
+    // Create a DOCX table running over several pages, with its first row flagged as a header
+    let cell = |text: &str| TableCell::new().add_paragraph(Paragraph::new().add_run(Run::new().add_text(text)));
+    let mut rows = vec![TableRow::new(vec![cell("Item"), cell("Amount")])];
+    rows.extend((1..=120).map(|index| TableRow::new(vec![cell(&format!("Row {}", index)), cell(&index.to_string())])));
+    let docx_path = common::create_docx(&input_dir, "long_table", Docx::new().add_table(Table::new(rows)))?;
+    common::edit_docx_part(&docx_path, "word/document.xml", |xml| {
+        xml.replacen("<w:trPr />", "<w:trPr><w:tblHeader /></w:trPr>", 1)
+    })?;
+
+    let content = docx_reader::extract_content(&docx_path)?;
+    assert_eq!(content.tables().next().unwrap().header_rows, 1);
+
+    // Every page starts with the header row
+    let pdf_path = converter::convert_to_pdf(&docx_path, &output_dir)?;
+    let pages = page_lines(&lopdf::Document::load(&pdf_path)?)?;
+    assert!(pages.len() > 1);
+    for page in &pages[1..] {
+        assert_eq!(page[..2], pages[0][..2]);
+    }
+
+    // A long sheet repeats its detected header row below the sheet heading
+    let xlsx_path = input_dir.join("long_sheet.xlsx");
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet().set_name("Ledger")?;
+    worksheet.write_row(0, 0, ["Entry", "Balance"])?;
+    for row in 1..=150u32 {
+        worksheet.write_string(row, 0, format!("Entry {}", row))?;
+        worksheet.write_number(row, 1, f64::from(row) * 10.0)?;
+    }
+    workbook.save(&xlsx_path)?;
+
+    let pdf_path = converter::convert_to_pdf(&xlsx_path, &output_dir)?;
+    let pages = page_lines(&lopdf::Document::load(&pdf_path)?)?;
+    assert!(pages.len() > 1);
+    for page in &pages[1..] {
+        assert_eq!(page[..2], pages[0][1..3]);
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "bundled-font")]
+fn test_bundled_font() -> Result<()> {
+    // Set up test environment
+    let (temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // Create a document with Vietnamese and Latin Extended text
+    let text = "Tiếng Việt có dấu: Đường phố Hà Nội, ắ ằ ẳ ẵ ặ ơ ư; Łódź, Ærøskøbing, Ğüşçö";
+    let docx = Docx::new().add_paragraph(Paragraph::new().add_run(Run::new().add_text(text)));
+    common::create_docx(&input_dir, "unicode", docx)?;
+
+    // Run the converter from a directory without font resources
+    let working_dir = temp_dir.path().join("elsewhere");
+    std::fs::create_dir(&working_dir)?;
+    let status = std::process::Command::new(env!("CARGO_BIN_EXE_aqon"))
+        .current_dir(&working_dir)
+        .args(["convert", "--input"]).arg(&input_dir)
+        .arg("--output").arg(&output_dir)
+        .status()?;
+    assert!(status.success());
+
+    // The PDF is written instead of falling back to Markdown
+    let pdf_path = common::verify_pdf_output(&output_dir, "unicode")?;
+    assert!(!output_dir.join("unicode.md").exists());
+
+    // The bundled font is the default wherever the converter runs, with no lookup in the working directory
+    let status = std::process::Command::new(env!("CARGO_BIN_EXE_aqon"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["convert", "--input"]).arg(&input_dir)
+        .arg("--output").arg(&output_dir)
+        .status()?;
+    assert!(status.success());
+    let fonts = common::pdf_font_names(&pdf_path)?;
+    assert!(!fonts.is_empty() && fonts.iter().all(|name| name.starts_with("DejaVuSans")), "Unexpected fonts: {:?}", fonts);
+
+    // Only the glyphs shown are embedded, far less than the complete font
+    let full_size = std::fs::metadata(Path::new(env!("CARGO_MANIFEST_DIR")).join("fonts/DejaVuSans/DejaVuSans.ttf"))?.len();
+    assert!(std::fs::metadata(&pdf_path)?.len() < full_size);
+
+    Ok(())
+}
+
+#[test]
+fn test_font_selection() -> Result<()> {
+    // Set up test environment
+    let (temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+    let bundled_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fonts/DejaVuSans");
+
+    // The variants of a font directory are told apart by their style, not their file names
+    let files = font_loader::find_font_in_dir(&bundled_dir)?;
+    assert_eq!(files.family, "DejaVu Sans");
+    assert_eq!(files.regular, bundled_dir.join("DejaVuSans.ttf"));
+    assert_eq!(files.bold, bundled_dir.join("DejaVuSans-Bold.ttf"));
+    assert_eq!(files.italic, bundled_dir.join("DejaVuSans-Oblique.ttf"));
+    assert_eq!(files.bold_italic, bundled_dir.join("DejaVuSans-BoldOblique.ttf"));
+
+    // Family names are looked up in the font tables, even if the file names differ
+    let font_dir = temp_dir.path().join("fonts/truetype");
+    std::fs::create_dir_all(&font_dir)?;
+    for (index, name) in ["DejaVuSans.ttf", "DejaVuSans-Bold.ttf", "DejaVuSans-Oblique.ttf"].iter().enumerate() {
+        std::fs::copy(bundled_dir.join(name), font_dir.join(format!("face{}.ttf", index)))?;
+    }
+    let files = font_loader::find_font_by_name("dejavu sans", &[temp_dir.path().join("fonts")])?;
+    assert_eq!(files.bold, font_dir.join("face1.ttf"));
+    // A missing bold italic falls back to the closest face, keeping the slant
+    assert_eq!(files.bold_italic, font_dir.join("face2.ttf"));
+    assert!(font_loader::find_font_by_name("Missing Sans", &[temp_dir.path().join("fonts")]).is_err());
+
+    // The font option selects the PDF font
+    let docx = Docx::new().add_paragraph(Paragraph::new().add_run(Run::new().add_text("Branded output")));
+    let docx_path = common::create_docx(&input_dir, "branded", docx)?;
+    let options = ConversionOptions { font: Some(font_dir.display().to_string()), ..Default::default() };
+    let pdf_path = converter::convert_to_pdf_with_options(&docx_path, &output_dir, &options)?;
+    assert_eq!(pdf_path, common::verify_pdf_output(&output_dir, "branded")?);
+
+    // Font files resolved up front are used without looking up the font again
+    let options = ConversionOptions {
+        font: Some("Missing Sans".to_string()),
+        font_files: Some(font_loader::find_font_in_dir(&bundled_dir)?),
+        ..Default::default()
+    };
+    converter::convert_to_pdf_with_options(&docx_path, &output_dir, &options)?;
+
+    // The configuration file names the font too
+    let config = Config::parse("# PDF output\nfont = \"Noto Sans\"  # brand font\n")?;
+    assert_eq!(config.font.as_deref(), Some("Noto Sans"));
+    assert!(Config::parse("fonts = Roboto").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_pdf_page_options() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // The page size of the first page of a PDF, in points
+    let page_size = |pdf_path: &Path| -> Result<(f64, f64)> {
+        let pdf = lopdf::Document::load(pdf_path)?;
+        let page = pdf.get_dictionary(*pdf.get_pages().values().next().unwrap())?;
+        let media_box: Vec<f64> = page.get(b"MediaBox")?.as_array()?.iter()
+            .map(|value| value.as_f64().or_else(|_| value.as_i64().map(|v| v as f64)))
+            .collect::<std::result::Result<_, _>>()?;
+        Ok((media_box[2].round(), media_box[3].round()))
+    };
+
+    // Paper sizes, orientations and margins are parsed from their command line form
+    assert_eq!("letter".parse::<PaperSize>()?.size_mm(), (215.9, 279.4));
+    assert_eq!("250x200mm".parse::<PaperSize>()?.size_mm(), (200.0, 250.0));
+    assert!("B5".parse::<PaperSize>().is_err());
+    assert_eq!("Landscape".parse::<Orientation>()?, Orientation::Landscape);
+    assert_eq!("25,20".parse::<PageMargins>()?, PageMargins { top: 25.0, right: 20.0, bottom: 25.0, left: 20.0 });
+    assert_eq!("10,15,20,25".parse::<PageMargins>()?.left, 25.0);
+    assert!("1,2,3,4,5".parse::<PageMargins>().is_err());
+
+    // Word documents keep their section's page size and margins by default
+    let docx = Docx::new()
+        .page_size(12240, 15840)
+        .page_margin(docx_rs::PageMargin::new().top(1440).right(1440).bottom(1440).left(1440))
+        .add_paragraph(Paragraph::new().add_run(Run::new().add_text("US letter memo")));
+    let docx_path = common::create_docx(&input_dir, "memo", docx)?;
+    let setup = docx_reader::extract_content(&docx_path)?.page_setup.unwrap();
+    assert_eq!((setup.width.round(), setup.height.round()), (216.0, 279.0));
+    assert!((setup.margins.top - 25.4).abs() < 0.01);
+
+    let pdf_path = converter::convert_to_pdf(&docx_path, &output_dir)?;
+    assert_eq!(page_size(&pdf_path)?, (612.0, 792.0));
+
+    // Options override the document's page setup
+    let options = ConversionOptions {
+        pdf: PdfOptions {
+            paper_size: Some(PaperSize::A4),
+            orientation: Some(Orientation::Landscape),
+            margins: Some(PageMargins::uniform(10.0)),
+            font_size: Some(10),
+            line_spacing: Some(1.5),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let pdf_path = converter::convert_to_pdf_with_options(&docx_path, &output_dir, &options)?;
+    assert_eq!(page_size(&pdf_path)?, (842.0, 595.0));
+
+    // A set orientation keeps wide sheets on portrait pages
+    let xlsx_path = input_dir.join("wide.xlsx");
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    for col in 0..12u16 {
+        worksheet.write_string(0, col, format!("Column name {}", col))?;
+    }
+    workbook.save(&xlsx_path)?;
+    let pdf_path = converter::convert_to_pdf(&xlsx_path, &output_dir)?;
+    assert_eq!(page_size(&pdf_path)?, (842.0, 595.0));
+    let options = ConversionOptions {
+        pdf: PdfOptions { paper_size: Some(PaperSize::Letter), orientation: Some(Orientation::Portrait), ..Default::default() },
+        ..Default::default()
+    };
+    let pdf_path = converter::convert_to_pdf_with_options(&xlsx_path, &output_dir, &options)?;
+    assert_eq!(page_size(&pdf_path)?, (612.0, 792.0));
+
+    // The configuration file sets the same options
+    let config = Config::parse("paper_size = Legal\norientation = \"landscape\"\nmargins = 25,20\nfont_size = 11\nline_spacing = 1.2\n")?;
+    assert_eq!(config.pdf.paper_size, Some(PaperSize::Legal));
+    assert_eq!(config.pdf.orientation, Some(Orientation::Landscape));
+    assert_eq!(config.pdf.margins, Some(PageMargins { top: 25.0, right: 20.0, bottom: 25.0, left: 20.0 }));
+    assert_eq!((config.pdf.font_size, config.pdf.line_spacing), (Some(11), Some(1.2)));
+    assert!(Config::parse("paper_size = B5").is_err());
+
+    // Margins must leave room on the page, and line spacing and font size must be usable
+    let invalid_dir = output_dir.join("invalid");
+    std::fs::create_dir_all(&invalid_dir)?;
+    let options = ConversionOptions {
+        pdf: PdfOptions { margins: Some("200".parse()?), ..Default::default() },
+        ..Default::default()
+    };
+    assert!(options.pdf.validate().is_err());
+    assert!(converter::convert_to_pdf_with_options(&docx_path, &invalid_dir, &options).is_err());
+    assert_eq!(std::fs::read_dir(&invalid_dir)?.count(), 0);
+    let wide_margins = PdfOptions { margins: Some("20,110".parse()?), ..Default::default() };
+    assert!(wide_margins.validate().is_err());
+    let landscape = PdfOptions { orientation: Some(Orientation::Landscape), ..wide_margins };
+    assert!(landscape.validate().is_ok());
+    assert!(converter::parse_line_spacing("0").is_err());
+    assert!(converter::parse_line_spacing("NaN").is_err());
+    assert_eq!(converter::parse_line_spacing("1.5")?, 1.5);
+    assert!(Config::parse("font_size = 0").is_err());
+    assert!(Config::parse("line_spacing = -1").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_page_headers_and_footers() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // The text drawn on each page, header and footer first; unlike extracted text, this keeps digits
+    let page_lines = |pdf_path: &Path| -> Result<Vec<Vec<String>>> {
+        let pdf = lopdf::Document::load(pdf_path)?;
+        pdf.get_pages().values()
+            .map(|page| Ok(String::from_utf8_lossy(&pdf.get_page_content(*page)?)
+                .lines()
+                .filter(|line| line.ends_with("TJ"))
+                .map(str::to_string)
+                .collect()))
+            .collect()
+    };
+    let with_templates = |header: &str, footer: &str| ConversionOptions {
+        pdf: PdfOptions { header: Some(header.to_string()), footer: Some(footer.to_string()), ..Default::default() },
+        ..Default::default()
+    };
+
+    // Create a DOCX running over several pages
+    let mut docx = Docx::new();
+    for index in 1..=60 {
+        docx = docx.add_paragraph(Paragraph::new().add_run(Run::new().add_text(format!("Paragraph {}", index))));
+    }
+    let docx_path = common::create_docx(&input_dir, "packet", docx)?;
+
+    // Templates fill in the file name, title, page number and page count
+    let options = with_templates("{filename}", "{title} — page {n} of {total}");
+    let pages = page_lines(&converter::convert_to_pdf_with_options(&docx_path, &output_dir, &options)?)?;
+    let total = pages.len();
+    assert!(total > 2);
+
+    // The same lines are printed by literal templates for the second page
+    let options = with_templates("packet.docx", &format!("packet — page 2 of {}", total));
+    let expected = page_lines(&converter::convert_to_pdf_with_options(&docx_path, &output_dir, &options)?)?;
+    assert_eq!(pages[1][..2], expected[1][..2]);
+    assert_ne!(pages[0][1], expected[0][1]);
+
+    // Page count fields of Word footers are read as the page count placeholder
+    let docx = Docx::new()
+        .footer(Footer::new().add_paragraph(Paragraph::new().add_page_num(PageNum::new())))
+        .add_paragraph(Paragraph::new().add_run(Run::new().add_text("Short memo")));
+    let docx_path = common::create_docx(&input_dir, "memo", docx)?;
+    common::edit_docx_part(&docx_path, "word/footer1.xml", |xml| xml.replace("PAGE", "NUMPAGES"))?;
+    let content = docx_reader::extract_content(&docx_path)?;
+    assert!(content.footer.iter().any(|line| line.contains(docx_reader::PAGE_COUNT_PLACEHOLDER)),
+            "Footer has no page count: {:?}", content.footer);
+
+    // Text that only looks like a placeholder is printed as it is, in Word footers and in expanded fields;
+    // the body repeats the text, so each line is drawn twice
+    let docx = Docx::new()
+        .footer(Footer::new().add_paragraph(Paragraph::new().add_run(Run::new().add_text("Form {n}"))))
+        .add_paragraph(Paragraph::new().add_run(Run::new().add_text("Form {n}")))
+        .add_paragraph(Paragraph::new().add_run(Run::new().add_text("form{total}.docx")));
+    let form_path = common::create_docx(&input_dir, "form{total}", docx)?;
+    let content = docx_reader::extract_content(&form_path)?;
+    assert_eq!(content.footer, ["Form {n}"]);
+    let repeated_lines = |pdf_path: &Path| -> Result<usize> {
+        let text = lopdf::Document::load(pdf_path)?.extract_text(&[1])?;
+        let lines: Vec<&str> = text.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+        Ok(lines.iter().filter(|line| lines.iter().filter(|other| other == line).count() == 2).count())
+    };
+    assert_eq!(repeated_lines(&converter::convert_to_pdf(&form_path, &output_dir)?)?, 2);
+    let options = with_templates("{filename}", "");
+    assert_eq!(repeated_lines(&converter::convert_to_pdf_with_options(&form_path, &output_dir, &options)?)?, 2);
+
+    // An empty template leaves out the Word footer
+    let options = with_templates("", "");
+    let pages = page_lines(&converter::convert_to_pdf_with_options(&docx_path, &output_dir, &options)?)?;
+    let plain = page_lines(&converter::convert_to_pdf(&docx_path, &output_dir)?)?;
+    assert_eq!(pages[0].len() + 1, plain[0].len());
+
+    // Spreadsheets print the templates as well
+    let xlsx_path = common::create_mock_xlsx(&input_dir, "figures")?;
+    let options = with_templates("", "page {n} of {total}");
+    let pages = page_lines(&converter::convert_to_pdf_with_options(&xlsx_path, &output_dir, &options)?)?;
+    let options = with_templates("", "page 1 of 1");
+    let expected = page_lines(&converter::convert_to_pdf_with_options(&xlsx_path, &output_dir, &options)?)?;
+    assert_eq!(pages, expected);
+
+    // The configuration file sets the templates
+    let config = Config::parse("header = \"{title}\\n{date}\"\nfooter = 'page {n} of {total}'\n")?;
+    assert_eq!(config.pdf.header.as_deref(), Some("{title}\n{date}"));
+    assert_eq!(config.pdf.footer.as_deref(), Some("page {n} of {total}"));
+    // A ` #` ends an unquoted value but not a quoted one, and only a comment may follow a quote
+    let config = Config::parse("header = Page # {n}\nfooter = \"Page # {n}\"  # numbered\n")?;
+    assert_eq!(config.pdf.header.as_deref(), Some("Page"));
+    assert_eq!(config.pdf.footer.as_deref(), Some("Page # {n}"));
+    assert!(Config::parse("header = 'it''s'").is_err());
+    assert!(Config::parse("header = \"a\" b").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_pdf_outline_and_contents() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // The level, title and page number of each outline entry, in document order
+    fn outline(pdf: &lopdf::Document) -> Result<Vec<(usize, String, u32)>> {
+        let pages: std::collections::HashMap<lopdf::ObjectId, u32> = pdf.get_pages().into_iter()
+            .map(|(number, page)| (page, number))
+            .collect();
+        let root = pdf.catalog()?.get(b"Outlines")?.as_reference()?;
+        let mut entries = Vec::new();
+        let mut stack = vec![(1, pdf.get_dictionary(root)?.get(b"First")?.as_reference()?)];
+        while let Some((level, id)) = stack.pop() {
+            let item = pdf.get_dictionary(id)?;
+            let title = String::from_utf8_lossy(item.get(b"Title")?.as_str()?).to_string();
+            let page = item.get(b"Dest")?.as_array()?[0].as_reference()?;
+            entries.push((level, title, pages[&page]));
+            if let Ok(next) = item.get(b"Next").and_then(lopdf::Object::as_reference) {
+                stack.push((level, next));
+            }
+            if let Ok(first) = item.get(b"First").and_then(lopdf::Object::as_reference) {
+                stack.push((level + 1, first));
+            }
+        }
+        Ok(entries)
+    }
+
+    // Create a DOCX with nested headings spread over several pages
+    let heading = |style: &str, text: &str| Paragraph::new().style(style).add_run(Run::new().add_text(text));
+    let mut docx = Docx::new()
+        .add_style(Style::new("Heading1", StyleType::Paragraph).name("heading 1"))
+        .add_style(Style::new("Heading2", StyleType::Paragraph).name("heading 2"));
+    for (style, title) in [("Heading1", "Introduction"), ("Heading2", "Details"), ("Heading1", "Summary")] {
+        docx = docx.add_paragraph(heading(style, title));
+        for index in 1..=30 {
+            docx = docx.add_paragraph(Paragraph::new().add_run(Run::new().add_text(format!("Text {}", index))));
+        }
+    }
+    let docx_path = common::create_docx(&input_dir, "report", docx)?;
+
+    // Headings become nested outline entries pointing to their pages
+    let pdf = lopdf::Document::load(converter::convert_to_pdf(&docx_path, &output_dir)?)?;
+    let entries = outline(&pdf)?;
+    let titles: Vec<(usize, &str)> = entries.iter().map(|(level, title, _)| (*level, title.as_str())).collect();
+    assert_eq!(titles, vec![(1, "Introduction"), (2, "Details"), (1, "Summary")]);
+    assert_eq!(entries[0].2, 1);
+    assert!(entries[2].2 > entries[1].2 && entries[1].2 > entries[0].2);
+
+    // The table of contents takes the first page and links to each heading
+    let options = ConversionOptions {
+        pdf: PdfOptions { table_of_contents: true, ..Default::default() },
+        ..Default::default()
+    };
+    let pdf_with_contents = lopdf::Document::load(converter::convert_to_pdf_with_options(&docx_path, &output_dir, &options)?)?;
+    assert_eq!(pdf_with_contents.get_pages().len(), pdf.get_pages().len() + 1);
+    let shifted: Vec<u32> = outline(&pdf_with_contents)?.iter().map(|(_, _, page)| *page).collect();
+    assert_eq!(shifted, entries.iter().map(|(_, _, page)| page + 1).collect::<Vec<_>>());
+
+    let pages = pdf_with_contents.get_pages();
+    let first_page = pdf_with_contents.get_dictionary(pages[&1])?;
+    let mut link_pages = Vec::new();
+    for annotation in first_page.get(b"Annots")?.as_array()? {
+        let annotation = pdf_with_contents.get_dictionary(annotation.as_reference()?)?;
+        let target = annotation.get(b"Dest")?.as_array()?[0].as_reference()?;
+        link_pages.extend(pages.iter().filter(|(_, page)| **page == target).map(|(number, _)| *number));
+    }
+    link_pages.sort();
+    assert_eq!(link_pages, shifted);
+
+    // Each sheet of a workbook has a bookmark
+    let xlsx_path = input_dir.join("workbook.xlsx");
+    let mut workbook = Workbook::new();
+    for name in ["North", "South", "East"] {
+        workbook.add_worksheet().set_name(name)?.write_string(0, 0, format!("{} region", name))?;
+    }
+    workbook.save(&xlsx_path)?;
+    let pdf = lopdf::Document::load(converter::convert_to_pdf_with_options(&xlsx_path, &output_dir, &options)?)?;
+    assert_eq!(outline(&pdf)?, vec![
+        (1, "North".to_string(), 2),
+        (1, "South".to_string(), 3),
+        (1, "East".to_string(), 4),
+    ]);
+
+    // The configuration file turns on the table of contents
+    assert!(Config::parse("toc = true")?.pdf.table_of_contents);
+    assert!(Config::parse("toc = maybe").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_document_properties() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    // Create a DOCX with a title, author and dates in its core properties and a company in its app properties
+    let docx = Docx::new().add_paragraph(Paragraph::new().add_run(Run::new().add_text("Body text")));
+    let docx_path = common::create_docx(&input_dir, "notes", docx)?;
+    common::edit_docx_part(&docx_path, "docProps/core.xml", |xml| {
+        xml.replace("<dc:creator>unknown</dc:creator>", "<dc:creator>Ada Lovelace</dc:creator>")
+            .replace("1970-01-01T00:00:00Z</dcterms:created>", "2024-03-01T09:30:00Z</dcterms:created>")
+            .replace("</cp:coreProperties>", concat!(
+                "<dc:title>Notes: &quot;Q3&quot; &amp; more</dc:title>",
+                "<dc:subject>Engines</dc:subject>",
+                "<cp:keywords>analysis, engines</cp:keywords>",
+                "</cp:coreProperties>",
+            ))
+    })?;
+    common::edit_docx_part(&docx_path, "docProps/app.xml", |xml| {
+        xml.replace(" />", "><Company>Analytical Engines</Company></Properties>")
+    })?;
+
+    let properties = metadata::read_properties(&docx_path)?;
+    assert_eq!(properties, DocumentProperties {
+        title: Some("Notes: \"Q3\" & more".to_string()),
+        author: Some("Ada Lovelace".to_string()),
+        subject: Some("Engines".to_string()),
+        keywords: Some("analysis, engines".to_string()),
+        created: Some("2024-03-01T09:30:00Z".to_string()),
+        modified: Some("1970-01-01T00:00:00Z".to_string()),
+        company: Some("Analytical Engines".to_string()),
+    });
+
+    // The properties fill in the information dictionary and the XMP metadata of the PDF
+    let pdf = lopdf::Document::load(converter::convert_to_pdf(&docx_path, &output_dir)?)?;
+    let info = pdf.get_dictionary(pdf.trailer.get(b"Info")?.as_reference()?)?;
+    let info_text = |key: &[u8]| -> Result<String> { Ok(String::from_utf8_lossy(info.get(key)?.as_str()?).to_string()) };
+    assert_eq!(info_text(b"Title")?, "Notes: \"Q3\" & more");
+    assert_eq!(info_text(b"Author")?, "Ada Lovelace");
+    assert_eq!(info_text(b"Subject")?, "Engines");
+    assert_eq!(info_text(b"Keywords")?, "analysis, engines");
+    assert_eq!(info_text(b"CreationDate")?, "D:20240301093000Z");
+    assert!(info_text(b"Producer")?.starts_with("Aqon "));
+
+    let xmp = pdf.get_object(pdf.catalog()?.get(b"Metadata")?.as_reference()?)?.as_stream()?;
+    let xmp = String::from_utf8(xmp.content.clone())?;
+    assert!(xmp.contains("<rdf:li xml:lang=\"x-default\">Notes: &quot;Q3&quot; &amp; more</rdf:li>"));
+    assert!(xmp.contains("<dc:creator><rdf:Seq><rdf:li>Ada Lovelace</rdf:li></rdf:Seq></dc:creator>"));
+    assert!(xmp.contains("<xmp:CreateDate>2024-03-01T09:30:00Z</xmp:CreateDate>"));
+
+    // Markdown starts with the properties as YAML front matter, followed by the title
+    let markdown = std::fs::read_to_string(converter::convert_to_markdown(&docx_path, &output_dir)?)?;
+    assert!(markdown.starts_with(concat!(
+        "---\n",
+        "title: \"Notes: \\\"Q3\\\" & more\"\n",
+        "author: \"Ada Lovelace\"\n",
+        "subject: \"Engines\"\n",
+        "keywords: \"analysis, engines\"\n",
+        "created: \"2024-03-01T09:30:00Z\"\n",
+        "modified: \"1970-01-01T00:00:00Z\"\n",
+        "company: \"Analytical Engines\"\n",
+        "---\n\n",
+        "# Notes: \"Q3\" & more\n\n",
+    )), "{}", markdown);
+
+    // Workbook properties are read the same way
+    let xlsx_path = input_dir.join("figures.xlsx");
+    let mut workbook = Workbook::new();
+    workbook.set_properties(&DocProperties::new().set_title("Quarterly figures").set_author("Grace Hopper").set_company("Navy"));
+    workbook.add_worksheet().write_string(0, 0, "Revenue")?;
+    workbook.save(&xlsx_path)?;
+
+    let pdf = lopdf::Document::load(converter::convert_to_pdf(&xlsx_path, &output_dir)?)?;
+    let info = pdf.get_dictionary(pdf.trailer.get(b"Info")?.as_reference()?)?;
+    assert_eq!(info.get(b"Title")?.as_str()?, b"Quarterly figures");
+    assert_eq!(info.get(b"Company")?.as_str()?, b"Navy");
+    let markdown = std::fs::read_to_string(converter::convert_to_markdown(&xlsx_path, &output_dir)?)?;
+    assert!(markdown.starts_with("---\ntitle: \"Quarterly figures\"\nauthor: \"Grace Hopper\"\n"));
+    assert!(markdown.contains("company: \"Navy\"\n---\n\n"));
+    assert!(markdown.contains("# Quarterly figures\n\n"));
+
+    // W3C dates are converted to PDF dates, keeping their offset
+    assert_eq!(metadata::pdf_date("2024-03-01T09:30:00+01:00").as_deref(), Some("D:20240301093000+01'00'"));
+    // Offsets must be given as hours and minutes within a day
+    assert!(metadata::pdf_date("2024-03-01T09:30:00+0100").is_none());
+    assert!(metadata::pdf_date("2024-03-01T09:30:00+99999999:00").is_none());
+    assert!(metadata::pdf_date("2024-03-01T09:30:00-05:60").is_none());
+    assert_eq!(metadata::w3c_date("2024-03-01").as_deref(), Some("2024-03-01T00:00:00Z"));
+    assert_eq!(metadata::w3c_date("yesterday"), None);
+    // Days are checked against the length of the month, including leap years
+    assert_eq!(metadata::w3c_date("2024-02-31"), None);
+    assert_eq!(metadata::w3c_date("2023-02-29"), None);
+    assert_eq!(metadata::w3c_date("1900-02-29"), None);
+    assert!(metadata::w3c_date("2000-02-29").is_some());
+    assert!(metadata::w3c_date("2024-04-30").is_some());
+    // PDF dates are read back, with fields left out from the right
+    let parsed = metadata::Timestamp::parse_pdf("D:20240301093000+01'00'");
+    assert_eq!(parsed.map(metadata::Timestamp::to_w3c).as_deref(), Some("2024-03-01T09:30:00+01:00"));
+    assert_eq!(metadata::Timestamp::parse_pdf("D:2024").map(metadata::Timestamp::to_pdf).as_deref(), Some("D:20240101000000"));
+    assert!(metadata::Timestamp::parse_pdf("D:20240230").is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_pdf_a_output() -> Result<()> {
+    // Set up test environment
+    let (_temp_dir, input_dir, output_dir) = common::setup_test_env()?;
+
+    let docx = Docx::new()
+        .add_paragraph(Paragraph::new().add_run(Run::new().add_text("Archived report")))
+        .add_paragraph(Paragraph::new()
+            .add_hyperlink(Hyperlink::new("https://example.com/docs", HyperlinkType::External)
+                .add_run(Run::new().add_text("the documentation"))));
+    let docx_path = common::create_docx(&input_dir, "report", docx)?;
+    // A title outside ASCII is written as UTF-16 in the document information
+    common::edit_docx_part(&docx_path, "docProps/core.xml", |xml| {
+        xml.replace("</cp:coreProperties>", "<dc:title>Rapport d'été</dc:title></cp:coreProperties>")
+    })?;
+
+    // Regular PDF files carry a PDF/X output intent, which fails the check
+    let pdf = lopdf::Document::load(converter::convert_to_pdf(&docx_path, &output_dir)?)?;
+    assert!(!pdf_archive::check(&pdf).is_empty());
+
+    let options = ConversionOptions {
+        pdf: PdfOptions { pdf_a: true, ..Default::default() },
+        ..Default::default()
+    };
+    let pdf_path = converter::convert_to_pdf_with_options(&docx_path, &output_dir, &options)?;
+
+    // The header is followed by a comment of binary characters
+    let bytes = std::fs::read(&pdf_path)?;
+    assert!(bytes.starts_with(b"%PDF-1.7\n%"));
+    assert!(bytes[10..14].iter().all(|byte| *byte > 127));
+
+    let pdf = lopdf::Document::load(&pdf_path)?;
+    assert_eq!(pdf_archive::check(&pdf), Vec::<String>::new());
+
+    // The catalog has a single sRGB output intent and PDF/A identification in its metadata
+    let catalog = pdf.catalog()?;
+    let intents = catalog.get(b"OutputIntents")?.as_array()?;
+    assert_eq!(intents.len(), 1);
+    let intent = intents[0].as_dict()?;
+    assert_eq!(intent.get(b"S")?.as_name()?, b"GTS_PDFA1");
+    let xmp = pdf.get_object(catalog.get(b"Metadata")?.as_reference()?)?.as_stream()?;
+    let xmp = String::from_utf8(xmp.content.clone())?;
+    assert!(xmp.contains("<pdfaid:part>2</pdfaid:part>"));
+    assert!(xmp.contains("<pdfaid:conformance>B</pdfaid:conformance>"));
+
+    // Fonts are subset, which is marked by a tag before their name
+    let fonts: Vec<&lopdf::Dictionary> = pdf.objects.values()
+        .filter_map(|object| object.as_dict().ok())
+        .filter(|dict| dict.type_is(b"Font") && matches!(dict.get(b"Subtype").and_then(lopdf::Object::as_name), Ok(b"Type0")))
+        .collect();
+    assert!(!fonts.is_empty());
+    for font in fonts {
+        let name = font.get(b"BaseFont")?.as_name_str()?;
+        assert_eq!(name.find('+'), Some(6), "{}", name);
+        let descendant = &font.get(b"DescendantFonts")?.as_array()?[0];
+        let descendant = pdf.dereference(descendant)?.1.as_dict()?;
+        assert_eq!(descendant.get(b"CIDToGIDMap")?.as_name()?, b"Identity");
+    }
+
+    // The document information must match the XMP metadata, with dates compared as points in time
+    let mut pdf = pdf;
+    let info = pdf.trailer.get(b"Info")?.as_reference()?;
+    let info = pdf.get_object_mut(info)?.as_dict_mut()?;
+    info.set("ModDate", lopdf::Object::string_literal("D:19700101010000+01'00'"));
+    assert_eq!(pdf_archive::check(&pdf), Vec::<String>::new());
+    let info = pdf.trailer.get(b"Info")?.as_reference()?;
+    let info = pdf.get_object_mut(info)?.as_dict_mut()?;
+    info.set("Title", lopdf::Object::string_literal("Summer report"));
+    info.set("CreationDate", lopdf::Object::string_literal("D:20240301"));
+    assert_eq!(pdf_archive::check(&pdf), vec![
+        "The Title of the document information does not match dc:title in the XMP metadata".to_string(),
+        "The CreationDate of the document information does not match xmp:CreateDate in the XMP metadata".to_string(),
+    ]);
+
+    // Workbooks are archived the same way
+    let xlsx_path = common::create_mock_xlsx(&input_dir, "figures")?;
+    let pdf_path = converter::convert_to_pdf_with_options(&xlsx_path, &output_dir, &options)?;
+    assert_eq!(pdf_archive::check(&lopdf::Document::load(&pdf_path)?), Vec::<String>::new());
+
+    // The configuration file enables the mode as well
+    assert!(Config::parse("pdf_a = true")?.pdf.pdf_a);
+    assert!(Config::parse("pdf_a = yes").is_err());
+
+    Ok(())
+}